@@ -0,0 +1,228 @@
+#!/usr/bin/env python3
+"""Exports the guesslang TensorFlow model to ONNX for the `onnx` feature.
+
+The TensorFlow graph tokenizes the raw snippet with string ops that ONNX
+can't express. The exported graph therefore starts at the hashed n-gram
+counts, which skriptorium computes itself, and rebuilds the rest of the
+estimator (linear part, mean embedding, hidden layers and softmax) from the
+checkpoint variables.
+
+    pip install guesslang onnx numpy
+    python export_guesslang_onnx.py --out guesslang-onnx
+
+This writes:
+
+    onnx/model.onnx          n-gram counts [1, buckets] -> scores [1, classes]
+    onnx/preprocessing.json  tokenizer settings and the label vocabulary
+    onnx/parity.json         top-1 of the TensorFlow model for each file of
+                             the fixture corpus
+
+Before writing anything, the exported graph is run on the fixture corpus
+and the export fails unless its top-1 matches TensorFlow for every file.
+Afterwards, point skriptorium at the output folder (or at a server hosting
+it) once:
+
+    skriptorium scribe --model-url guesslang-onnx
+
+`cargo test --features onnx -- --ignored` then checks that the Rust
+tokenizer and tract reproduce `parity.json`.
+"""
+
+import argparse
+import json
+import pathlib
+import re
+import sys
+
+import numpy as np
+import onnx
+import tensorflow as tf
+from onnx import TensorProto, helper, numpy_helper
+from onnx.reference import ReferenceEvaluator
+
+import guesslang
+from guesslang import model as guesslang_model
+
+CORPUS = pathlib.Path(__file__).resolve().parent.parent / "tests" / "fixtures" / "guesslang"
+
+OPSET = 11
+
+
+def setting(value, name):
+    """Prefers the command line value, then the constant of guesslang.model."""
+    if value is not None:
+        return value
+    if hasattr(guesslang_model, name):
+        return getattr(guesslang_model, name)
+    sys.exit(f"guesslang.model has no {name}, please pass it explicitly.")
+
+
+def variable(reader, names, pattern):
+    matches = [name for name in names if re.search(pattern, name)]
+    if len(matches) != 1:
+        sys.exit(
+            f"Expected one variable matching {pattern}, found {matches}.\n"
+            f"Variables: {sorted(names)}"
+        )
+    return reader.get_tensor(matches[0]).astype(np.float32)
+
+
+def load_weights(model_dir):
+    reader = tf.train.load_checkpoint(str(model_dir / "variables" / "variables"))
+    names = list(reader.get_variable_to_shape_map())
+    hidden_layers = sorted(
+        {int(m.group(1)) for m in map(re.compile(r"dnn/hiddenlayer_(\d+)/kernel$").search, names) if m}
+    )
+    return {
+        "linear_weights": variable(reader, names, r"linear_model/content/weights$"),
+        "linear_bias": variable(reader, names, r"linear_model/bias_weights$"),
+        "embedding": variable(reader, names, r"content_embedding/embedding_weights$"),
+        "hidden": [
+            (
+                variable(reader, names, rf"dnn/hiddenlayer_{i}/kernel$"),
+                variable(reader, names, rf"dnn/hiddenlayer_{i}/bias$"),
+            )
+            for i in hidden_layers
+        ],
+        "logits": (
+            variable(reader, names, r"dnn/logits/kernel$"),
+            variable(reader, names, r"dnn/logits/bias$"),
+        ),
+    }
+
+
+def build_graph(weights, buckets, classes):
+    """Dense equivalent of the DNNLinearCombinedClassifier on hashed counts.
+
+    The linear part sums the weights of every n-gram, the embedding column
+    averages them (`combiner="mean"`), the hidden layers use ReLU and the
+    scores are the softmax of the summed logits.
+    """
+    initializers = []
+    nodes = []
+
+    def constant(name, array):
+        initializers.append(numpy_helper.from_array(array, name))
+        return name
+
+    def node(op, inputs, output, **attributes):
+        nodes.append(helper.make_node(op, inputs, [output], **attributes))
+        return output
+
+    counts = "counts"
+    linear = node(
+        "Gemm",
+        [counts, constant("linear_weights", weights["linear_weights"]),
+         constant("linear_bias", weights["linear_bias"])],
+        "linear_logits",
+    )
+
+    total = node("ReduceSum", [counts], "total", axes=[1], keepdims=1)
+    total = node("Max", [total, constant("one", np.ones((1, 1), np.float32))], "total_or_one")
+    embedded = node("MatMul", [counts, constant("embedding", weights["embedding"])], "embedded")
+    hidden = node("Div", [embedded, total], "embedding_mean")
+
+    for i, (kernel, bias) in enumerate(weights["hidden"]):
+        hidden = node(
+            "Gemm",
+            [hidden, constant(f"hidden_{i}_kernel", kernel), constant(f"hidden_{i}_bias", bias)],
+            f"hidden_{i}",
+        )
+        hidden = node("Relu", [hidden], f"hidden_{i}_relu")
+
+    kernel, bias = weights["logits"]
+    dnn = node(
+        "Gemm",
+        [hidden, constant("dnn_logits_kernel", kernel), constant("dnn_logits_bias", bias)],
+        "dnn_logits",
+    )
+    logits = node("Add", [linear, dnn], "logits")
+    node("Softmax", [logits], "scores", axis=1)
+
+    graph = helper.make_graph(
+        nodes,
+        "guesslang",
+        [helper.make_tensor_value_info(counts, TensorProto.FLOAT, [1, buckets])],
+        [helper.make_tensor_value_info("scores", TensorProto.FLOAT, [1, len(classes)])],
+        initializers,
+    )
+    model = helper.make_model(graph, opset_imports=[helper.make_opsetid("", OPSET)])
+    onnx.checker.check_model(model)
+    return model
+
+
+def token_counts(snippet, preprocessing):
+    """The same tokenization as `_preprocess_text` and the hashed column."""
+    tokens = tf.strings.bytes_split(tf.constant(snippet))
+    ngrams = tf.strings.ngrams(tokens, preprocessing["ngram_width"])
+    ngrams = ngrams[: preprocessing["max_tokens"]]
+    buckets = tf.strings.to_hash_bucket_fast(ngrams, preprocessing["buckets"]).numpy()
+    return np.bincount(buckets, minlength=preprocessing["buckets"]).astype(np.float32)[None, :]
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--out", required=True, type=pathlib.Path, help="output folder")
+    parser.add_argument(
+        "--model",
+        type=pathlib.Path,
+        default=pathlib.Path(guesslang.__file__).parent / "data" / "model",
+        help="guesslang SavedModel folder (default: the one of the installed package)",
+    )
+    parser.add_argument("--corpus", type=pathlib.Path, default=CORPUS, help="fixture corpus")
+    parser.add_argument("--ngram-width", type=int, help="default: guesslang.model.N_GRAM")
+    parser.add_argument("--max-tokens", type=int, help="default: guesslang.model.NB_TOKENS")
+    parser.add_argument("--buckets", type=int, help="default: guesslang.model.VOCABULARY_SIZE")
+    args = parser.parse_args()
+
+    saved_model = tf.saved_model.load(str(args.model))
+    serve = saved_model.signatures["serving_default"]
+
+    def tensorflow_scores(snippet):
+        outputs = serve(inputs=tf.constant([snippet]))
+        classes = [c.decode("utf-8") for c in outputs["classes"].numpy()[0]]
+        return classes, outputs["scores"].numpy()[0]
+
+    # The order of the `classes` output is the label vocabulary, which is
+    # also the order of the logits.
+    classes, _ = tensorflow_scores("")
+    preprocessing = {
+        "classes": classes,
+        "ngram_width": setting(args.ngram_width, "N_GRAM"),
+        "max_tokens": setting(args.max_tokens, "NB_TOKENS"),
+        "buckets": setting(args.buckets, "VOCABULARY_SIZE"),
+    }
+
+    weights = load_weights(args.model)
+    if weights["embedding"].shape[0] != preprocessing["buckets"]:
+        sys.exit(
+            f"The embedding has {weights['embedding'].shape[0]} rows but "
+            f"{preprocessing['buckets']} buckets are configured, pass --buckets."
+        )
+    model = build_graph(weights, preprocessing["buckets"], classes)
+    evaluator = ReferenceEvaluator(model)
+
+    parity = {}
+    mismatches = []
+    for path in sorted(p for p in args.corpus.iterdir() if p.is_file()):
+        snippet = path.read_text(encoding="utf-8")
+        tf_classes, tf_scores = tensorflow_scores(snippet)
+        (onnx_scores,) = evaluator.run(None, {"counts": token_counts(snippet, preprocessing)})
+        expected = tf_classes[int(np.argmax(tf_scores))]
+        actual = classes[int(np.argmax(onnx_scores[0]))]
+        parity[path.name] = expected
+        if expected != actual:
+            mismatches.append(f"{path.name}: TensorFlow {expected}, ONNX {actual}")
+    if mismatches:
+        sys.exit("The exported model disagrees with TensorFlow:\n" + "\n".join(mismatches))
+
+    out = args.out / "onnx"
+    out.mkdir(parents=True, exist_ok=True)
+    onnx.save(model, str(out / "model.onnx"))
+    (out / "preprocessing.json").write_text(json.dumps(preprocessing, indent=2) + "\n")
+    (out / "parity.json").write_text(json.dumps(parity, indent=2, sort_keys=True) + "\n")
+    print(f"Exported {len(classes)} classes to {out}, {len(parity)} fixtures agree.")
+
+
+if __name__ == "__main__":
+    main()
@@ -1,46 +1,178 @@
 extern crate base64;
 
+use crate::api::client;
+use crate::error::Error;
 use crate::types::Result;
 use base64::encode;
-use log::info;
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::path;
-
-#[derive(Debug)]
-pub enum ClassificationError {
-    ClassificationFailed,
-}
-
-impl std::error::Error for ClassificationError {}
-
-impl fmt::Display for ClassificationError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ClassificationError::ClassificationFailed => write!(f, "Classification failed."),
-        }
-    }
-}
+use std::collections;
 
 #[derive(Serialize, Deserialize, Debug)]
-struct ClassificationFile {
+pub struct ClassificationFile {
     name: Option<String>,
     content: String,
 }
 
+/// Latest classification schema this client understands. Version 1 only has
+/// `name`, `tldr`, `usage`, `version` and `license`, version 2 adds the
+/// optional README sections.
+pub const SCHEMA_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ClassificationRequest {
     language: Option<String>,
     files: Vec<ClassificationFile>,
+    schema_version: u32,
+    /// Documentation and API reference of the project, as plain text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    /// How many alternatives to return per section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidates: Option<usize>,
+}
+
+fn default_schema_version() -> u32 {
+    1
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Classification {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     pub name: String,
     pub tldr: String,
     pub usage: String,
     pub version: Option<String>,
     pub license: Option<String>,
+    #[serde(default)]
+    pub installation: Option<String>,
+    #[serde(default)]
+    pub features: Option<Vec<String>>,
+    #[serde(default)]
+    pub configuration: Option<String>,
+    #[serde(default)]
+    pub examples: Option<String>,
+    #[serde(default)]
+    pub contributing: Option<String>,
+    /// Alternatives per section when they were asked for, texts or for
+    /// `features` lists.
+    #[serde(default)]
+    pub candidates: Option<collections::BTreeMap<String, Vec<serde_json::Value>>>,
+}
+
+fn has_text(section: &Option<String>) -> bool {
+    section
+        .as_ref()
+        .map(|text| !text.trim().is_empty())
+        .unwrap_or(false)
+}
+
+/// The sections of a classification, in the order they appear in the README.
+pub const SECTIONS: [&str; 10] = [
+    "name",
+    "tldr",
+    "features",
+    "installation",
+    "usage",
+    "configuration",
+    "examples",
+    "contributing",
+    "version",
+    "license",
+];
+
+impl Classification {
+    /// The text of a section, features one per line. Empty sections are none.
+    pub fn section(&self, section: &str) -> Option<String> {
+        let text = match section {
+            "name" => Some(self.name.clone()),
+            "tldr" => Some(self.tldr.clone()),
+            "usage" => Some(self.usage.clone()),
+            "version" => self.version.clone(),
+            "license" => self.license.clone(),
+            "installation" => self.installation.clone(),
+            "features" => self.features.as_ref().map(|f| f.join("\n")),
+            "configuration" => self.configuration.clone(),
+            "examples" => self.examples.clone(),
+            "contributing" => self.contributing.clone(),
+            _ => None,
+        };
+        text.filter(|text| !text.trim().is_empty())
+    }
+
+    /// The alternatives returned for a section, as texts like `section`.
+    pub fn candidates(&self, section: &str) -> Vec<String> {
+        let candidates = match self.candidates.as_ref().and_then(|c| c.get(section)) {
+            Some(candidates) => candidates,
+            None => return Vec::new(),
+        };
+        candidates
+            .iter()
+            .filter_map(|candidate| match candidate {
+                serde_json::Value::String(text) => Some(text.clone()),
+                serde_json::Value::Array(items) => Some(
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_str())
+                        .collect::<Vec<&str>>()
+                        .join("\n"),
+                ),
+                _ => None,
+            })
+            .filter(|text| !text.trim().is_empty())
+            .collect()
+    }
+
+    /// Replaces the text of a section. Required sections are emptied instead
+    /// of removed, the README leaves out empty sections.
+    pub fn set_section(&mut self, section: &str, text: Option<String>) {
+        match section {
+            "name" => self.name = text.unwrap_or_default(),
+            "tldr" => self.tldr = text.unwrap_or_default(),
+            "usage" => self.usage = text.unwrap_or_default(),
+            "version" => self.version = text,
+            "license" => self.license = text,
+            "installation" => self.installation = text,
+            "features" => {
+                self.features = text.map(|text| {
+                    text.lines()
+                        .map(|line| line.trim().trim_start_matches("- ").to_string())
+                        .filter(|line| !line.is_empty())
+                        .collect()
+                })
+            }
+            "configuration" => self.configuration = text,
+            "examples" => self.examples = text,
+            "contributing" => self.contributing = text,
+            _ => {}
+        }
+    }
+
+    /// Names of the optional sections the response contains.
+    pub fn sections(&self) -> Vec<&'static str> {
+        let mut sections = Vec::new();
+        if self
+            .features
+            .as_ref()
+            .map(|f| !f.is_empty())
+            .unwrap_or(false)
+        {
+            sections.push("features");
+        }
+        if has_text(&self.installation) {
+            sections.push("installation");
+        }
+        if has_text(&self.configuration) {
+            sections.push("configuration");
+        }
+        if has_text(&self.examples) {
+            sections.push("examples");
+        }
+        if has_text(&self.contributing) {
+            sections.push("contributing");
+        }
+        sections
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -54,48 +186,215 @@ pub struct Selection {
     pub files: Vec<String>,
 }
 
-pub async fn classify(
-    maybe_language: Option<String>,
-    files: Vec<(Option<String>, String)>,
-) -> Result<Classification> {
-    let client = reqwest::Client::new();
-    let encoded_files: Vec<ClassificationFile> = files
+pub fn encode_files(files: &[(Option<String>, String)]) -> Vec<ClassificationFile> {
+    files
         .iter()
         .map(|(name, content)| ClassificationFile {
             name: name.clone(),
             content: encode(content.trim()),
         })
-        .collect();
+        .collect()
+}
+
+pub async fn classify(
+    client: &client::ApiClient,
+    maybe_language: Option<String>,
+    files: Vec<(Option<String>, String)>,
+    context: Option<String>,
+    candidates: Option<usize>,
+) -> Result<Classification> {
     let request = ClassificationRequest {
         language: maybe_language,
-        files: encoded_files,
+        files: encode_files(&files),
+        schema_version: SCHEMA_VERSION,
+        context,
+        candidates,
     };
-    let request = serde_json::to_string(&request)?;
-    info!("Sending request {}", request);
-    let response = client
-        .post("http://localhost:8080/api/v1/classification")
-        .body(request)
-        .send()
-        .await?
-        .json::<Classification>()
-        .await?;
-    Ok(response)
-}
-
-pub async fn select(maybe_language: Option<String>, files: Vec<String>) -> Result<Selection> {
-    let client = reqwest::Client::new();
+    client.post("classification", &request).await
+}
+
+/// The body of the last classification request of the run, not counting
+/// localized or regenerated ones.
+fn main_request(client: &client::ApiClient) -> Result<serde_json::Value> {
+    client
+        .recorded_requests()
+        .into_iter()
+        .rev()
+        .filter(|request| request.endpoint == "classification")
+        .map(|request| request.body)
+        .find(|body| body.get("locale").is_none() && body.get("section").is_none())
+        .ok_or_else(|| {
+            Error::config(
+                "There is no classification to start from.",
+                Some("Localized READMEs and regenerated sections come after the classification step."),
+            )
+        })
+}
+
+/// Sends the main classification request again with one more field.
+async fn classify_again(
+    client: &client::ApiClient,
+    field: &str,
+    value: &str,
+) -> Result<Classification> {
+    let mut request = main_request(client)?;
+    if let Some(body) = request.as_object_mut() {
+        body.insert(field.to_string(), serde_json::Value::from(value));
+    }
+    client.post("classification", &request).await
+}
+
+/// Asks for the last classification again, written in another language. The
+/// files of the recorded request are sent unchanged together with the
+/// `locale`, so that every language describes the same selection.
+pub async fn classify_localized(
+    client: &client::ApiClient,
+    locale: &str,
+) -> Result<Classification> {
+    classify_again(client, "locale", locale).await
+}
+
+/// Asks for the last classification again with `section` naming the one
+/// section that should be written anew, e.g. `usage`.
+pub async fn regenerate_section(
+    client: &client::ApiClient,
+    section: &str,
+) -> Result<Classification> {
+    classify_again(client, "section", section).await
+}
+
+pub async fn select(
+    client: &client::ApiClient,
+    maybe_language: Option<String>,
+    files: Vec<String>,
+) -> Result<Selection> {
     let request = SelectionRequest {
         language: maybe_language,
-        files: files,
+        files,
     };
-    let request = serde_json::to_string(&request)?;
-    info!("Sending request {}", request);
-    let response = client
-        .post("http://localhost:8080/api/v1/select-files")
-        .body(request)
-        .send()
-        .await?
-        .json::<Selection>()
-        .await?;
-    Ok(response)
+    client.post("select-files", &request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::stub::{self, Reply};
+
+    const RESPONSE: &[u8] =
+        br#"{"name": "cli", "tldr": "Does things.", "usage": "", "version": null, "license": null}"#;
+
+    async fn stub_client() -> (stub::StubServer, client::ApiClient) {
+        let server = stub::serve(|_, _| Reply::ok(RESPONSE)).await;
+        let client =
+            client::ApiClient::with_base_url(stub::client(0), server.url.trim_end_matches('/'));
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn localized_requests_repeat_the_main_request() {
+        let (server, client) = stub_client().await;
+        let files = vec![(Some("src/main.rs".to_string()), "fn main() {}".to_string())];
+        classify(&client, Some("rs".to_string()), files, None, None)
+            .await
+            .unwrap();
+        classify_localized(&client, "de").await.unwrap();
+        classify_localized(&client, "ja").await.unwrap();
+
+        let requests = client.recorded_requests();
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(requests[1].body["locale"], "de");
+        assert_eq!(requests[2].body["locale"], "ja");
+        // The Japanese README starts from the main request, not the German one.
+        assert_eq!(requests[2].body["files"], requests[0].body["files"]);
+        assert_eq!(requests[2].body["language"], "rs");
+    }
+
+    #[tokio::test]
+    async fn regenerated_sections_are_named_in_the_request() {
+        let (_server, client) = stub_client().await;
+        classify(&client, None, Vec::new(), Some("docs".to_string()), None)
+            .await
+            .unwrap();
+        regenerate_section(&client, "usage").await.unwrap();
+        classify_localized(&client, "de").await.unwrap();
+
+        let requests = client.recorded_requests();
+        assert_eq!(requests[1].body["section"], "usage");
+        assert_eq!(requests[1].body["context"], "docs");
+        // Localizing starts from the main request, without the section.
+        assert!(requests[2].body.get("section").is_none());
+    }
+
+    #[tokio::test]
+    async fn localizing_needs_a_classification() {
+        let (_server, client) = stub_client().await;
+        assert!(classify_localized(&client, "de").await.is_err());
+    }
+
+    #[test]
+    fn version_one_responses_are_accepted() {
+        let classification: Classification = serde_json::from_str(
+            r#"{"name": "cli", "tldr": "Does things.", "usage": "cli run", "version": null, "license": "MIT"}"#,
+        )
+        .unwrap();
+        assert_eq!(classification.schema_version, 1);
+        assert!(classification.sections().is_empty());
+        assert_eq!(classification.section("license").as_deref(), Some("MIT"));
+        assert_eq!(classification.section("version"), None);
+    }
+
+    #[test]
+    fn sections_skip_blank_text() {
+        let classification: Classification = serde_json::from_str(
+            r#"{
+                "schema_version": 2,
+                "name": "cli", "tldr": "Does things.", "usage": " ",
+                "installation": "cargo install cli",
+                "features": ["fast", "small"],
+                "configuration": "  ",
+                "examples": null
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(classification.sections(), vec!["features", "installation"]);
+        assert_eq!(classification.section("usage"), None);
+        assert_eq!(
+            classification.section("features").as_deref(),
+            Some("fast\nsmall")
+        );
+        assert_eq!(classification.section("unknown"), None);
+    }
+
+    #[test]
+    fn set_section_round_trips_features() {
+        let mut classification: Classification = serde_json::from_str(
+            r#"{"name": "cli", "tldr": "", "usage": "", "version": null, "license": null}"#,
+        )
+        .unwrap();
+        classification.set_section("features", Some("- fast\n\n- small\n".to_string()));
+        assert_eq!(
+            classification.features,
+            Some(vec!["fast".to_string(), "small".to_string()])
+        );
+        classification.set_section("name", None);
+        assert_eq!(classification.name, "");
+        assert_eq!(classification.section("name"), None);
+    }
+
+    #[test]
+    fn candidates_flatten_feature_lists() {
+        let classification: Classification = serde_json::from_str(
+            r#"{
+                "name": "cli", "tldr": "", "usage": "", "version": null, "license": null,
+                "candidates": {
+                    "tldr": ["One.", "", "Two."],
+                    "features": [["fast", "small"], 3]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(classification.candidates("tldr"), vec!["One.", "Two."]);
+        assert_eq!(classification.candidates("features"), vec!["fast\nsmall"]);
+        assert!(classification.candidates("usage").is_empty());
+    }
 }
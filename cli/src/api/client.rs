@@ -0,0 +1,186 @@
+use crate::error::Error;
+use crate::http;
+use crate::types::Result;
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync;
+
+const API_URL: &str = "http://localhost:8080/api/v1";
+
+/// Error body returned by the API, e.g. `{ "error": "Unable to parse request format." }`.
+#[derive(Deserialize, Debug)]
+pub struct ApiError {
+    pub error: String,
+}
+
+impl std::error::Error for ApiError {}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// A request as it was sent, kept for the run manifest so it can be replayed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedRequest {
+    pub endpoint: String,
+    pub body: serde_json::Value,
+    /// Whether the request was answered from a local cache instead of being sent.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    http_client: http::HttpClient,
+    base_url: String,
+    recorded_requests: sync::Arc<sync::Mutex<Vec<RecordedRequest>>>,
+}
+
+impl ApiClient {
+    pub fn new(http_client: http::HttpClient) -> ApiClient {
+        ApiClient::with_base_url(http_client, API_URL)
+    }
+
+    pub fn with_base_url(http_client: http::HttpClient, base_url: &str) -> ApiClient {
+        ApiClient {
+            http_client,
+            base_url: base_url.to_string(),
+            recorded_requests: sync::Arc::new(sync::Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// All requests sent by this client and its clones, in the order they were sent.
+    pub fn recorded_requests(&self) -> Vec<RecordedRequest> {
+        self.recorded_requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, endpoint: &str, body: serde_json::Value, cached: bool) {
+        if let Ok(mut requests) = self.recorded_requests.lock() {
+            requests.push(RecordedRequest {
+                endpoint: endpoint.to_string(),
+                body,
+                cached,
+            });
+        }
+    }
+
+    /// Records a request that was answered locally, e.g. from the summary
+    /// cache, so that the run manifest still lists it.
+    pub fn record_cached<Request: Serialize>(&self, endpoint: &str, request: &Request) {
+        if let Ok(value) = serde_json::to_value(request) {
+            self.record(endpoint, value, true);
+        }
+    }
+
+    pub async fn post<Request, Response>(
+        &self,
+        endpoint: &str,
+        request: &Request,
+    ) -> Result<Response>
+    where
+        Request: Serialize,
+        Response: DeserializeOwned,
+    {
+        let url = format!("{}/{}", self.base_url, endpoint);
+        let value = serde_json::to_value(request).map_err(|e| Error::http(&url, None, e))?;
+        let body = value.to_string();
+        info!("Sending request {}", body);
+        self.record(endpoint, value, false);
+
+        let response = self
+            .http_client
+            .send(|| {
+                self.http_client
+                    .post(&url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            response
+                .json::<Response>()
+                .await
+                .map_err(|e| Error::http(&url, Some(status.as_u16()), e))
+        } else {
+            let text = response.text().await.unwrap_or_default();
+            let api_error = serde_json::from_str::<ApiError>(&text).unwrap_or_else(|_| ApiError {
+                error: status
+                    .canonical_reason()
+                    .unwrap_or("Unexpected response")
+                    .to_string(),
+            });
+            Err(Error::http(&url, Some(status.as_u16()), api_error))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+    use crate::http::stub::{self, Reply};
+
+    async fn post_to(reply: fn() -> Reply) -> Result<serde_json::Value> {
+        let server = stub::serve(move |_, _| reply()).await;
+        let client = ApiClient::with_base_url(stub::client(0), server.url.trim_end_matches('/'));
+        client
+            .post::<_, serde_json::Value>("classify", &serde_json::json!({}))
+            .await
+    }
+
+    #[tokio::test]
+    async fn error_bodies_are_decoded_into_the_error() {
+        let error = post_to(|| Reply::Response {
+            status: 500,
+            headers: Vec::new(),
+            body: br#"{"error": "Unable to parse request format."}"#.to_vec(),
+        })
+        .await
+        .unwrap_err();
+        match &error {
+            Error::Http {
+                status: Some(500),
+                url: Some(url),
+                source,
+            } => {
+                assert!(url.ends_with("/classify"));
+                let api_error = source.downcast_ref::<ApiError>().unwrap();
+                assert_eq!(api_error.error, "Unable to parse request format.");
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(error::report(&error, false).contains("Cause: Unable to parse request format."));
+    }
+
+    #[tokio::test]
+    async fn other_error_bodies_fall_back_to_the_status_reason() {
+        let error = post_to(|| Reply::status(404)).await.unwrap_err();
+        assert!(error::report(&error, false).contains("Cause: Not Found"));
+    }
+
+    #[tokio::test]
+    async fn undecodable_responses_keep_their_status() {
+        let error = post_to(|| Reply::ok(b"<html>")).await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Http {
+                status: Some(200),
+                ..
+            }
+        ));
+        assert!(!error::report(&error, false).contains("--proxy"));
+    }
+}
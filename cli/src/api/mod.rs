@@ -1 +1,3 @@
 pub mod classification;
+pub mod client;
+pub mod summary;
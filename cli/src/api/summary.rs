@@ -0,0 +1,43 @@
+use crate::api::classification;
+use crate::api::client;
+use crate::types::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SummaryRequest {
+    language: Option<String>,
+    files: Vec<classification::ClassificationFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Summary {
+    pub summary: String,
+}
+
+fn summary_request(
+    maybe_language: Option<String>,
+    files: &[(Option<String>, String)],
+) -> SummaryRequest {
+    SummaryRequest {
+        language: maybe_language,
+        files: classification::encode_files(files),
+    }
+}
+
+pub async fn summarize(
+    client: &client::ApiClient,
+    maybe_language: Option<String>,
+    files: &[(Option<String>, String)],
+) -> Result<Summary> {
+    let request = summary_request(maybe_language, files);
+    client.post("summarize", &request).await
+}
+
+/// Records the request for a summary that was found in the cache.
+pub fn record_cached(
+    client: &client::ApiClient,
+    maybe_language: Option<String>,
+    files: &[(Option<String>, String)],
+) {
+    client.record_cached("summarize", &summary_request(maybe_language, files));
+}
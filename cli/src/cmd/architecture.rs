@@ -0,0 +1,227 @@
+extern crate clap;
+
+use crate::error::Error;
+use crate::extraction::imports;
+use crate::types;
+use std::collections;
+
+/// How the module graph is drawn.
+#[derive(Debug, Clone, Default)]
+pub struct ArchitectureSettings {
+    /// Modules deeper than this are drawn as their ancestor at this depth.
+    pub depth: Option<usize>,
+    pub exclude_tests: bool,
+}
+
+impl ArchitectureSettings {
+    pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<ArchitectureSettings> {
+        let depth = match matches.value_of("architecture-depth") {
+            Some(depth) => {
+                let hint = Some("--architecture-depth expects a positive number.");
+                let message = format!("Invalid architecture depth {}.", depth);
+                match depth.parse::<usize>() {
+                    Ok(0) => return Err(Error::config(&message, hint)),
+                    Ok(depth) => Some(depth),
+                    Err(e) => return Err(Error::config_caused_by(&message, hint, e)),
+                }
+            }
+            None => None,
+        };
+        Ok(ArchitectureSettings {
+            depth,
+            exclude_tests: matches.is_present("exclude-tests"),
+        })
+    }
+}
+
+fn node_label(id: &imports::ModuleId) -> String {
+    id.label().replace('"', "#quot;")
+}
+
+/// Renders the module graph as a Mermaid flowchart, or nothing if no module
+/// imports another.
+pub fn render(graph: &imports::ModuleGraph, settings: &ArchitectureSettings) -> Option<String> {
+    let collapse = |id: &imports::ModuleId| match settings.depth {
+        Some(depth) => id.collapse(depth),
+        None => id.clone(),
+    };
+    let included = |id: &imports::ModuleId| {
+        graph.modules.contains(id) && !(settings.exclude_tests && graph.test_modules.contains(id))
+    };
+
+    let nodes: collections::BTreeSet<imports::ModuleId> = graph
+        .modules
+        .iter()
+        .filter(|id| included(id))
+        .map(collapse)
+        .collect();
+    let edges: collections::BTreeSet<(imports::ModuleId, imports::ModuleId)> = graph
+        .edges
+        .iter()
+        .filter(|(from, to)| included(from) && included(to))
+        .map(|(from, to)| (collapse(from), collapse(to)))
+        .filter(|(from, to)| from != to)
+        .collect();
+    if edges.is_empty() {
+        return None;
+    }
+
+    let ids: collections::BTreeMap<&imports::ModuleId, String> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (id, format!("m{}", i)))
+        .collect();
+    let mut lines = vec!["```mermaid".to_string(), "graph LR".to_string()];
+    for (id, node) in ids.iter() {
+        lines.push(format!("    {}[\"{}\"]", node, node_label(id)));
+    }
+    for (from, to) in edges.iter() {
+        if let (Some(from), Some(to)) = (ids.get(from), ids.get(to)) {
+            lines.push(format!("    {} --> {}", from, to));
+        }
+    }
+    lines.push("```".to_string());
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(args: &[&str]) -> types::Result<ArchitectureSettings> {
+        let app = clap::App::new("test")
+            .arg(
+                clap::Arg::with_name("architecture-depth")
+                    .long("architecture-depth")
+                    .takes_value(true),
+            )
+            .arg(clap::Arg::with_name("exclude-tests").long("exclude-tests"));
+        let matches = app.get_matches_from(std::iter::once("test").chain(args.iter().copied()));
+        ArchitectureSettings::from_matches(&matches)
+    }
+
+    fn id(name: &str) -> imports::ModuleId {
+        imports::ModuleId {
+            root: "cli".to_string(),
+            segments: name
+                .split("::")
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| segment.to_string())
+                .collect(),
+            separator: "::",
+        }
+    }
+
+    fn graph(edges: &[(&str, &str)], tests: &[&str]) -> imports::ModuleGraph {
+        let mut graph = imports::ModuleGraph::default();
+        for (from, to) in edges.iter() {
+            graph.modules.insert(id(from));
+            graph.modules.insert(id(to));
+            graph.edges.insert((id(from), id(to)));
+        }
+        graph.test_modules = tests.iter().map(|name| id(name)).collect();
+        graph
+    }
+
+    #[test]
+    fn depth_must_be_positive() {
+        assert_eq!(settings(&[]).unwrap().depth, None);
+        assert_eq!(
+            settings(&["--architecture-depth=2"]).unwrap().depth,
+            Some(2)
+        );
+        assert!(settings(&["--architecture-depth=0"]).is_err());
+        assert!(settings(&["--architecture-depth=deep"]).is_err());
+        assert!(settings(&["--exclude-tests"]).unwrap().exclude_tests);
+    }
+
+    #[test]
+    fn modules_are_drawn_as_a_mermaid_graph() {
+        let graph = graph(
+            &[
+                ("", "cmd::scribe"),
+                ("cmd::scribe", "cmd::file_selection"),
+                ("cmd::file_selection", "http"),
+            ],
+            &[],
+        );
+        assert_eq!(
+            render(&graph, &ArchitectureSettings::default()).unwrap(),
+            "```mermaid\n\
+             graph LR\n    \
+             m0[\"cli\"]\n    \
+             m1[\"cmd::file_selection\"]\n    \
+             m2[\"cmd::scribe\"]\n    \
+             m3[\"http\"]\n    \
+             m0 --> m2\n    \
+             m1 --> m3\n    \
+             m2 --> m1\n\
+             ```"
+        );
+    }
+
+    #[test]
+    fn deep_modules_collapse_into_their_ancestor() {
+        let graph = graph(
+            &[
+                ("cmd::scribe", "cmd::file_selection"),
+                ("cmd::file_selection", "http"),
+            ],
+            &[],
+        );
+        let collapsed = render(
+            &graph,
+            &ArchitectureSettings {
+                depth: Some(1),
+                exclude_tests: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            collapsed,
+            "```mermaid\ngraph LR\n    m0[\"cmd\"]\n    m1[\"http\"]\n    m0 --> m1\n```"
+        );
+        // Edges within a collapsed module disappear with it.
+        let internal = imports::ModuleGraph {
+            edges: [(id("cmd::scribe"), id("cmd::file_selection"))]
+                .into_iter()
+                .collect(),
+            ..graph.clone()
+        };
+        assert_eq!(
+            render(
+                &internal,
+                &ArchitectureSettings {
+                    depth: Some(1),
+                    exclude_tests: false,
+                },
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_modules_can_be_left_out() {
+        let graph = graph(
+            &[
+                ("cmd::scribe", "http"),
+                ("cmd::scribe::tests", "cmd::scribe"),
+                ("cmd::scribe::tests", "http::stub"),
+            ],
+            &["cmd::scribe::tests", "http::stub"],
+        );
+        let all = render(&graph, &ArchitectureSettings::default()).unwrap();
+        assert!(all.contains("cmd::scribe::tests"));
+        let without_tests = render(
+            &graph,
+            &ArchitectureSettings {
+                depth: None,
+                exclude_tests: true,
+            },
+        )
+        .unwrap();
+        assert!(!without_tests.contains("tests"));
+        assert!(!without_tests.contains("stub"));
+        assert!(without_tests.ends_with("m0 --> m1\n```"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Badges drawn locally in the layout of shields.io, so that the README
+//! doesn't depend on a third party to show them.
+
+/// Advance widths of Verdana, the font shields.io measures text with, for
+/// the printable ASCII characters from space to `~`, in font units.
+const VERDANA_WIDTHS: [u16; 95] = [
+    720, 824, 1074, 1888, 1424, 2498, 1608, 602, 1018, 1018, 1424, 1888, 720, 1022, 720, 1024,
+    1424, 1424, 1424, 1424, 1424, 1424, 1424, 1424, 1424, 1424, 1024, 1024, 1888, 1888, 1888, 1232,
+    2254, 1568, 1542, 1570, 1732, 1416, 1300, 1734, 1700, 862, 1026, 1562, 1278, 1940, 1700, 1800,
+    1384, 1800, 1586, 1424, 1390, 1666, 1568, 2254, 1568, 1390, 1568, 1018, 1024, 1018, 1888, 1424,
+    1424, 1366, 1400, 1188, 1400, 1340, 782, 1400, 1418, 614, 760, 1338, 614, 2176, 1418, 1384,
+    1400, 1400, 984, 1164, 888, 1418, 1298, 1794, 1298, 1298, 1176, 1424, 1024, 1424, 1888,
+];
+
+const UNITS_PER_EM: f64 = 2048.0;
+
+/// Characters outside of the table are measured like a capital `M`.
+const FALLBACK_WIDTH: u16 = 1940;
+
+/// Bold Verdana is about a tenth wider than the regular weight.
+const BOLD_FACTOR: f64 = 1.1;
+
+/// The badge styles of shields.io.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Flat,
+    FlatSquare,
+    Plastic,
+    ForTheBadge,
+    Social,
+}
+
+impl Style {
+    pub const ALL: [Style; 5] = [
+        Style::Flat,
+        Style::FlatSquare,
+        Style::Plastic,
+        Style::ForTheBadge,
+        Style::Social,
+    ];
+
+    /// The name shields.io expects in `?style=`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Style::Flat => "flat",
+            Style::FlatSquare => "flat-square",
+            Style::Plastic => "plastic",
+            Style::ForTheBadge => "for-the-badge",
+            Style::Social => "social",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Style> {
+        Style::ALL.into_iter().find(|style| style.name() == name)
+    }
+}
+
+/// Geometry and typography of the two-colored styles.
+struct Layout {
+    height: f64,
+    radius: f64,
+    gradient: &'static str,
+    font_size: f64,
+    /// Space left and right of the text in each half of the badge.
+    padding: f64,
+    letter_spacing: f64,
+    uppercase: bool,
+    bold_message: bool,
+    /// Baseline of the text, the shadow is drawn one pixel below.
+    text_y: f64,
+    shadow: bool,
+}
+
+fn layout(style: Style) -> Layout {
+    let flat = Layout {
+        height: 20.0,
+        radius: 3.0,
+        gradient: "<stop offset=\"0\" stop-color=\"#bbb\" stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/>",
+        font_size: 11.0,
+        padding: 5.0,
+        letter_spacing: 0.0,
+        uppercase: false,
+        bold_message: false,
+        text_y: 14.0,
+        shadow: true,
+    };
+    match style {
+        Style::Flat | Style::Social => flat,
+        Style::FlatSquare => Layout {
+            radius: 0.0,
+            gradient: "",
+            ..flat
+        },
+        Style::Plastic => Layout {
+            height: 18.0,
+            radius: 4.0,
+            gradient: "<stop offset=\"0\" stop-color=\"#fff\" stop-opacity=\".7\"/><stop offset=\".1\" stop-color=\"#aaa\" stop-opacity=\".1\"/><stop offset=\".9\" stop-color=\"#000\" stop-opacity=\".3\"/><stop offset=\"1\" stop-color=\"#000\" stop-opacity=\".5\"/>",
+            text_y: 13.0,
+            ..flat
+        },
+        Style::ForTheBadge => Layout {
+            height: 28.0,
+            radius: 0.0,
+            gradient: "",
+            font_size: 10.0,
+            padding: 12.0,
+            letter_spacing: 1.25,
+            uppercase: true,
+            bold_message: true,
+            text_y: 17.5,
+            shadow: false,
+        },
+    }
+}
+
+/// Width of `text` in pixels at `font_size`.
+fn text_width(text: &str, font_size: f64) -> f64 {
+    let units: u32 = text
+        .chars()
+        .map(|c| {
+            let index = (c as usize).wrapping_sub(' ' as usize);
+            VERDANA_WIDTHS.get(index).copied().unwrap_or(FALLBACK_WIDTH) as u32
+        })
+        .sum();
+    units as f64 * font_size / UNITS_PER_EM
+}
+
+/// Hex value of a shields.io color name. Hex colors are passed through.
+fn color_value(color: &str) -> String {
+    match color {
+        "brightgreen" => "#4c1".to_string(),
+        "green" => "#97ca00".to_string(),
+        "yellow" => "#dfb317".to_string(),
+        "orange" => "#fe7d37".to_string(),
+        "red" => "#e05d44".to_string(),
+        "blue" => "#007ec6".to_string(),
+        "lightgrey" => "#9f9f9f".to_string(),
+        color if color.starts_with('#') => color.to_string(),
+        color => format!("#{}", color),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a badge as SVG in the given style, `flat` by default.
+pub fn render(label: &str, message: &str, color: &str, style: Option<Style>) -> String {
+    match style.unwrap_or(Style::Flat) {
+        Style::Social => render_social(label, message),
+        style => render_two_colored(label, message, color, &layout(style)),
+    }
+}
+
+/// The label on gray and the message on the badge color.
+fn render_two_colored(label: &str, message: &str, color: &str, layout: &Layout) -> String {
+    let (label, message) = if layout.uppercase {
+        (label.to_uppercase(), message.to_uppercase())
+    } else {
+        (label.to_string(), message.to_string())
+    };
+    let width_of = |text: &str, bold: bool| {
+        let width = text_width(text, layout.font_size)
+            + layout.letter_spacing * text.chars().count() as f64;
+        let width = if bold { width * BOLD_FACTOR } else { width };
+        (width + 2.0 * layout.padding).round()
+    };
+    let label_width = width_of(&label, false);
+    let message_width = width_of(&message, layout.bold_message);
+    let width = label_width + message_width;
+    let height = layout.height;
+    let (gradient, shade) = if layout.gradient.is_empty() {
+        (String::new(), String::new())
+    } else {
+        (
+            format!(
+                "<linearGradient id=\"s\" x2=\"0\" y2=\"100%\">{}</linearGradient>",
+                layout.gradient
+            ),
+            format!(
+                "<rect width=\"{}\" height=\"{}\" fill=\"url(#s)\"/>",
+                width, height
+            ),
+        )
+    };
+    let label = escape_xml(&label);
+    let message = escape_xml(&message);
+    let text = |x: f64, content: &str, bold: bool| {
+        let weight = if bold { " font-weight=\"bold\"" } else { "" };
+        let shadow = if layout.shadow {
+            format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"#010101\" fill-opacity=\".3\"{}>{}</text>",
+                x,
+                layout.text_y + 1.0,
+                weight,
+                content
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "{}<text x=\"{}\" y=\"{}\"{}>{}</text>",
+            shadow, x, layout.text_y, weight, content
+        )
+    };
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" role=\"img\" aria-label=\"{label}: {message}\">\
+<title>{label}: {message}</title>{gradient}\
+<clipPath id=\"r\"><rect width=\"{width}\" height=\"{height}\" rx=\"{radius}\" fill=\"#fff\"/></clipPath>\
+<g clip-path=\"url(#r)\"><rect width=\"{label_width}\" height=\"{height}\" fill=\"#555\"/>\
+<rect x=\"{label_width}\" width=\"{message_width}\" height=\"{height}\" fill=\"{color}\"/>{shade}</g>\
+<g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"{font_size}\" letter-spacing=\"{letter_spacing}\">\
+{label_text}{message_text}</g></svg>\n",
+        width = width,
+        height = height,
+        label = label,
+        message = message,
+        gradient = gradient,
+        radius = layout.radius,
+        label_width = label_width,
+        message_width = message_width,
+        color = color_value(color),
+        shade = shade,
+        font_size = layout.font_size,
+        letter_spacing = layout.letter_spacing,
+        label_text = text(label_width / 2.0, &label, false),
+        message_text = text(
+            label_width + message_width / 2.0,
+            &message,
+            layout.bold_message
+        ),
+    )
+}
+
+/// Space between the label button and the message bubble of `social`.
+const SOCIAL_GAP: f64 = 6.0;
+
+/// A light button with the label, capitalized, and the message in a speech
+/// bubble next to it, like GitHub's star buttons. The color isn't used.
+fn render_social(label: &str, message: &str) -> String {
+    let mut chars = label.chars();
+    let label: String = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default();
+    let padding = layout(Style::Social).padding;
+    let label_width = (text_width(&label, 11.0) * BOLD_FACTOR + 2.0 * padding).round();
+    let message_width = (text_width(message, 11.0) * BOLD_FACTOR + 2.0 * padding).round();
+    let bubble_x = label_width + SOCIAL_GAP;
+    let width = bubble_x + message_width + 1.0;
+    let label = escape_xml(&label);
+    let message = escape_xml(message);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"20\" role=\"img\" aria-label=\"{label}: {message}\">\
+<title>{label}: {message}</title>\
+<linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#fcfcfc\" stop-opacity=\"0\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>\
+<g stroke=\"#d5d5d5\"><rect fill=\"#fcfcfc\" x=\".5\" y=\".5\" width=\"{label_width}\" height=\"19\" rx=\"2\"/>\
+<rect fill=\"url(#s)\" stroke=\"none\" x=\".5\" y=\".5\" width=\"{label_width}\" height=\"19\" rx=\"2\"/>\
+<rect fill=\"#fafafa\" x=\"{bubble_x}\" y=\".5\" width=\"{message_width}\" height=\"19\" rx=\"2\"/>\
+<path fill=\"#fafafa\" d=\"M{bubble_x} 6.5l-3 3v1l3 3\"/></g>\
+<g fill=\"#333\" text-anchor=\"middle\" font-family=\"Helvetica Neue,Helvetica,Arial,sans-serif\" font-weight=\"700\" font-size=\"11\">\
+<text x=\"{label_x}\" y=\"14\">{label}</text><text x=\"{message_x}\" y=\"14\">{message}</text></g></svg>\n",
+        width = width,
+        label = label,
+        message = message,
+        label_width = label_width,
+        bubble_x = bubble_x,
+        message_width = message_width,
+        label_x = label_width / 2.0 + 0.5,
+        message_x = bubble_x + message_width / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute<'a>(svg: &'a str, name: &str) -> &'a str {
+        let start = svg.find(&format!("{}=\"", name)).unwrap() + name.len() + 2;
+        &svg[start..start + svg[start..].find('"').unwrap()]
+    }
+
+    #[test]
+    fn styles_round_trip_their_names() {
+        for style in Style::ALL {
+            assert_eq!(Style::from_name(style.name()), Some(style));
+        }
+        assert_eq!(Style::from_name("rounded"), None);
+    }
+
+    #[test]
+    fn styles_have_their_own_shape() {
+        let flat = render("build", "passing", "brightgreen", None);
+        let square = render("build", "passing", "brightgreen", Some(Style::FlatSquare));
+        let plastic = render("build", "passing", "brightgreen", Some(Style::Plastic));
+        let big = render("build", "passing", "brightgreen", Some(Style::ForTheBadge));
+        let social = render("build", "passing", "brightgreen", Some(Style::Social));
+
+        assert_eq!(attribute(&flat, "height"), "20");
+        assert_eq!(attribute(&flat, "rx"), "3");
+        assert_eq!(attribute(&square, "rx"), "0");
+        assert!(!square.contains("linearGradient"));
+        assert_eq!(attribute(&plastic, "height"), "18");
+        assert_eq!(attribute(&plastic, "rx"), "4");
+        assert_eq!(attribute(&big, "height"), "28");
+        assert!(big.contains(">BUILD</text>") && big.contains(">PASSING</text>"));
+        assert!(
+            attribute(&big, "width").parse::<f64>().unwrap()
+                > attribute(&flat, "width").parse::<f64>().unwrap()
+        );
+        assert!(social.contains(">Build</text>"));
+        assert!(!social.contains("#4c1"));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let svg = render("a<b", "\"c\" & d", "blue", None);
+        assert!(svg.contains("a&lt;b: &quot;c&quot; &amp; d"));
+    }
+}
@@ -0,0 +1,638 @@
+extern crate clap;
+
+use crate::api::classification;
+use crate::cmd::badge_svg;
+use crate::cmd::language_detection;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::git;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path;
+
+const SHIELDS_URL: &str = "https://img.shields.io";
+
+pub const BADGES_DIR: &str = "docs/badges";
+
+/// Where the README, which is written to `docs`, finds the local badges.
+const BADGES_README_DIR: &str = "badges";
+
+/// Manifests are only read at the root and one directory below, like for
+/// the Usage section.
+const MAX_MANIFEST_DEPTH: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BadgeKind {
+    Version,
+    License,
+    Language,
+    Ci,
+    Package,
+    Docs,
+    Coverage,
+}
+
+impl BadgeKind {
+    const ALL: [BadgeKind; 7] = [
+        BadgeKind::Version,
+        BadgeKind::License,
+        BadgeKind::Language,
+        BadgeKind::Ci,
+        BadgeKind::Package,
+        BadgeKind::Docs,
+        BadgeKind::Coverage,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BadgeKind::Version => "version",
+            BadgeKind::License => "license",
+            BadgeKind::Language => "language",
+            BadgeKind::Ci => "ci",
+            BadgeKind::Package => "package",
+            BadgeKind::Docs => "docs",
+            BadgeKind::Coverage => "coverage",
+        }
+    }
+}
+
+/// Which badges are rendered, in which order and style.
+#[derive(Debug, Clone)]
+pub struct BadgeSettings {
+    pub order: Vec<BadgeKind>,
+    pub style: Option<badge_svg::Style>,
+    /// Draw the badges into `docs/badges` instead of linking to shields.io.
+    pub offline: bool,
+    /// The package is published, so that its registry and docs.rs pages
+    /// exist. A manifest alone doesn't tell.
+    pub published: bool,
+}
+
+impl Default for BadgeSettings {
+    fn default() -> Self {
+        BadgeSettings {
+            order: BadgeKind::ALL.to_vec(),
+            style: None,
+            offline: false,
+            published: false,
+        }
+    }
+}
+
+impl BadgeSettings {
+    pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<BadgeSettings> {
+        let kinds = BadgeKind::ALL
+            .iter()
+            .map(|kind| kind.name())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let order = match matches.value_of("badges") {
+            Some(badges) => badges
+                .split(',')
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    BadgeKind::ALL
+                        .into_iter()
+                        .find(|kind| kind.name() == name)
+                        .ok_or_else(|| {
+                            Error::config(
+                                &format!("Unknown badge {}.", name),
+                                Some(&format!("--badges expects a list of: {}.", kinds)),
+                            )
+                        })
+                })
+                .collect::<types::Result<Vec<BadgeKind>>>()?,
+            None => BadgeKind::ALL.to_vec(),
+        };
+        let style = match matches.value_of("badge-style") {
+            Some(name) => match badge_svg::Style::from_name(name) {
+                Some(style) => Some(style),
+                None => {
+                    let styles = badge_svg::Style::ALL
+                        .iter()
+                        .map(|style| style.name())
+                        .collect::<Vec<&str>>()
+                        .join(", ");
+                    return Err(Error::config(
+                        &format!("Unknown badge style {}.", name),
+                        Some(&format!("--badge-style expects one of: {}.", styles)),
+                    ));
+                }
+            },
+            None => None,
+        };
+        Ok(BadgeSettings {
+            order,
+            style,
+            offline: matches.is_present("offline-badges"),
+            published: matches.is_present("published"),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registry {
+    Crates,
+    Npm,
+    PyPi,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Package {
+    pub registry: Registry,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageService {
+    Codecov,
+    Coveralls,
+}
+
+/// What the badges are derived from besides the classification.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProjectFacts {
+    pub language: Option<String>,
+    pub package: Option<Package>,
+    /// Owner and name of the GitHub repository, from the `origin` remote.
+    pub github: Option<(String, String)>,
+    pub workflows: Vec<String>,
+    pub coverage: Option<CoverageService>,
+    pub read_the_docs: bool,
+}
+
+/// A badge and what it shows when it is drawn locally. Badges of live data,
+/// like the CI status, show it through their `remote_image` when online.
+#[derive(Debug, Clone)]
+pub struct Badge {
+    pub alt: String,
+    pub label: String,
+    pub message: String,
+    pub color: &'static str,
+    pub remote_image: Option<String>,
+    pub link: Option<String>,
+    /// Name of the SVG in `docs/badges`, unique among the badges.
+    pub file_name: String,
+}
+
+impl Badge {
+    fn new(label: &str, message: &str, color: &'static str) -> Badge {
+        Badge {
+            alt: message.to_string(),
+            label: label.to_string(),
+            message: message.to_string(),
+            color,
+            remote_image: None,
+            link: None,
+            file_name: String::new(),
+        }
+    }
+
+    fn remote(self, image: String, link: String) -> Badge {
+        Badge {
+            remote_image: Some(image),
+            link: Some(link),
+            ..self
+        }
+    }
+
+    fn image(&self, settings: &BadgeSettings) -> String {
+        if settings.offline {
+            return format!("{}/{}", BADGES_README_DIR, self.file_name);
+        }
+        let image = self
+            .remote_image
+            .clone()
+            .unwrap_or_else(|| static_badge(&self.label, &self.message, self.color));
+        match &settings.style {
+            Some(style) => format!("{}?style={}", image, style.name()),
+            None => image,
+        }
+    }
+
+    pub fn markdown(&self, settings: &BadgeSettings) -> String {
+        let image = format!("![{}]({})", self.alt, self.image(settings));
+        match &self.link {
+            Some(link) => format!("[{}]({})", image, link),
+            None => image,
+        }
+    }
+}
+
+/// Escapes a value for a shields.io static badge: dashes and underscores are
+/// doubled, spaces become underscores and everything else that isn't safe in
+/// a path segment is percent-encoded.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '-' => escaped.push_str("--"),
+            '_' => escaped.push_str("__"),
+            ' ' => escaped.push('_'),
+            c if c.is_ascii_alphanumeric() || c == '.' || c == '~' => escaped.push(c),
+            c => {
+                let mut buffer = [0; 4];
+                for byte in c.encode_utf8(&mut buffer).bytes() {
+                    escaped.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+    }
+    escaped
+}
+
+fn static_badge(label: &str, message: &str, color: &str) -> String {
+    format!(
+        "{}/badge/{}-{}-{}",
+        SHIELDS_URL,
+        escape(label),
+        escape(message),
+        color
+    )
+}
+
+fn badges_of_kind(
+    kind: BadgeKind,
+    result: &classification::Classification,
+    facts: &ProjectFacts,
+    published: bool,
+) -> Vec<Badge> {
+    let github = facts.github.as_ref();
+    // Registry pages only exist for published packages.
+    let published_package = facts.package.as_ref().filter(|_| published);
+    match kind {
+        BadgeKind::Version => result
+            .version
+            .iter()
+            .map(|version| Badge::new("version", version, "red"))
+            .collect(),
+        BadgeKind::License => result
+            .license
+            .iter()
+            .map(|license| Badge::new("license", license, "blue"))
+            .collect(),
+        BadgeKind::Language => facts
+            .language
+            .iter()
+            .map(|language| Badge::new("language", language, "orange"))
+            .collect(),
+        BadgeKind::Ci => match github {
+            Some((owner, repo)) => facts
+                .workflows
+                .iter()
+                .map(|workflow| {
+                    let name = workflow.trim_end_matches(".yml").trim_end_matches(".yaml");
+                    Badge {
+                        alt: name.to_string(),
+                        ..Badge::new(name, "GitHub Actions", "lightgrey")
+                    }
+                    .remote(
+                        format!(
+                            "{}/github/actions/workflow/status/{}/{}/{}",
+                            SHIELDS_URL, owner, repo, workflow
+                        ),
+                        format!(
+                            "https://github.com/{}/{}/actions/workflows/{}",
+                            owner, repo, workflow
+                        ),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        },
+        BadgeKind::Package => published_package
+            .iter()
+            .map(|package| {
+                let (label, registry, link) = match package.registry {
+                    Registry::Crates => ("crates.io", "crates/v", "https://crates.io/crates"),
+                    Registry::Npm => ("npm", "npm/v", "https://www.npmjs.com/package"),
+                    Registry::PyPi => ("PyPI", "pypi/v", "https://pypi.org/project"),
+                };
+                Badge::new(label, &package.name, "blue").remote(
+                    format!("{}/{}/{}", SHIELDS_URL, registry, package.name),
+                    format!("{}/{}", link, package.name),
+                )
+            })
+            .collect(),
+        BadgeKind::Docs => match &facts.package {
+            Some(package)
+                if package.registry == Registry::Crates && published_package.is_some() =>
+            {
+                vec![Badge::new("docs", "docs.rs", "blue").remote(
+                    format!("{}/docsrs/{}", SHIELDS_URL, package.name),
+                    format!("https://docs.rs/{}", package.name),
+                )]
+            }
+            Some(package) if facts.read_the_docs => {
+                vec![Badge::new("docs", "Read the Docs", "blue").remote(
+                    format!("{}/readthedocs/{}", SHIELDS_URL, package.name),
+                    format!("https://{}.readthedocs.io", package.name),
+                )]
+            }
+            _ => Vec::new(),
+        },
+        BadgeKind::Coverage => match (facts.coverage, github) {
+            (Some(CoverageService::Codecov), Some((owner, repo))) => {
+                vec![Badge::new("coverage", "Codecov", "lightgrey").remote(
+                    format!("{}/codecov/c/github/{}/{}", SHIELDS_URL, owner, repo),
+                    format!("https://codecov.io/gh/{}/{}", owner, repo),
+                )]
+            }
+            (Some(CoverageService::Coveralls), Some((owner, repo))) => {
+                vec![Badge::new("coverage", "Coveralls", "lightgrey").remote(
+                    format!(
+                        "{}/coverallsCoverage/github/{}/{}",
+                        SHIELDS_URL, owner, repo
+                    ),
+                    format!("https://coveralls.io/github/{}/{}", owner, repo),
+                )]
+            }
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// File name stem of a badge, e.g. `crates-io` for `crates.io`.
+fn slug(label: &str) -> String {
+    label
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/// The badges for the README, in the configured order. Badges whose facts
+/// weren't found are left out.
+pub fn badges(
+    result: &classification::Classification,
+    facts: &ProjectFacts,
+    settings: &BadgeSettings,
+) -> Vec<Badge> {
+    let mut file_names: Vec<String> = Vec::new();
+    settings
+        .order
+        .iter()
+        .flat_map(|kind| badges_of_kind(*kind, result, facts, settings.published))
+        .map(|badge| {
+            let stem = slug(&badge.label);
+            let mut file_name = format!("{}.svg", stem);
+            let mut n = 2;
+            while file_names.contains(&file_name) {
+                file_name = format!("{}-{}.svg", stem, n);
+                n += 1;
+            }
+            file_names.push(file_name.clone());
+            Badge { file_name, ..badge }
+        })
+        .collect()
+}
+
+pub fn render(badges: &[Badge], settings: &BadgeSettings) -> String {
+    badges
+        .iter()
+        .map(|badge| badge.markdown(settings))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Draws the badges into `docs/badges` for `--offline-badges`.
+pub async fn write_svgs(badges: &[Badge], settings: &BadgeSettings) -> types::Result<()> {
+    let badges_dir = path::Path::new(BADGES_DIR);
+    fs::create_dir_all(badges_dir).map_err(|e| Error::io(badges_dir, e))?;
+    for badge in badges.iter() {
+        let svg = badge_svg::render(&badge.label, &badge.message, badge.color, settings.style);
+        let svg_path = badges_dir.join(&badge.file_name);
+        util::write_utf8_file(svg_path.to_string_lossy().to_string(), svg).await?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoPackage {
+    name: String,
+    publish: Option<toml::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageJson {
+    name: Option<String>,
+    #[serde(default)]
+    private: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyProject {
+    project: Option<PyProjectProject>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyProjectProject {
+    name: String,
+}
+
+/// The package as published to its registry. Unparsable manifests and
+/// packages that aren't published have none, the Usage section reports
+/// broken manifests already.
+fn package(file_name: &str, source: &str) -> Option<Package> {
+    match file_name {
+        "Cargo.toml" => {
+            let package = toml::from_str::<CargoManifest>(source).ok()?.package?;
+            if package.publish == Some(toml::Value::Boolean(false)) {
+                return None;
+            }
+            Some(Package {
+                registry: Registry::Crates,
+                name: package.name,
+            })
+        }
+        "package.json" => {
+            let package = serde_json::from_str::<PackageJson>(source).ok()?;
+            if package.private {
+                return None;
+            }
+            Some(Package {
+                registry: Registry::Npm,
+                name: package.name?,
+            })
+        }
+        "pyproject.toml" => Some(Package {
+            registry: Registry::PyPi,
+            name: toml::from_str::<PyProject>(source).ok()?.project?.name,
+        }),
+        _ => None,
+    }
+}
+
+/// Owner and name of a GitHub remote, e.g. from
+/// `git@github.com:owner/repo.git` or `https://github.com/owner/repo`.
+pub fn github_repository(url: &str) -> Option<(String, String)> {
+    let (_, path) = url.split_once("github.com")?;
+    let mut parts = path
+        .trim_start_matches([':', '/'])
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .split('/');
+    let owner = parts.next().filter(|owner| !owner.is_empty())?;
+    let repo = parts.next().filter(|repo| !repo.is_empty())?;
+    Some((owner.to_string(), repo.to_string()))
+}
+
+fn workflows() -> Vec<String> {
+    let mut workflows: Vec<String> = fs::read_dir(".github/workflows")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.ends_with(".yml") || name.ends_with(".yaml"))
+                .collect()
+        })
+        .unwrap_or_default();
+    workflows.sort();
+    workflows
+}
+
+fn coverage_service() -> Option<CoverageService> {
+    let exists = |name: &str| path::Path::new(name).is_file();
+    if exists("codecov.yml") || exists(".codecov.yml") {
+        Some(CoverageService::Codecov)
+    } else if exists(".coveralls.yml") {
+        Some(CoverageService::Coveralls)
+    } else {
+        None
+    }
+}
+
+/// Collects the facts for the badges from the manifests, the git remote and
+/// the CI and coverage configuration. Hidden files aren't listed, so they
+/// are looked up directly.
+pub async fn detect(
+    relevant_files: &[path::PathBuf],
+    detected_language: &Option<String>,
+) -> types::Result<ProjectFacts> {
+    let mut manifests: Vec<&path::PathBuf> = relevant_files
+        .iter()
+        .filter(|file| {
+            let components = file.strip_prefix(".").unwrap_or(file).components().count();
+            let file_name = file.file_name().and_then(|name| name.to_str());
+            components <= MAX_MANIFEST_DEPTH
+                && matches!(
+                    file_name,
+                    Some("Cargo.toml") | Some("package.json") | Some("pyproject.toml")
+                )
+        })
+        .collect();
+    // Root manifests first, then the ones below in path order.
+    manifests.sort_by_key(|file| (file.components().count(), file.to_path_buf()));
+    let mut package_found = None;
+    for manifest in manifests {
+        let source = util::read_utf8_file(manifest).await?;
+        let file_name = manifest
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if let Some(package) = package(file_name, &source) {
+            package_found = Some(package);
+            break;
+        }
+    }
+
+    let github = git::origin_url().and_then(|url| github_repository(&url));
+    Ok(ProjectFacts {
+        language: detected_language
+            .as_ref()
+            .map(|language| language_detection::language_display_name_or_default(language))
+            // Display names start with an emoji, which has no place on a badge.
+            .map(|name| {
+                name.trim_start_matches(|c: char| !c.is_alphanumeric())
+                    .to_string()
+            }),
+        package: package_found,
+        github,
+        workflows: workflows(),
+        coverage: coverage_service(),
+        read_the_docs: path::Path::new(".readthedocs.yaml").is_file()
+            || path::Path::new(".readthedocs.yml").is_file(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classification() -> classification::Classification {
+        serde_json::from_str(
+            r#"{"name": "cli", "tldr": "", "usage": "", "version": "1.0-rc_1", "license": null}"#,
+        )
+        .unwrap()
+    }
+
+    fn crate_facts() -> ProjectFacts {
+        ProjectFacts {
+            package: package("Cargo.toml", "[package]\nname = \"cli\"\n"),
+            ..ProjectFacts::default()
+        }
+    }
+
+    fn labels(badges: &[Badge]) -> Vec<&str> {
+        badges.iter().map(|badge| badge.label.as_str()).collect()
+    }
+
+    #[test]
+    fn escape_follows_shields_rules() {
+        assert_eq!(escape("1.0-rc_1"), "1.0--rc__1");
+        assert_eq!(escape("MIT or Apache"), "MIT_or_Apache");
+        assert_eq!(escape("C++/Rust"), "C%2B%2B%2FRust");
+        assert_eq!(escape("🦀"), "%F0%9F%A6%80");
+    }
+
+    #[test]
+    fn github_repository_from_remotes() {
+        let expected = Some(("owner".to_string(), "repo".to_string()));
+        assert_eq!(github_repository("git@github.com:owner/repo.git"), expected);
+        assert_eq!(
+            github_repository("https://github.com/owner/repo/"),
+            expected
+        );
+        assert_eq!(github_repository("https://gitlab.com/owner/repo"), None);
+        assert_eq!(github_repository("https://github.com/owner"), None);
+    }
+
+    #[test]
+    fn unpublished_packages_have_no_package() {
+        assert!(package("Cargo.toml", "[package]\nname = \"a\"\npublish = false\n").is_none());
+        assert!(package("package.json", r#"{"name": "a", "private": true}"#).is_none());
+        assert!(package("Cargo.toml", "[workspace]\n").is_none());
+    }
+
+    #[test]
+    fn registry_badges_need_published() {
+        let settings = BadgeSettings::default();
+        let unpublished = badges(&classification(), &crate_facts(), &settings);
+        assert_eq!(labels(&unpublished), vec!["version"]);
+
+        let settings = BadgeSettings {
+            published: true,
+            ..BadgeSettings::default()
+        };
+        let published = badges(&classification(), &crate_facts(), &settings);
+        assert_eq!(labels(&published), vec!["version", "crates.io", "docs"]);
+        assert_eq!(published[2].link.as_deref(), Some("https://docs.rs/cli"));
+    }
+
+    #[test]
+    fn static_badges_are_escaped() {
+        let badge = Badge::new("version", "1.0-rc_1", "red");
+        assert_eq!(
+            badge.markdown(&BadgeSettings::default()),
+            "![1.0-rc_1](https://img.shields.io/badge/version-1.0--rc__1-red)"
+        );
+    }
+}
@@ -0,0 +1,609 @@
+use crate::cmd::util;
+use crate::error::Error;
+use crate::types;
+use indicatif::HumanBytes;
+use std::fmt;
+use std::io;
+use std::path;
+
+/// Optional file name and content as sent to the API.
+pub type FileContent = (Option<String>, String);
+
+/// Default payload budget when neither `--max-bytes` nor `--max-tokens` is given.
+const DEFAULT_MAX_BYTES: usize = 256 * 1024;
+
+/// Rough number of bytes per token for source code.
+const BYTES_PER_TOKEN: usize = 4;
+
+/// Files are only truncated if at least this share of the budget (in percent)
+/// is left, anything smaller isn't worth sending.
+const MIN_CHUNK_PERCENT: usize = 1;
+
+/// Share of the budget, in percent, that extra context may take up.
+const CONTEXT_PERCENT: usize = 25;
+
+/// Bytes of a classification request around the files and the context: the
+/// field names, language, schema version and options.
+const ENVELOPE_BYTES: usize = 256;
+
+/// Bytes a file adds to the request around its name and content,
+/// `{"name":,"content":""},`.
+const FILE_ENVELOPE_BYTES: usize = 24;
+
+/// Bytes the context adds to the request around its text, `,"context":`.
+const CONTEXT_ENVELOPE_BYTES: usize = 11;
+
+/// Upper bound for the size of a classification request, either measured in
+/// bytes of the encoded payload or in estimated tokens of the raw content.
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    Bytes(usize),
+    Tokens(usize),
+}
+
+impl Budget {
+    pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<Budget> {
+        let parse = |arg: &str| -> types::Result<Option<usize>> {
+            let hint = format!("--{} expects a positive number.", arg);
+            match matches.value_of(arg) {
+                Some(value) => match value.parse::<usize>() {
+                    Ok(0) => Err(Error::config(
+                        &format!("Invalid payload budget {}.", value),
+                        Some(&hint),
+                    )),
+                    Ok(amount) => Ok(Some(amount)),
+                    Err(e) => Err(Error::config_caused_by(
+                        &format!("Invalid payload budget {}.", value),
+                        Some(&hint),
+                        e,
+                    )),
+                },
+                None => Ok(None),
+            }
+        };
+        match (parse("max-bytes")?, parse("max-tokens")?) {
+            (_, Some(tokens)) => Ok(Budget::Tokens(tokens)),
+            (Some(bytes), None) => Ok(Budget::Bytes(bytes)),
+            (None, None) => Ok(Budget::Bytes(DEFAULT_MAX_BYTES)),
+        }
+    }
+
+    /// Takes the context out of the budget, cut to at most `percent` of it.
+    /// Returns the context as sent and the budget that is left.
+    fn reserve(self, context: &str, percent: usize) -> (String, Budget) {
+        let share = self.capacity() * percent / 100;
+        let content = context.trim();
+        // Escaping may make the text longer than its raw size, so shrink it
+        // until it fits.
+        let mut capacity = match self {
+            Budget::Bytes(_) => share.saturating_sub(CONTEXT_ENVELOPE_BYTES),
+            Budget::Tokens(_) => share * BYTES_PER_TOKEN,
+        };
+        let mut kept = truncate(content, capacity);
+        while self.context_cost(kept) > share && !kept.is_empty() {
+            capacity = capacity.saturating_sub(self.context_cost(kept) - share);
+            kept = truncate(content, capacity);
+        }
+        let left = self.limit().saturating_sub(self.context_cost(kept));
+        let budget = match self {
+            Budget::Bytes(_) => Budget::Bytes(left),
+            Budget::Tokens(_) => Budget::Tokens(left),
+        };
+        (kept.to_string(), budget)
+    }
+
+    fn limit(&self) -> usize {
+        match self {
+            Budget::Bytes(bytes) => *bytes,
+            Budget::Tokens(tokens) => *tokens,
+        }
+    }
+
+    /// What is left for files and context once the request envelope is
+    /// accounted for.
+    fn capacity(&self) -> usize {
+        match self {
+            Budget::Bytes(bytes) => bytes.saturating_sub(ENVELOPE_BYTES),
+            Budget::Tokens(tokens) => *tokens,
+        }
+    }
+
+    /// Cost of a file within this budget. Contents are sent base64-encoded
+    /// in a JSON object, so byte budgets account for the encoding overhead.
+    fn cost(&self, name: &str, content: &str) -> usize {
+        match self {
+            Budget::Bytes(_) => {
+                json_len(name) + FILE_ENVELOPE_BYTES + content.len().div_ceil(3) * 4
+            }
+            Budget::Tokens(_) => estimate_tokens(name) + estimate_tokens(content),
+        }
+    }
+
+    /// Cost of the context, which is sent as a plain JSON string.
+    fn context_cost(&self, context: &str) -> usize {
+        match self {
+            Budget::Bytes(_) => json_len(context) + CONTEXT_ENVELOPE_BYTES,
+            Budget::Tokens(_) => estimate_tokens(context),
+        }
+    }
+
+    /// How many bytes of raw content fit into `remaining` for a file called `name`.
+    fn content_capacity(&self, name: &str, remaining: usize) -> usize {
+        match self {
+            Budget::Bytes(_) => {
+                remaining.saturating_sub(json_len(name) + FILE_ENVELOPE_BYTES) / 4 * 3
+            }
+            Budget::Tokens(_) => remaining.saturating_sub(estimate_tokens(name)) * BYTES_PER_TOKEN,
+        }
+    }
+
+    fn format_amount(&self, amount: usize) -> String {
+        match self {
+            Budget::Bytes(_) => format!("{}", HumanBytes(amount as u64)),
+            Budget::Tokens(_) => format!("{} tokens", amount),
+        }
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_amount(self.limit()))
+    }
+}
+
+/// Length of `text` as a JSON string, with quotes and escapes.
+fn json_len(text: &str) -> usize {
+    serde_json::to_string(text)
+        .map(|json| json.len())
+        .unwrap_or(text.len() + 2)
+}
+
+fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(BYTES_PER_TOKEN)
+}
+
+/// Why a file didn't make it into the payload.
+#[derive(Debug, Clone, Copy)]
+pub enum Omission {
+    BudgetExhausted,
+    NotUtf8,
+}
+
+impl fmt::Display for Omission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Omission::BudgetExhausted => write!(f, "over budget"),
+            Omission::NotUtf8 => write!(f, "not UTF-8"),
+        }
+    }
+}
+
+/// A file that was cut to fit the budget, with the kept and the original size in bytes.
+#[derive(Debug, Clone)]
+pub struct Truncation {
+    pub path: path::PathBuf,
+    pub kept: usize,
+    pub total: usize,
+}
+
+/// What went into a classification request and what was left out.
+#[derive(Debug, Clone)]
+pub struct PayloadReport {
+    pub budget: Budget,
+    pub used: usize,
+    pub included: Vec<path::PathBuf>,
+    pub requests: usize,
+    pub cached: usize,
+    pub truncated: Vec<Truncation>,
+    pub omitted: Vec<(path::PathBuf, Omission)>,
+}
+
+impl PayloadReport {
+    pub fn summary(&self) -> String {
+        let mut summary = if self.requests > 1 {
+            format!(
+                "{} files, {} in {} requests",
+                self.included.len(),
+                self.budget.format_amount(self.used),
+                self.requests
+            )
+        } else {
+            format!(
+                "{} files, {} of {}",
+                self.included.len(),
+                self.budget.format_amount(self.used),
+                self.budget
+            )
+        };
+        if self.cached > 0 {
+            summary.push_str(&format!(", {} summaries cached", self.cached));
+        }
+        if !self.truncated.is_empty() {
+            summary.push_str(&format!(", {} truncated", self.truncated.len()));
+        }
+        if !self.omitted.is_empty() {
+            summary.push_str(&format!(", {} left out", self.omitted.len()));
+        }
+        summary
+    }
+}
+
+/// Cuts `content` to at most `capacity` bytes, preferring to end on a line break.
+fn truncate(content: &str, capacity: usize) -> &str {
+    if content.len() <= capacity {
+        return content;
+    }
+    let mut end = capacity.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    let head = &content[..end];
+    match head.rfind('\n') {
+        Some(line_end) if line_end > 0 => &head[..line_end + 1],
+        _ => head,
+    }
+}
+
+/// Takes context like the API reference out of the budget, at most a
+/// quarter of it. Returns the context and the budget left for files.
+pub fn with_context(budget: Budget, context: Option<String>) -> (Option<String>, Budget) {
+    match context {
+        Some(context) => {
+            let (kept, left) = budget.reserve(&context, CONTEXT_PERCENT);
+            (Some(kept).filter(|kept| !kept.is_empty()), left)
+        }
+        None => (None, budget),
+    }
+}
+
+/// Splits `content` into consecutive parts of at most `capacity` bytes.
+fn split(content: &str, capacity: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let mut part = truncate(rest, capacity);
+        if part.is_empty() {
+            // Not even a single character fits, send it anyway rather than looping.
+            part = &rest[..rest
+                .chars()
+                .next()
+                .map(|c| c.len_utf8())
+                .unwrap_or(rest.len())];
+        }
+        parts.push(part);
+        rest = &rest[part.len()..];
+    }
+    parts
+}
+
+/// Reads a file to be sent, `None` if it isn't UTF-8.
+async fn read_file(file_path: &path::Path) -> types::Result<Option<String>> {
+    match util::read_utf8_file(file_path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::InvalidData => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads `ranked_files` in order and packs them into the budget. Files that
+/// don't fit whole are truncated while a meaningful chunk is left, the rest
+/// is left out without being read.
+pub async fn fill(
+    budget: Budget,
+    ranked_files: &[path::PathBuf],
+) -> types::Result<(Vec<FileContent>, PayloadReport)> {
+    let limit = budget.capacity();
+    let min_chunk = (limit * MIN_CHUNK_PERCENT / 100).max(1);
+    let mut files = Vec::new();
+    let mut report = PayloadReport {
+        budget,
+        used: 0,
+        included: Vec::new(),
+        requests: 1,
+        cached: 0,
+        truncated: Vec::new(),
+        omitted: Vec::new(),
+    };
+
+    for file_path in ranked_files.iter() {
+        let remaining = limit - report.used;
+        if remaining < min_chunk {
+            report
+                .omitted
+                .push((file_path.clone(), Omission::BudgetExhausted));
+            continue;
+        }
+        let content = match read_file(file_path).await? {
+            Some(content) => content,
+            None => {
+                report.omitted.push((file_path.clone(), Omission::NotUtf8));
+                continue;
+            }
+        };
+        let name = file_path.to_string_lossy().to_string();
+        let content = content.trim();
+
+        let cost = budget.cost(&name, content);
+        let kept = if cost <= remaining {
+            content
+        } else {
+            let head = truncate(content, budget.content_capacity(&name, remaining));
+            if head.is_empty() {
+                report
+                    .omitted
+                    .push((file_path.clone(), Omission::BudgetExhausted));
+                continue;
+            }
+            report.truncated.push(Truncation {
+                path: file_path.clone(),
+                kept: head.len(),
+                total: content.len(),
+            });
+            head
+        };
+
+        report.used += budget.cost(&name, kept);
+        report.included.push(file_path.clone());
+        files.push((Some(name), kept.to_string()));
+    }
+
+    let over_budget = report
+        .omitted
+        .iter()
+        .any(|(_, omission)| matches!(omission, Omission::BudgetExhausted));
+    if files.is_empty() && over_budget {
+        return Err(Error::config(
+            &format!("No file fits into the payload budget of {}.", budget),
+            Some("Increase --max-bytes or --max-tokens."),
+        ));
+    }
+    Ok((files, report))
+}
+
+/// Packs contents into as few chunks as possible, each within the budget.
+/// Contents that don't fit into a chunk on their own are truncated.
+pub fn pack(budget: Budget, contents: Vec<FileContent>) -> Vec<Vec<FileContent>> {
+    let limit = budget.capacity();
+    let mut chunks: Vec<Vec<FileContent>> = Vec::new();
+    let mut chunk: Vec<FileContent> = Vec::new();
+    let mut used = 0;
+    for (name, content) in contents.into_iter() {
+        let label = name.clone().unwrap_or_default();
+        let content = truncate(content.trim(), budget.content_capacity(&label, limit)).to_string();
+        let cost = budget.cost(&label, &content);
+        if used + cost > limit && !chunk.is_empty() {
+            chunks.push(std::mem::take(&mut chunk));
+            used = 0;
+        }
+        used += cost;
+        chunk.push((name, content));
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Pairs up contents, each cut to half the budget, so that every pair fits
+/// into one chunk. Used when `pack` can't combine anything because every
+/// content fills a chunk on its own.
+pub fn pair(budget: Budget, contents: Vec<FileContent>) -> Vec<Vec<FileContent>> {
+    let half = budget.capacity() / 2;
+    let halved: Vec<FileContent> = contents
+        .into_iter()
+        .map(|(name, content)| {
+            let label = name.clone().unwrap_or_default();
+            let kept = truncate(content.trim(), budget.content_capacity(&label, half));
+            (name, kept.to_string())
+        })
+        .collect();
+    halved.chunks(2).map(|pair| pair.to_vec()).collect()
+}
+
+/// Reads all files of each group and packs them into chunks that each fit
+/// the budget, so that every file is sent in some request. Chunks never
+/// span groups and files larger than the whole budget are split into parts.
+pub async fn chunk(
+    budget: Budget,
+    groups: &[Vec<path::PathBuf>],
+) -> types::Result<(Vec<Vec<FileContent>>, PayloadReport)> {
+    let limit = budget.capacity();
+    let mut chunks = Vec::new();
+    let mut report = PayloadReport {
+        budget,
+        used: 0,
+        included: Vec::new(),
+        requests: 0,
+        cached: 0,
+        truncated: Vec::new(),
+        omitted: Vec::new(),
+    };
+    for group in groups.iter() {
+        let mut contents = Vec::new();
+        for file_path in group.iter() {
+            let content = match read_file(file_path).await? {
+                Some(content) => content,
+                None => {
+                    report.omitted.push((file_path.clone(), Omission::NotUtf8));
+                    continue;
+                }
+            };
+            let name = file_path.to_string_lossy().to_string();
+            // Leave room for the part suffix in the name.
+            let capacity = budget.content_capacity(&format!("{} (part 999 of 999)", name), limit);
+            if capacity == 0 {
+                report
+                    .omitted
+                    .push((file_path.clone(), Omission::BudgetExhausted));
+                continue;
+            }
+            let parts = split(content.trim(), capacity);
+            report.included.push(file_path.clone());
+            let part_count = parts.len();
+            for (index, part) in parts.into_iter().enumerate() {
+                let part_name = if part_count > 1 {
+                    format!("{} (part {} of {})", name, index + 1, part_count)
+                } else {
+                    name.clone()
+                };
+                report.used += budget.cost(&part_name, part);
+                contents.push((Some(part_name), part.to_string()));
+            }
+        }
+        chunks.extend(pack(budget, contents));
+    }
+    report.requests = chunks.len();
+    Ok((chunks, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn budget_from(args: &[&str]) -> types::Result<Budget> {
+        let matches = clap::App::new("test")
+            .arg(
+                clap::Arg::with_name("max-bytes")
+                    .long("max-bytes")
+                    .takes_value(true),
+            )
+            .arg(
+                clap::Arg::with_name("max-tokens")
+                    .long("max-tokens")
+                    .takes_value(true),
+            )
+            .get_matches_from(std::iter::once("test").chain(args.iter().copied()));
+        Budget::from_matches(&matches)
+    }
+
+    #[test]
+    fn parses_the_budget() {
+        assert!(matches!(
+            budget_from(&[]),
+            Ok(Budget::Bytes(DEFAULT_MAX_BYTES))
+        ));
+        assert!(matches!(
+            budget_from(&["--max-bytes", "100"]),
+            Ok(Budget::Bytes(100))
+        ));
+        assert!(matches!(
+            budget_from(&["--max-tokens", "50"]),
+            Ok(Budget::Tokens(50))
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_budgets() {
+        assert!(budget_from(&["--max-bytes", "0"]).is_err());
+        assert!(budget_from(&["--max-tokens", "0"]).is_err());
+        assert!(budget_from(&["--max-bytes=-1"]).is_err());
+        assert!(budget_from(&["--max-tokens", "many"]).is_err());
+    }
+
+    #[test]
+    fn truncates_at_line_breaks() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("first\nsecond\nthird", 14), "first\nsecond\n");
+        assert_eq!(truncate("no line breaks", 5), "no li");
+    }
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        assert_eq!(truncate("äöü", 3), "ä");
+        assert_eq!(truncate("äöü", 1), "");
+    }
+
+    #[test]
+    fn splits_into_consecutive_parts() {
+        assert_eq!(split("one\ntwo\nthree", 8), vec!["one\ntwo\n", "three"]);
+        assert_eq!(split("", 8), Vec::<&str>::new());
+        // A character wider than the capacity is still sent on its own.
+        assert_eq!(split("äb", 1), vec!["ä", "b"]);
+    }
+
+    #[test]
+    fn counts_the_json_envelope_of_files() {
+        let budget = Budget::Bytes(1000);
+        // `"a.rs"` plus `{"name":,"content":""},` plus base64 of three bytes.
+        assert_eq!(budget.cost("a.rs", "abc"), 6 + 24 + 4);
+        assert_eq!(budget.capacity(), 1000 - ENVELOPE_BYTES);
+        let content = "x".repeat(budget.content_capacity("a.rs", 100));
+        assert!(budget.cost("a.rs", &content) <= 100);
+    }
+
+    #[test]
+    fn sends_the_context_within_its_share() {
+        let budget = Budget::Bytes(ENVELOPE_BYTES + 400);
+        let context = "\"quoted\"\n".repeat(100);
+        let (kept, left) = with_context(budget, Some(context));
+        let kept = kept.unwrap();
+        assert!(budget.context_cost(&kept) <= 100);
+        assert_eq!(left.limit(), budget.limit() - budget.context_cost(&kept));
+        assert!(matches!(
+            with_context(budget, None),
+            (None, Budget::Bytes(_))
+        ));
+    }
+
+    #[test]
+    fn packs_contents_into_chunks_within_the_budget() {
+        let budget = Budget::Tokens(10);
+        let contents: Vec<FileContent> = ["a", "b", "c"]
+            .iter()
+            .map(|name| (Some(name.to_string()), "x".repeat(12)))
+            .collect();
+        let chunks = pack(budget, contents);
+        assert_eq!(chunks.len(), 2);
+        for chunk in chunks.iter() {
+            let cost: usize = chunk
+                .iter()
+                .map(|(name, content)| budget.cost(name.as_deref().unwrap(), content))
+                .sum();
+            assert!(cost <= 10);
+        }
+    }
+
+    #[test]
+    fn pairs_contents_within_the_budget() {
+        let budget = Budget::Tokens(10);
+        let contents: Vec<FileContent> = ["a", "b", "c"]
+            .iter()
+            .map(|name| (Some(name.to_string()), "x".repeat(40)))
+            .collect();
+        let pairs = pair(budget, contents);
+        assert_eq!(
+            pairs.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        for pair in pairs.iter() {
+            let cost: usize = pair
+                .iter()
+                .map(|(name, content)| budget.cost(name.as_deref().unwrap(), content))
+                .sum();
+            assert!(cost <= 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn fills_the_budget_in_ranking_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<path::PathBuf> = ["first.rs", "second.rs", "third.rs"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        for file in files.iter() {
+            fs::write(file, "line\n".repeat(40)).unwrap();
+        }
+        let budget = Budget::Tokens(80);
+
+        let (contents, report) = fill(budget, &files).await.unwrap();
+
+        assert_eq!(contents.len(), 2);
+        assert_eq!(report.included, files[..2].to_vec());
+        assert_eq!(report.truncated.len(), 1);
+        assert_eq!(report.truncated[0].path, files[1]);
+        assert_eq!(report.omitted.len(), 1);
+        assert!(report.used <= 80);
+    }
+}
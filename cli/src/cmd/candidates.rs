@@ -0,0 +1,301 @@
+extern crate clap;
+
+use crate::api::classification;
+use crate::error::Error;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::path;
+
+/// Marketing words that say nothing about what the code does.
+const FLUFF: [&str; 20] = [
+    "revolutionary",
+    "cutting-edge",
+    "blazing",
+    "blazingly",
+    "seamless",
+    "seamlessly",
+    "world-class",
+    "game-changing",
+    "next-generation",
+    "state-of-the-art",
+    "powerful",
+    "robust",
+    "effortless",
+    "effortlessly",
+    "ultimate",
+    "best-in-class",
+    "unparalleled",
+    "innovative",
+    "supercharge",
+    "leverage",
+];
+
+/// Extensions of the file names a section may mention.
+const FILE_EXTENSIONS: [&str; 22] = [
+    "rs", "py", "js", "mjs", "ts", "tsx", "jsx", "purs", "hs", "go", "java", "kt", "c", "h", "cpp",
+    "rb", "toml", "json", "yaml", "yml", "lock", "cfg",
+];
+
+/// Sensible lengths of a section in characters.
+fn length_range(section: &str) -> (usize, usize) {
+    match section {
+        "name" | "version" | "license" => (1, 40),
+        "tldr" => (40, 300),
+        _ => (20, 3000),
+    }
+}
+
+/// The number of candidates from `--candidates`.
+pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<Option<usize>> {
+    match matches.value_of("candidates") {
+        Some(count) => match count.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(Some(count)),
+            Ok(_) => Err(Error::config(
+                "Invalid number of candidates 0.",
+                Some("--candidates expects a positive number."),
+            )),
+            Err(e) => Err(Error::config_caused_by(
+                &format!("Invalid number of candidates {}.", count),
+                Some("--candidates expects a positive number."),
+                e,
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// A candidate's score together with the reasons that make it up.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoredCandidate {
+    pub text: String,
+    pub score: i32,
+    pub reasons: Vec<(String, i32)>,
+}
+
+impl ScoredCandidate {
+    pub fn explanation(&self) -> String {
+        if self.reasons.is_empty() {
+            return "no issues".to_string();
+        }
+        self.reasons
+            .iter()
+            .map(|(reason, points)| format!("{} {:+}", reason, points))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Words of a text that look like file names, e.g. `main.rs` or `src/lib.rs`.
+fn file_names(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| "`'\"()[]{},:;!?".contains(c))
+                .trim_end_matches('.')
+        })
+        .filter(|word| !word.contains("://"))
+        .filter(|word| {
+            let file = path::Path::new(word);
+            let has_stem = file
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(|stem| !stem.is_empty())
+                .unwrap_or(false);
+            let extension = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+            has_stem && FILE_EXTENSIONS.contains(&extension)
+        })
+        .collect()
+}
+
+/// Whether a file name mentioned in a section is one of the listed files.
+fn is_listed(name: &str, files: &[path::PathBuf]) -> bool {
+    let name = name.trim_start_matches("./");
+    files.iter().any(|file| {
+        let file = file.strip_prefix(".").unwrap_or(file).to_string_lossy();
+        file == name || file.ends_with(&format!("/{}", name))
+    })
+}
+
+/// Scores a candidate by its length, the file names it makes up and the
+/// marketing words it uses. Higher is better, a flawless candidate scores 0.
+pub fn score(section: &str, text: &str, files: &[path::PathBuf]) -> ScoredCandidate {
+    let mut reasons: Vec<(String, i32)> = Vec::new();
+
+    let length = text.trim().chars().count();
+    let (min, max) = length_range(section);
+    if length < min {
+        reasons.push((format!("too short ({} chars)", length), -30));
+    } else if length > max {
+        reasons.push((format!("too long ({} chars)", length), -20));
+    }
+    for name in file_names(text) {
+        if !is_listed(name, files) {
+            reasons.push((format!("unknown file `{}`", name), -25));
+        }
+    }
+    let lowercase = text.to_lowercase();
+    for word in lowercase.split(|c: char| !c.is_alphanumeric() && c != '-') {
+        if FLUFF.contains(&word) {
+            reasons.push((format!("fluff \"{}\"", word), -10));
+        }
+    }
+    let exclamations = text.matches('!').count() as i32;
+    if exclamations > 0 {
+        reasons.push(("exclamation marks".to_string(), -5 * exclamations));
+    }
+
+    ScoredCandidate {
+        text: text.to_string(),
+        score: reasons.iter().map(|(_, points)| points).sum(),
+        reasons,
+    }
+}
+
+/// The candidates of a section, best first, and which one was chosen.
+#[derive(Debug, Clone)]
+pub struct SectionCandidates {
+    pub section: String,
+    pub candidates: Vec<ScoredCandidate>,
+    pub chosen: usize,
+}
+
+/// How a section was chosen, for the run manifest.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CandidateRecord {
+    pub section: String,
+    pub chosen: ScoredCandidate,
+    pub rejected: Vec<ScoredCandidate>,
+}
+
+impl SectionCandidates {
+    pub fn chosen(&self) -> &ScoredCandidate {
+        &self.candidates[self.chosen]
+    }
+
+    pub fn record(&self) -> CandidateRecord {
+        CandidateRecord {
+            section: self.section.clone(),
+            chosen: self.chosen().clone(),
+            rejected: self
+                .candidates
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != self.chosen)
+                .map(|(_, candidate)| candidate.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Ranks the alternatives the backend returned for each section and puts
+/// the best one into the classification. Ties keep the backend's order.
+pub fn choose_best(
+    classification: &mut classification::Classification,
+    files: &[path::PathBuf],
+) -> Vec<SectionCandidates> {
+    let mut chosen = Vec::new();
+    for section in classification::SECTIONS.iter() {
+        let mut candidates: Vec<ScoredCandidate> = classification
+            .candidates(section)
+            .iter()
+            .map(|text| score(section, text, files))
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+        candidates.sort_by_key(|candidate| -candidate.score);
+        classification.set_section(section, Some(candidates[0].text.clone()));
+        chosen.push(SectionCandidates {
+            section: section.to_string(),
+            candidates,
+            chosen: 0,
+        });
+    }
+    chosen
+}
+
+/// Puts the candidates chosen in an earlier run back into a replayed
+/// classification.
+pub fn apply_records(
+    classification: &mut classification::Classification,
+    records: &[CandidateRecord],
+) {
+    for record in records.iter() {
+        classification.set_section(&record.section, Some(record.chosen.text.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<path::PathBuf> {
+        vec![
+            path::PathBuf::from("./src/main.rs"),
+            path::PathBuf::from("./Cargo.toml"),
+        ]
+    }
+
+    #[test]
+    fn flawless_candidates_score_zero() {
+        let candidate = score(
+            "tldr",
+            "Generates a README from the code in `src/main.rs` and the Cargo.toml manifest.",
+            &files(),
+        );
+        assert_eq!(candidate.score, 0, "{}", candidate.explanation());
+        assert_eq!(candidate.explanation(), "no issues");
+    }
+
+    #[test]
+    fn made_up_files_fluff_and_length_cost_points() {
+        let candidate = score(
+            "tldr",
+            "A blazingly fast, robust tool! See lib.rs and https://example.com/x.rs.",
+            &files(),
+        );
+        let reasons: Vec<&str> = candidate
+            .reasons
+            .iter()
+            .map(|(reason, _)| reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "unknown file `lib.rs`",
+                "fluff \"blazingly\"",
+                "fluff \"robust\"",
+                "exclamation marks"
+            ]
+        );
+        assert_eq!(candidate.score, -25 - 10 - 10 - 5);
+        assert_eq!(score("tldr", "Short.", &files()).score, -30);
+        assert_eq!(score("name", &"x".repeat(41), &files()).score, -20);
+    }
+
+    #[test]
+    fn choose_best_keeps_the_backend_order_on_ties() {
+        let mut classification: classification::Classification = serde_json::from_str(
+            r#"{
+                "name": "cli", "tldr": "", "usage": "", "version": null, "license": null,
+                "candidates": {"tldr": [
+                    "A powerful tool that reads src/app.rs to write a README file.",
+                    "Writes a README from the code of the project, first of two ties.",
+                    "Writes a README from the code of the project, second of two ties."
+                ]}
+            }"#,
+        )
+        .unwrap();
+        let chosen = choose_best(&mut classification, &files());
+        assert_eq!(chosen.len(), 1);
+        assert!(classification.tldr.contains("first of two ties"));
+
+        let record = chosen[0].record();
+        assert_eq!(record.chosen.text, classification.tldr);
+        assert_eq!(record.rejected.len(), 2);
+        assert!(record.rejected[1].score < 0);
+
+        classification.tldr = String::new();
+        apply_records(&mut classification, &[record]);
+        assert!(classification.tldr.contains("first of two ties"));
+    }
+}
@@ -0,0 +1,552 @@
+extern crate clap;
+
+use crate::api::client;
+use crate::cmd::badges;
+use crate::cmd::scribe::create_task;
+use crate::cmd::summarization;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::git;
+use crate::http;
+use crate::types::Result;
+use console::style;
+use console::Emoji;
+use std::path;
+
+static PEN: Emoji<'_, '_> = Emoji("🖋", "=>");
+static CROSS_MARK: Emoji<'_, '_> = Emoji("🔥", "X");
+static HISTORY: Emoji<'_, '_> = Emoji("📜", "H");
+static SUMMARY: Emoji<'_, '_> = Emoji("🗞", "S");
+
+pub const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+const UNRELEASED: &str = "Unreleased";
+
+/// The sections of Keep a Changelog, in their order.
+const SECTIONS: [&str; 6] = [
+    "Added",
+    "Changed",
+    "Deprecated",
+    "Removed",
+    "Fixed",
+    "Security",
+];
+
+const HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).";
+
+/// A changelog line made from a commit.
+#[derive(Debug, Clone)]
+struct Entry {
+    section: &'static str,
+    scope: Option<String>,
+    description: String,
+    breaking: bool,
+    commit: String,
+}
+
+/// The Keep a Changelog section of a conventional commit type. Types that
+/// don't change what users get, like `docs` or `ci`, have none.
+fn section_of(commit_type: &str) -> Option<Option<&'static str>> {
+    match commit_type {
+        "feat" | "feature" => Some(Some("Added")),
+        "fix" | "bugfix" => Some(Some("Fixed")),
+        "perf" | "refactor" | "change" => Some(Some("Changed")),
+        "deprecate" | "deprecated" => Some(Some("Deprecated")),
+        "remove" | "revert" => Some(Some("Removed")),
+        "security" => Some(Some("Security")),
+        "docs" | "doc" | "chore" | "test" | "tests" | "ci" | "build" | "style" => Some(None),
+        _ => None,
+    }
+}
+
+/// Splits a conventional commit summary like `feat(parser)!: add x` into its
+/// type, scope, breaking marker and description.
+fn conventional(summary: &str) -> Option<(String, Option<String>, bool, String)> {
+    let (head, description) = summary.split_once(':')?;
+    let (head, breaking) = match head.strip_suffix('!') {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let (commit_type, scope) = match head.split_once('(') {
+        Some((commit_type, scope)) => (commit_type, Some(scope.strip_suffix(')')?.trim())),
+        None => (head, None),
+    };
+    if commit_type.is_empty() || !commit_type.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((
+        commit_type.to_lowercase(),
+        scope.filter(|scope| !scope.is_empty()).map(str::to_string),
+        breaking,
+        description.trim().to_string(),
+    ))
+}
+
+/// The changelog entry of a commit. Commits that don't follow conventional
+/// commits are listed as changes unless `conventional_only` is set.
+fn entry(commit: &git::Commit, conventional_only: bool) -> Option<Entry> {
+    let summary = commit.summary();
+    if summary.is_empty() || summary.starts_with("fixup!") || summary.starts_with("squash!") {
+        return None;
+    }
+    let body = commit.body();
+    let breaking_note = body.contains("BREAKING CHANGE:") || body.contains("BREAKING-CHANGE:");
+    let parsed = conventional(summary)
+        .and_then(|parsed| section_of(&parsed.0).map(|section| (parsed, section)));
+    match parsed {
+        Some(((_, scope, breaking, description), section)) => {
+            let breaking = breaking || breaking_note;
+            // Breaking changes are listed whatever their type.
+            let section = match (section, breaking) {
+                (Some(section), _) => section,
+                (None, true) => "Changed",
+                (None, false) => return None,
+            };
+            Some(Entry {
+                section,
+                scope,
+                description,
+                breaking,
+                commit: commit.id.clone(),
+            })
+        }
+        None if conventional_only => None,
+        None => Some(Entry {
+            section: "Changed",
+            scope: None,
+            description: summary.to_string(),
+            breaking: breaking_note,
+            commit: commit.id.clone(),
+        }),
+    }
+}
+
+/// The version of a tag as shown in headings, `v1.2.0` becomes `1.2.0`.
+fn version(tag: &str) -> &str {
+    match tag.strip_prefix('v') {
+        Some(version) if version.starts_with(|c: char| c.is_ascii_digit()) => version,
+        _ => tag,
+    }
+}
+
+/// A release ready to be rendered.
+#[derive(Debug)]
+struct Release {
+    label: String,
+    tag: Option<String>,
+    previous_tag: Option<String>,
+    date: Option<String>,
+    entries: Vec<Entry>,
+    summary: Option<String>,
+}
+
+impl Release {
+    fn new(release: &git::Release, conventional_only: bool) -> Release {
+        Release {
+            label: release
+                .tag
+                .as_deref()
+                .map(version)
+                .unwrap_or(UNRELEASED)
+                .to_string(),
+            tag: release.tag.clone(),
+            previous_tag: release.previous_tag.clone(),
+            date: release.date.clone(),
+            entries: release
+                .commits
+                .iter()
+                .filter_map(|commit| entry(commit, conventional_only))
+                .collect(),
+            summary: None,
+        }
+    }
+
+    /// The entries as a bulleted list per section.
+    fn render_entries(&self, github: Option<&(String, String)>) -> String {
+        let mut sections = Vec::new();
+        for section in SECTIONS.iter() {
+            let entries: Vec<String> = self
+                .entries
+                .iter()
+                .filter(|entry| entry.section == *section)
+                .map(|entry| {
+                    let short_id = &entry.commit[..entry.commit.len().min(7)];
+                    let commit = match github {
+                        Some((owner, repo)) => format!(
+                            "[{}](https://github.com/{}/{}/commit/{})",
+                            short_id, owner, repo, entry.commit
+                        ),
+                        None => short_id.to_string(),
+                    };
+                    format!(
+                        "- {}{}{} ({})",
+                        if entry.breaking { "**BREAKING:** " } else { "" },
+                        entry
+                            .scope
+                            .as_ref()
+                            .map(|scope| format!("**{}:** ", scope))
+                            .unwrap_or_default(),
+                        entry.description,
+                        commit
+                    )
+                })
+                .collect();
+            if !entries.is_empty() {
+                sections.push(format!("### {}\n\n{}", section, entries.join("\n")));
+            }
+        }
+        sections.join("\n\n")
+    }
+
+    fn render(&self, github: Option<&(String, String)>) -> String {
+        let mut block = match &self.date {
+            Some(date) => format!("## [{}] - {}", self.label, date),
+            None => format!("## [{}]", self.label),
+        };
+        if let Some(summary) = self.summary.as_ref() {
+            block.push_str(&format!("\n\n{}", summary.trim()));
+        }
+        let entries = self.render_entries(github);
+        if entries.is_empty() {
+            block.push_str("\n\nNo notable changes.");
+        } else {
+            block.push_str(&format!("\n\n{}", entries));
+        }
+        block
+    }
+
+    /// The link reference of the heading, comparing to the previous tag.
+    fn link(&self, owner: &str, repo: &str) -> String {
+        let target = self.tag.as_deref().unwrap_or("HEAD");
+        let url = match &self.previous_tag {
+            Some(previous) => format!(
+                "https://github.com/{}/{}/compare/{}...{}",
+                owner, repo, previous, target
+            ),
+            None if self.tag.is_some() => {
+                format!(
+                    "https://github.com/{}/{}/releases/tag/{}",
+                    owner, repo, target
+                )
+            }
+            None => format!("https://github.com/{}/{}/commits/HEAD", owner, repo),
+        };
+        format!("[{}]: {}", self.label, url)
+    }
+}
+
+/// An existing changelog split into the text before the first release, the
+/// release blocks by label and the link references at the end.
+struct Existing {
+    header: Option<String>,
+    releases: Vec<(String, String)>,
+    links: Vec<(String, String)>,
+}
+
+fn link_reference(line: &str) -> Option<(String, String)> {
+    let rest = line.strip_prefix('[')?;
+    let (label, url) = rest.split_once("]: ")?;
+    Some((label.to_string(), url.trim().to_string()))
+}
+
+fn parse_existing(markdown: &str) -> Existing {
+    let mut header = Vec::new();
+    let mut releases: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut links = Vec::new();
+    for line in markdown.lines() {
+        if let Some(link) = link_reference(line) {
+            links.push(link);
+        } else if let Some(heading) = line.strip_prefix("## ") {
+            let label = heading
+                .trim_start_matches('[')
+                .split([']', ' '])
+                .next()
+                .unwrap_or(heading)
+                .to_string();
+            releases.push((label, vec![line]));
+        } else if let Some((_, block)) = releases.last_mut() {
+            block.push(line);
+        } else {
+            header.push(line);
+        }
+    }
+    let header = header.join("\n").trim().to_string();
+    Existing {
+        header: Some(header).filter(|header| !header.is_empty()),
+        releases: releases
+            .into_iter()
+            .map(|(label, block)| (label, block.join("\n").trim().to_string()))
+            .collect(),
+        links,
+    }
+}
+
+/// Renders the changelog. Released versions already in `existing` are kept
+/// as they are, so that edits by hand survive, the unreleased changes are
+/// always written anew. Releases only found in `existing` stay at the end.
+fn render(releases: &[Release], existing: Option<&Existing>, origin_url: Option<&str>) -> String {
+    let github = origin_url.and_then(badges::github_repository);
+    let kept = |label: &str| {
+        existing
+            .filter(|_| label != UNRELEASED)
+            .and_then(|existing| existing.releases.iter().find(|(known, _)| known == label))
+            .map(|(_, block)| block.clone())
+    };
+    let mut blocks = vec![existing
+        .and_then(|existing| existing.header.clone())
+        .unwrap_or_else(|| HEADER.to_string())];
+    for release in releases.iter() {
+        blocks.push(kept(&release.label).unwrap_or_else(|| release.render(github.as_ref())));
+    }
+    if let Some(existing) = existing {
+        for (label, block) in existing.releases.iter() {
+            if label != UNRELEASED && !releases.iter().any(|release| release.label == *label) {
+                blocks.push(block.clone());
+            }
+        }
+    }
+
+    let mut links: Vec<(String, String)> = existing
+        .map(|existing| existing.links.clone())
+        .unwrap_or_default();
+    if let Some((owner, repo)) = github.as_ref() {
+        for release in releases.iter() {
+            if let Some(link) = link_reference(&release.link(owner, repo)) {
+                links.retain(|(label, _)| *label != link.0);
+                links.push(link);
+            }
+        }
+    }
+    let mut markdown = blocks.join("\n\n");
+    if !links.is_empty() {
+        markdown.push_str("\n\n");
+        markdown.push_str(
+            &links
+                .iter()
+                .map(|(label, url)| format!("[{}]: {}", label, url))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        );
+    }
+    markdown.push('\n');
+    markdown
+}
+
+/// Adds a summary written by the backend to each release that is rendered
+/// anew. Summaries are cached like the ones of the README.
+async fn summarize_releases(
+    client: client::ApiClient,
+    mut releases: Vec<Release>,
+    existing_labels: Vec<String>,
+) -> Result<Vec<Release>> {
+    let cache_path = summarization::get_summary_cache_path()?;
+    for release in releases.iter_mut() {
+        let kept = release.label != UNRELEASED && existing_labels.contains(&release.label);
+        if kept || release.entries.is_empty() {
+            continue;
+        }
+        let changes = release.render_entries(None);
+        let (summary, _) = summarization::summarize_cached(
+            client.clone(),
+            None,
+            cache_path.clone(),
+            vec![(Some(format!("Changes in {}", release.label)), changes)],
+        )
+        .await?;
+        release.summary = Some(summary).filter(|summary| !summary.trim().is_empty());
+    }
+    Ok(releases)
+}
+
+/// Writes or updates `CHANGELOG.md` at the root of the git repository from
+/// the commits between its tags.
+pub async fn changelog<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
+    println!(
+        "{}  {}",
+        PEN,
+        style("Writing the changelog...").bold().white()
+    );
+    let running = format!("{}", style("Reading the git history...").dim().white());
+    let success = |history: &git::History| {
+        format!(
+            "{} {} {}",
+            HISTORY,
+            style("Git history:").dim().white(),
+            style(format!(
+                "{} releases, {} commits",
+                history.releases.len(),
+                history
+                    .releases
+                    .iter()
+                    .map(|release| release.commits.len())
+                    .sum::<usize>()
+            ))
+            .blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to read the git history 😢").dim().white(),
+            style(e).red()
+        )
+    };
+    let history = create_task(
+        git::history_async(path::PathBuf::from(".")),
+        running,
+        success,
+        failure,
+    )
+    .await?;
+
+    let changelog_path = history.workdir.join(CHANGELOG_FILE);
+    let existing = if changelog_path.exists() {
+        Some(parse_existing(
+            &util::read_utf8_file(&changelog_path).await?,
+        ))
+    } else {
+        None
+    };
+    let conventional_only = matches.is_present("conventional-only");
+    let mut releases: Vec<Release> = history
+        .releases
+        .iter()
+        .map(|release| Release::new(release, conventional_only))
+        .collect();
+
+    if matches.is_present("summarize") {
+        let http_client = http::build_client(&http::HttpSettings::from_matches(matches)?)?;
+        let existing_labels: Vec<String> = existing
+            .as_ref()
+            .map(|existing| {
+                existing
+                    .releases
+                    .iter()
+                    .map(|(label, _)| label.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let running = format!("{}", style("Summarizing releases...").dim().white());
+        let success = |releases: &Vec<Release>| {
+            format!(
+                "{} {} {}",
+                SUMMARY,
+                style("Release summaries:").dim().white(),
+                style(format!(
+                    "{} releases summarized",
+                    releases
+                        .iter()
+                        .filter(|release| release.summary.is_some())
+                        .count()
+                ))
+                .blue()
+            )
+        };
+        let failure = |e: &Error| {
+            format!(
+                "{} {} {}",
+                CROSS_MARK,
+                style("Unable to summarize releases 😢").dim().white(),
+                style(e).red()
+            )
+        };
+        releases = create_task(
+            summarize_releases(
+                client::ApiClient::new(http_client),
+                releases,
+                existing_labels,
+            ),
+            running,
+            success,
+            failure,
+        )
+        .await?;
+    }
+
+    let markdown = render(&releases, existing.as_ref(), history.origin_url.as_deref());
+    util::write_utf8_file(changelog_path.to_string_lossy().to_string(), markdown).await?;
+    println!(
+        "{} {}",
+        style("Changelog written to").dim().white(),
+        style(display_path(&changelog_path)).dim().white()
+    );
+    Ok(())
+}
+
+fn display_path(file: &path::Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|current_dir| {
+            file.strip_prefix(current_dir)
+                .ok()
+                .map(path::Path::to_path_buf)
+        })
+        .unwrap_or_else(|| file.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conventional_splits_type_scope_and_breaking_marker() {
+        assert_eq!(
+            conventional("feat(parser)!: add x"),
+            Some((
+                "feat".to_string(),
+                Some("parser".to_string()),
+                true,
+                "add x".to_string()
+            ))
+        );
+        assert_eq!(
+            conventional("Fix: typo "),
+            Some(("fix".to_string(), None, false, "typo".to_string()))
+        );
+        assert_eq!(
+            conventional("fix(): typo"),
+            Some(("fix".to_string(), None, false, "typo".to_string()))
+        );
+        assert_eq!(conventional("Merge branch 'main'"), None);
+        assert_eq!(conventional("see http://example.com"), None);
+        assert_eq!(conventional("feat(parser: add x"), None);
+        assert_eq!(conventional(": add x"), None);
+    }
+
+    #[test]
+    fn parse_existing_splits_header_releases_and_links() {
+        let existing = parse_existing(
+            "# Changelog\n\nNotes.\n\n## [Unreleased]\n\n### Added\n\n- x\n\n\
+             ## [1.0.0] - 2024-01-01\n\n- first\n\n\
+             [Unreleased]: https://example.com/compare\n\
+             [1.0.0]: https://example.com/1.0.0\n",
+        );
+        assert_eq!(existing.header.as_deref(), Some("# Changelog\n\nNotes."));
+        let labels: Vec<&str> = existing
+            .releases
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["Unreleased", "1.0.0"]);
+        assert_eq!(existing.releases[1].1, "## [1.0.0] - 2024-01-01\n\n- first");
+        assert_eq!(
+            existing.links,
+            vec![
+                (
+                    "Unreleased".to_string(),
+                    "https://example.com/compare".to_string()
+                ),
+                ("1.0.0".to_string(), "https://example.com/1.0.0".to_string())
+            ]
+        );
+        assert!(parse_existing("## 0.1.0\n").header.is_none());
+        assert_eq!(parse_existing("## 0.1.0\n").releases[0].0, "0.1.0");
+    }
+}
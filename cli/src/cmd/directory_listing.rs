@@ -1,92 +1,129 @@
+use crate::error::Error;
+use crate::git;
 use crate::types;
 use std::env;
-use std::fmt;
 use std::path;
 use walkdir;
 
-#[derive(Debug)]
-pub enum DirectoryListingError {
-    GitIgnoreNotFound,
-}
+/// How deep the listing goes, counting the file itself.
+const MAX_DEPTH: usize = 3;
 
-impl std::error::Error for DirectoryListingError {}
+/// The files to document, and those of them that `.gitattributes` marks as
+/// documentation.
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    pub files: Vec<path::PathBuf>,
+    pub documentation: Vec<path::PathBuf>,
+}
 
-impl fmt::Display for DirectoryListingError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            DirectoryListingError::GitIgnoreNotFound => write!(f, "Couldn't determine gitignore."),
-        }
+impl Listing {
+    /// The files that aren't documentation, e.g. for language detection.
+    pub fn code_files(&self) -> Vec<path::PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| !self.documentation.contains(file))
+            .cloned()
+            .collect()
     }
 }
 
-fn is_hidden(entry: &walkdir::DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
+fn file_name(file: &path::Path) -> &str {
+    file.file_name().and_then(|s| s.to_str()).unwrap_or("")
 }
 
-fn is_directory(entry: &walkdir::DirEntry) -> bool {
-    entry.path().is_dir()
+fn is_hidden(file: &path::Path) -> bool {
+    file_name(file).starts_with('.')
 }
 
-fn is_excluded_extension(entry: &walkdir::DirEntry) -> bool {
+fn is_excluded_extension(file: &path::Path) -> bool {
     let excluded_extensions: Vec<&str> = vec!["lock"];
-    entry
-        .file_name()
-        .to_str()
-        .and_then(|s| {
-            let p = path::Path::new(s);
-            p.extension()
-                .and_then(|e| e.to_str())
-                .map(|e| excluded_extensions.contains(&e))
-        })
+    file.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| excluded_extensions.contains(&e))
         .unwrap_or(false)
 }
 
-fn is_excluded_file(entry: &walkdir::DirEntry) -> bool {
+fn is_excluded_file(file: &path::Path) -> bool {
     let excluded_files: Vec<&str> = vec!["package-lock.json"];
-    entry
-        .file_name()
-        .to_str()
-        .and_then(|s| {
-            let p = path::Path::new(s);
-            p.file_name()
-                .and_then(|e| e.to_str())
-                .map(|e| excluded_files.contains(&e))
-        })
-        .unwrap_or(false)
+    excluded_files.contains(&file_name(file))
 }
 
-pub async fn list_directories_async() -> types::Result<Vec<path::PathBuf>> {
-    let handle = tokio::spawn(async { list_directories() });
+fn is_listed(file: &path::Path) -> bool {
+    !is_hidden(file) && !is_excluded_extension(file) && !is_excluded_file(file)
+}
+
+pub async fn list_directories_async(git_tracked: bool) -> types::Result<Listing> {
+    let handle = tokio::spawn(async move { list_directories(git_tracked) });
     handle.await?
 }
 
-pub fn list_directories() -> types::Result<Vec<path::PathBuf>> {
-    let current_dir = env::current_dir().map_err(|e| DirectoryListingError::GitIgnoreNotFound)?;
+/// Lists the files to document. With `git_tracked` the files come from the
+/// git index instead of the file system, falling back to the file system
+/// outside a git repository.
+pub fn list_directories(git_tracked: bool) -> types::Result<Listing> {
+    if git_tracked {
+        if let Some(listing) = list_tracked_files(path::Path::new("."))? {
+            return Ok(listing);
+        }
+    }
+    Ok(Listing {
+        files: walk_directories()?,
+        documentation: Vec::new(),
+    })
+}
+
+/// The files tracked below `directory`, leaving out those marked as vendored
+/// or generated in `.gitattributes`. The same names and depth are skipped as
+/// on the file system.
+fn list_tracked_files(directory: &path::Path) -> types::Result<Option<Listing>> {
+    let tracked_files = match git::tracked_files(directory)? {
+        Some(tracked_files) => tracked_files,
+        None => return Ok(None),
+    };
+    let mut listing = Listing::default();
+    for file in tracked_files.into_iter() {
+        let depth = file
+            .path
+            .components()
+            .filter(|component| matches!(component, path::Component::Normal(_)))
+            .count();
+        if depth > MAX_DEPTH || !is_listed(&file.path) || file.vendored || file.generated {
+            continue;
+        }
+        if file.documentation {
+            listing.documentation.push(file.path.clone());
+        }
+        listing.files.push(file.path);
+    }
+    Ok(Some(listing))
+}
+
+fn walk_directories() -> types::Result<Vec<path::PathBuf>> {
+    let current_dir = env::current_dir().map_err(|e| Error::io(".", e))?;
     let gitignore_path = path::Path::new(".gitignore");
     let gitignore_abs_path = current_dir.join(gitignore_path);
-    let gitignore = gitignore::File::new(gitignore_abs_path.as_path())
-        .map_err(|e| DirectoryListingError::GitIgnoreNotFound)?;
+    let gitignore = gitignore::File::new(gitignore_abs_path.as_path()).map_err(|e| {
+        Error::config_caused_by(
+            "Couldn't read .gitignore.",
+            Some("Run skriptorium from the root of a project that has a .gitignore file."),
+            e,
+        )
+    })?;
 
-    let cur_dir = env::current_dir().unwrap();
+    let cur_dir = current_dir.clone();
 
     let results: Vec<path::PathBuf> = walkdir::WalkDir::new(".")
-        .max_depth(3)
+        .max_depth(MAX_DEPTH)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| {
-            !is_hidden(e) && !is_excluded_extension(e) && !is_directory(e) && !is_excluded_file(e)
-        })
+        .filter(|e| !e.path().is_dir() && is_listed(e.path()))
         .filter_map(|entry| {
             let entry_path = path::PathBuf::from(entry.path());
             let entry_path2 = entry_path.clone();
             let abs_path = cur_dir.join(entry_path);
             abs_path.canonicalize().ok().and_then(|used_path| {
                 match gitignore.is_excluded(used_path.as_path()) {
-                    Err(err) => None,
+                    Err(_err) => None,
                     Ok(false) => Some(entry_path2),
                     Ok(true) => None,
                 }
@@ -95,3 +132,47 @@ pub fn list_directories() -> types::Result<Vec<path::PathBuf>> {
         .collect();
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracked_files_honor_linguist_attributes() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        let repository = git2::Repository::init(root).unwrap();
+        let mut index = repository.index().unwrap();
+        for (file, content) in [
+            (
+                ".gitattributes",
+                "vendor/** linguist-vendored\n*.pb.rs linguist-generated\ndocs/** linguist-documentation\n",
+            ),
+            ("vendor/lib.js", ""),
+            ("src/api.pb.rs", ""),
+            ("docs/guide.md", "# Guide"),
+            ("src/main.rs", "fn main() {}"),
+            ("a/b/c/deep.rs", ""),
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            index.add_path(path::Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+        std::fs::write(root.join("scratch.rs"), "untracked").unwrap();
+
+        let listing = list_tracked_files(root).unwrap().unwrap();
+        assert_eq!(
+            listing.files,
+            vec![
+                path::PathBuf::from("./docs/guide.md"),
+                path::PathBuf::from("./src/main.rs")
+            ]
+        );
+        assert_eq!(
+            listing.documentation,
+            vec![path::PathBuf::from("./docs/guide.md")]
+        );
+    }
+}
@@ -1,63 +1,67 @@
 use crate::api::classification;
-use crate::cmd::util;
+use crate::api::client;
+use crate::cmd::budget;
+use crate::cmd::directory_listing;
+use crate::cmd::ranking;
 use crate::types;
-use rand::prelude::*;
 use std::path;
-use tokio;
-use tokio::task;
 
-// [TODO] Make this more intelligent by using a local DL model.
+/// How many of the best ranked files the server gets to choose from.
+const SELECTION_CANDIDATES: usize = 30;
+
+/// Picks the files to classify from the ranked files. Unless `local_only`
+/// is set, the server chooses among the best ranked ones, falling back to
+/// the local ranking if it doesn't choose any.
 pub async fn select_files(
+    client: &client::ApiClient,
     detected_language: &Option<String>,
-    relevant_files: &Vec<path::PathBuf>,
+    ranked_files: &[path::PathBuf],
+    local_only: bool,
 ) -> types::Result<Vec<path::PathBuf>> {
-    let file_selection: Vec<String> = relevant_files
+    if local_only {
+        return Ok(ranked_files.to_vec());
+    }
+    let file_selection: Vec<String> = ranked_files
         .iter()
-        .take(30)
+        .take(SELECTION_CANDIDATES)
         .filter_map(|path| path.as_path().to_str())
         .map(|s| s.to_string())
         .collect();
 
-    let selected_files = classification::select(detected_language.clone(), file_selection).await?;
+    let selected_files =
+        classification::select(client, detected_language.clone(), file_selection).await?;
     if !selected_files.files.is_empty() {
         let selected_paths: Vec<path::PathBuf> = selected_files
             .files
             .iter()
-            .map(|file| path::PathBuf::from(file))
+            .map(path::PathBuf::from)
             .collect();
-        let candidate_paths: Vec<path::PathBuf> = relevant_files
+        let candidate_paths: Vec<path::PathBuf> = ranked_files
             .iter()
             .filter(|p| selected_paths.contains(p))
-            .map(|pb| pb.clone())
+            .cloned()
             .collect();
         Ok(candidate_paths)
     } else {
-        Ok(relevant_files.clone())
+        Ok(ranked_files.to_vec())
     }
 }
 
 pub async fn classify(
+    client: client::ApiClient,
     detected_language: Option<String>,
-    relevant_files: Vec<path::PathBuf>,
-) -> types::Result<classification::Classification> {
-    let selected_files = select_files(&detected_language, &relevant_files).await?;
-
-    let mut tasks: Vec<task::JoinHandle<types::Result<(Option<String>, String)>>> = Vec::new();
-    for file_path_buf in selected_files.iter() {
-        let my_path = file_path_buf.clone();
-        let my_path2 = file_path_buf.clone();
-        tasks.push(tokio::spawn(async move {
-            let content = util::read_utf8_file(my_path.as_path()).await?;
-            Ok((my_path2.as_path().to_str().map(|p| p.to_string()), content))
-        }));
-    }
-
-    let file_contents: Vec<
-        std::result::Result<types::Result<(Option<String>, String)>, task::JoinError>,
-    > = futures::future::join_all(tasks).await;
-    let result: types::Result<Vec<(Option<String>, String)>> =
-        file_contents.into_iter().flatten().collect();
-    let files = result?;
-    let classification = classification::classify(detected_language, files).await?;
-    Ok(classification)
+    listing: directory_listing::Listing,
+    budget: budget::Budget,
+    local_only: bool,
+    context: Option<String>,
+    candidates: Option<usize>,
+) -> types::Result<(classification::Classification, budget::PayloadReport)> {
+    let ranked_files = ranking::rank_files(&listing.files, &listing.documentation).paths();
+    let selected_files =
+        select_files(&client, &detected_language, &ranked_files, local_only).await?;
+    let (context, budget) = budget::with_context(budget, context);
+    let (files, report) = budget::fill(budget, &selected_files).await?;
+    let classification =
+        classification::classify(&client, detected_language, files, context, candidates).await?;
+    Ok((classification, report))
 }
@@ -1,12 +1,13 @@
 extern crate clap;
 
 use crate::cmd::util;
-use crate::guesslang;
+use crate::detection;
+use crate::error::Error;
 use crate::types::Result;
-use itertools::Itertools;
 use rust_embed::RustEmbed;
 use std::collections;
 use std::path;
+use std::sync;
 use tokio;
 use tokio::task;
 
@@ -14,47 +15,62 @@ use tokio::task;
 #[folder = "assets/"]
 struct Asset;
 
+/// Detected language identifier and file size in bytes.
+type FileLanguage = (String, u64);
+
 pub async fn multi_language_detection(
     files: Vec<path::PathBuf>,
-    guess_lang_settings: guesslang::classification::GuessLangSettings,
+    detector: sync::Arc<dyn detection::LanguageDetector>,
 ) -> Result<collections::HashMap<String, u64>> {
-    let settings_box = std::sync::Arc::new(guess_lang_settings);
-
-    let mut tasks: Vec<task::JoinHandle<Option<(String, u64)>>> = Vec::new();
+    let mut tasks: Vec<task::JoinHandle<Result<Option<FileLanguage>>>> = Vec::new();
 
     for file_path in files.iter() {
-        let my_box = settings_box.clone();
+        let my_detector = detector.clone();
         let my_path = path::PathBuf::from(file_path);
         tasks.push(tokio::spawn(async move {
             let maybe_file_contents = util::read_utf8_file(my_path.as_path()).await.ok();
             let maybe_file_size = my_path.metadata().ok().map(|md| md.len());
-            maybe_file_contents
-                .zip(maybe_file_size)
-                .and_then(|(file_contents, file_size)| {
-                    if let Some(maybe_classifications) =
-                        guesslang::classification::classify(&*my_box, file_contents).ok()
-                    {
-                        maybe_classifications.first().map(|classification| {
-                            (classification.identifier.to_string(), file_size)
-                        })
-                    } else {
-                        None
-                    }
-                })
+            match maybe_file_contents.zip(maybe_file_size) {
+                Some((file_contents, file_size)) => {
+                    let classifications =
+                        my_detector.classify(my_path.as_path(), file_contents.as_str())?;
+                    Ok(classifications
+                        .first()
+                        .map(|classification| (classification.identifier.to_string(), file_size)))
+                }
+                None => Ok(None),
+            }
         }))
     }
 
-    let results: Vec<std::result::Result<Option<(String, u64)>, task::JoinError>> =
+    let results: Vec<std::result::Result<Result<Option<FileLanguage>>, task::JoinError>> =
         futures::future::join_all(tasks).await;
-    let successful_results: Vec<Option<(String, u64)>> = results.into_iter().flatten().collect();
-    let some_results: Vec<(String, u64)> = successful_results.into_iter().flatten().collect();
+    let mut some_results: Vec<FileLanguage> = Vec::new();
+    let mut first_error: Option<Error> = None;
+    for result in results.into_iter().flatten() {
+        match result {
+            Ok(Some(classification)) => some_results.push(classification),
+            Ok(None) => {}
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    // Single files may fail, but if nothing could be classified the detector
+    // itself is most likely broken.
+    if let (true, Some(err)) = (some_results.is_empty(), first_error) {
+        return Err(Error::detection(
+            "Language detection failed for every file.",
+            err,
+        ));
+    }
 
     let results_map: collections::HashMap<String, u64> = classifications_to_map(&some_results);
     Ok(results_map)
 }
 
 pub fn classifications_to_map(
-    classifications: &Vec<(String, u64)>,
+    classifications: &[(String, u64)],
 ) -> collections::HashMap<String, u64> {
     let mut results_map: collections::HashMap<String, u64> = collections::HashMap::new();
     for (name, size) in classifications.iter() {
@@ -75,7 +91,7 @@ pub fn language_display_name_or_default(language: &str) -> String {
                 .map(|s| s.to_owned())
         })
         .and_then(|json| serde_json::from_str(json.as_str()).ok())
-        .unwrap_or(collections::HashMap::new());
+        .unwrap_or_default();
 
     mappings
         .get(language)
@@ -83,24 +99,87 @@ pub fn language_display_name_or_default(language: &str) -> String {
         .to_string()
 }
 
+/// Markup and data formats. They don't vote for the primary language unless
+/// the repo contains nothing else, or a doc-heavy repo would come out as
+/// Markdown.
+const NON_PROGRAMMING_LANGUAGES: [&str; 9] = [
+    "csv", "html", "ini", "json", "md", "tex", "toml", "xml", "yaml",
+];
+
+fn is_programming_language(language: &str) -> bool {
+    !NON_PROGRAMMING_LANGUAGES.contains(&language)
+}
+
 pub fn get_primary_language(
     classifications: &collections::HashMap<String, u64>,
 ) -> Option<(String, u64)> {
-    let sorted: Vec<(&String, &u64)> = classifications
+    let has_code = classifications
+        .keys()
+        .any(|language| is_programming_language(language));
+    classifications
         .iter()
-        .sorted_by_key(|tuple| tuple.1)
-        .rev()
-        .collect();
-    sorted
-        .first()
-        .map(|tuple| (tuple.0.clone(), tuple.1.clone()))
+        .filter(|(language, _)| !has_code || is_programming_language(language))
+        // Ties are broken by name so that the result doesn't depend on the
+        // iteration order of the map.
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(language, size)| (language.clone(), *size))
 }
 
-pub async fn language_detection(files: Vec<path::PathBuf>) -> Result<Option<String>> {
-    let guesslang_model_path = guesslang::model_downloader::retrieve_model().await?;
-    let guess_lang_settings =
-        guesslang::classification::load_settings(guesslang_model_path).await?;
-    let languages = multi_language_detection(files, guess_lang_settings).await?;
+pub async fn language_detection(
+    detector: sync::Arc<dyn detection::LanguageDetector>,
+    files: Vec<path::PathBuf>,
+) -> Result<Option<String>> {
+    let languages = multi_language_detection(files, detector).await?;
     let determined_language = get_primary_language(&languages).map(|(k, _)| k);
     Ok(determined_language)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(languages: &[(&str, u64)]) -> collections::HashMap<String, u64> {
+        languages
+            .iter()
+            .map(|(language, size)| (language.to_string(), *size))
+            .collect()
+    }
+
+    #[test]
+    fn sums_the_sizes_per_language() {
+        let map = classifications_to_map(&[
+            ("rs".to_string(), 10),
+            ("md".to_string(), 5),
+            ("rs".to_string(), 20),
+        ]);
+        assert_eq!(map, sizes(&[("rs", 30), ("md", 5)]));
+    }
+
+    #[test]
+    fn markup_and_data_dont_outvote_code() {
+        let languages = sizes(&[("md", 9000), ("json", 5000), ("yaml", 800), ("py", 300)]);
+        assert_eq!(
+            get_primary_language(&languages),
+            Some(("py".to_string(), 300))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_markup_without_code() {
+        let languages = sizes(&[("md", 9000), ("json", 5000)]);
+        assert_eq!(
+            get_primary_language(&languages),
+            Some(("md".to_string(), 9000))
+        );
+        assert_eq!(get_primary_language(&sizes(&[])), None);
+    }
+
+    #[test]
+    fn breaks_ties_by_name() {
+        let languages = sizes(&[("ts", 100), ("js", 100)]);
+        assert_eq!(
+            get_primary_language(&languages),
+            Some(("js".to_string(), 100))
+        );
+    }
+}
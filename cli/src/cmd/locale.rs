@@ -0,0 +1,227 @@
+extern crate clap;
+
+use crate::error::Error;
+use crate::types;
+
+/// The fixed strings of the README template.
+#[derive(Debug)]
+pub struct Strings {
+    pub features: &'static str,
+    pub installation: &'static str,
+    pub usage: &'static str,
+    pub configuration: &'static str,
+    pub examples: &'static str,
+    pub project_structure: &'static str,
+    pub architecture: &'static str,
+    pub api_reference: &'static str,
+    pub modules: &'static str,
+    pub contributing: &'static str,
+    /// Introduces the commands of a manifest, `{}` is the manifest.
+    pub from_manifest: &'static str,
+    pub install: &'static str,
+    pub run: &'static str,
+    pub build: &'static str,
+    pub scripts: &'static str,
+    pub tasks: &'static str,
+}
+
+impl Strings {
+    /// The translated title of a group of declared commands.
+    pub fn command_group<'a>(&self, title: &'a str) -> &'a str {
+        match title {
+            "Install" => self.install,
+            "Run" => self.run,
+            "Build" => self.build,
+            "Scripts" => self.scripts,
+            "Tasks" => self.tasks,
+            title => title,
+        }
+    }
+}
+
+/// A language the README can be written in.
+#[derive(Debug)]
+pub struct Locale {
+    pub code: &'static str,
+    /// The name of the language in the language itself.
+    pub name: &'static str,
+    pub strings: Strings,
+}
+
+impl Locale {
+    /// `README.md` for English, `README.de.md` and so on for the others.
+    pub fn readme_file_name(&self) -> String {
+        if self.code == ENGLISH.code {
+            "README.md".to_string()
+        } else {
+            format!("README.{}.md", self.code)
+        }
+    }
+}
+
+pub static ENGLISH: Locale = Locale {
+    code: "en",
+    name: "English",
+    strings: Strings {
+        features: "Features",
+        installation: "Installation",
+        usage: "Usage",
+        configuration: "Configuration",
+        examples: "Examples",
+        project_structure: "Project structure",
+        architecture: "Architecture",
+        api_reference: "API reference",
+        modules: "Modules",
+        contributing: "Contributing",
+        from_manifest: "From `{}`:",
+        install: "Install",
+        run: "Run",
+        build: "Build",
+        scripts: "Scripts",
+        tasks: "Tasks",
+    },
+};
+
+static GERMAN: Locale = Locale {
+    code: "de",
+    name: "Deutsch",
+    strings: Strings {
+        features: "Funktionen",
+        installation: "Installation",
+        usage: "Verwendung",
+        configuration: "Konfiguration",
+        examples: "Beispiele",
+        project_structure: "Projektstruktur",
+        architecture: "Architektur",
+        api_reference: "API-Referenz",
+        modules: "Module",
+        contributing: "Mitwirken",
+        from_manifest: "Aus `{}`:",
+        install: "Installieren",
+        run: "Ausführen",
+        build: "Bauen",
+        scripts: "Skripte",
+        tasks: "Aufgaben",
+    },
+};
+
+static SPANISH: Locale = Locale {
+    code: "es",
+    name: "Español",
+    strings: Strings {
+        features: "Características",
+        installation: "Instalación",
+        usage: "Uso",
+        configuration: "Configuración",
+        examples: "Ejemplos",
+        project_structure: "Estructura del proyecto",
+        architecture: "Arquitectura",
+        api_reference: "Referencia de la API",
+        modules: "Módulos",
+        contributing: "Contribuir",
+        from_manifest: "Desde `{}`:",
+        install: "Instalar",
+        run: "Ejecutar",
+        build: "Compilar",
+        scripts: "Scripts",
+        tasks: "Tareas",
+    },
+};
+
+static JAPANESE: Locale = Locale {
+    code: "ja",
+    name: "日本語",
+    strings: Strings {
+        features: "機能",
+        installation: "インストール",
+        usage: "使い方",
+        configuration: "設定",
+        examples: "使用例",
+        project_structure: "プロジェクト構成",
+        architecture: "アーキテクチャ",
+        api_reference: "API リファレンス",
+        modules: "モジュール",
+        contributing: "コントリビューション",
+        from_manifest: "`{}` から:",
+        install: "インストール",
+        run: "実行",
+        build: "ビルド",
+        scripts: "スクリプト",
+        tasks: "タスク",
+    },
+};
+
+static LOCALES: [&Locale; 4] = [&ENGLISH, &GERMAN, &SPANISH, &JAPANESE];
+
+/// The bundled locale of a language code. Region subtags are ignored, so
+/// `de-AT` is written in `de`.
+pub fn find(code: &str) -> Option<&'static Locale> {
+    let language = code.split(['-', '_']).next().unwrap_or(code).to_lowercase();
+    LOCALES
+        .iter()
+        .copied()
+        .find(|locale| locale.code == language)
+}
+
+/// The locales from `--locale`. English is always written, as `README.md`,
+/// the others follow in the order they were given.
+pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<Vec<&'static Locale>> {
+    let mut locales = vec![&ENGLISH];
+    for code in matches.values_of("locale").into_iter().flatten() {
+        let locale = find(code).ok_or_else(|| {
+            Error::config(
+                &format!("Unsupported locale {}.", code),
+                Some(&format!(
+                    "--locale expects one of {}.",
+                    LOCALES
+                        .iter()
+                        .map(|locale| locale.code)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                )),
+            )
+        })?;
+        if !locales.iter().any(|known| known.code == locale.code) {
+            locales.push(locale);
+        }
+    }
+    Ok(locales)
+}
+
+/// Links to the README in every language, the current one in bold.
+pub fn switcher(locales: &[&Locale], current: &Locale) -> String {
+    locales
+        .iter()
+        .map(|locale| {
+            if locale.code == current.code {
+                format!("**{}**", locale.name)
+            } else {
+                format!("[{}]({})", locale.name, locale.readme_file_name())
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_subtags_are_ignored() {
+        assert_eq!(find("de-AT").map(|locale| locale.code), Some("de"));
+        assert_eq!(find("ES").map(|locale| locale.code), Some("es"));
+        assert!(find("fr").is_none());
+    }
+
+    #[test]
+    fn switcher_links_the_other_readmes() {
+        assert_eq!(
+            switcher(&[&ENGLISH, &GERMAN, &JAPANESE], &GERMAN),
+            format!(
+                "[{}](README.md) | **{}** | [{}](README.ja.md)",
+                ENGLISH.name, GERMAN.name, JAPANESE.name
+            )
+        );
+    }
+}
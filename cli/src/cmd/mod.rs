@@ -1,5 +1,21 @@
+pub mod architecture;
+pub mod badge_svg;
+pub mod badges;
+pub mod budget;
+pub mod candidates;
+pub mod changelog;
 pub mod directory_listing;
 pub mod file_selection;
 pub mod language_detection;
+pub mod locale;
+pub mod modules;
+pub mod ranking;
+pub mod readme;
+pub mod review;
+pub mod run_manifest;
 pub mod scribe;
+pub mod structure;
+pub mod summarization;
+pub mod toc;
+pub mod usage;
 pub mod util;
@@ -0,0 +1,325 @@
+use crate::api::client;
+use crate::cmd::budget;
+use crate::cmd::directory_listing;
+use crate::cmd::ranking;
+use crate::cmd::summarization;
+use crate::cmd::toc;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::extraction::docs;
+use crate::types;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections;
+use std::fs;
+use std::path;
+
+pub const MODULES_DIR: &str = "docs/modules";
+
+/// Files that mark their directory as a module or package.
+const MODULE_MARKERS: [&str; 12] = [
+    "mod.rs",
+    "lib.rs",
+    "Cargo.toml",
+    "__init__.py",
+    "package.json",
+    "index.js",
+    "index.ts",
+    "spago.dhall",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+];
+
+/// Extensions of source files, as opposed to docs, data and assets.
+const SOURCE_EXTENSIONS: [&str; 27] = [
+    "c", "clj", "cpp", "cs", "ex", "exs", "go", "h", "hpp", "hs", "java", "js", "jsx", "kt", "lua",
+    "mjs", "ml", "php", "purs", "py", "rb", "rs", "scala", "sh", "swift", "ts", "tsx",
+];
+
+/// Directories without a marker are still treated as a module if they hold
+/// at least this many source files besides tests, like PureScript or Java
+/// packages that have no marker file of their own.
+const MIN_MODULE_SOURCE_FILES: usize = 3;
+
+/// How many module summaries are requested at once.
+const MODULE_CONCURRENCY: usize = 4;
+
+/// A directory documented on its own page.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub files: Vec<path::PathBuf>,
+    /// Files marked as documentation in `.gitattributes`.
+    pub documentation_files: Vec<path::PathBuf>,
+}
+
+/// A generated module page.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModulePage {
+    pub name: String,
+    pub summary: String,
+    pub page: String,
+}
+
+fn display_path(file: &path::Path) -> String {
+    file.strip_prefix(".")
+        .unwrap_or(file)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Whether `directory` is marked as a module: by a marker file inside it,
+/// or by a listed Rust module file next to it, like `cmd.rs` for `cmd/`.
+fn has_module_marker(
+    directory: &path::Path,
+    files: &[path::PathBuf],
+    listed_files: &[path::PathBuf],
+) -> bool {
+    files
+        .iter()
+        .filter_map(|file| file.file_name().and_then(|name| name.to_str()))
+        .any(|name| MODULE_MARKERS.contains(&name))
+        || listed_files.contains(&directory.with_extension("rs"))
+}
+
+fn is_source_file(file: &path::Path) -> bool {
+    file.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| SOURCE_EXTENSIONS.contains(&extension))
+        .unwrap_or(false)
+        && !ranking::is_test(file)
+}
+
+/// Splits the listed files into modules, one per directory that has a
+/// module marker or enough source files of its own. The root directory is covered
+/// by the top-level README and never becomes a module.
+pub fn detect_modules(listing: &directory_listing::Listing) -> Vec<Module> {
+    let mut directories: collections::BTreeMap<path::PathBuf, Vec<path::PathBuf>> =
+        collections::BTreeMap::new();
+    for file in listing.files.iter() {
+        if let Some(parent) = file.parent() {
+            directories
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(file.clone());
+        }
+    }
+    directories
+        .into_iter()
+        .filter(|(directory, _)| !display_path(directory).is_empty())
+        .filter_map(|(directory, mut files)| {
+            files.sort();
+            let is_module = has_module_marker(&directory, &files, &listing.files)
+                || files.iter().filter(|file| is_source_file(file)).count()
+                    >= MIN_MODULE_SOURCE_FILES;
+            if is_module {
+                let documentation_files = files
+                    .iter()
+                    .filter(|file| listing.documentation.contains(file))
+                    .cloned()
+                    .collect();
+                Some(Module {
+                    name: display_path(&directory),
+                    files,
+                    documentation_files,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// File name of a module's page, e.g. `src-cmd.md` for `src/cmd`.
+pub fn page_file_name(module_name: &str) -> String {
+    format!("{}.md", module_name.replace(['/', '\\'], "-"))
+}
+
+fn file_name(file: &path::Path) -> String {
+    file.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Quotes the authors' doc comments: module docs in full, item docs by
+/// their first paragraph.
+fn render_docs(documentation: &[docs::FileDocs]) -> String {
+    let mut section = String::from("## Documentation\n");
+    for file in documentation.iter() {
+        section.push_str(&format!("\n### `{}`\n", file_name(&file.path)));
+        for doc in file.docs.iter() {
+            match &doc.item {
+                Some(item) => section.push_str(&format!(
+                    "\n- `{}`: {}",
+                    item.replace('`', "'"),
+                    doc.summary()
+                )),
+                None => {
+                    let quote = doc
+                        .text
+                        .lines()
+                        .map(|line| format!("> {}", line).trim_end().to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    section.push_str(&format!("\n{}\n", quote));
+                }
+            }
+        }
+        section.push('\n');
+    }
+    section
+}
+
+fn render_page(
+    module: &Module,
+    summary: &str,
+    documentation: &[docs::FileDocs],
+    toc_depth: usize,
+) -> String {
+    let mut page = format!("# {}\n\n{}\n\n", module.name, summary.trim());
+    if !documentation.is_empty() {
+        page.push_str(&render_docs(documentation));
+        page.push('\n');
+    }
+    page.push_str("## Files\n");
+    for file in module.files.iter() {
+        // Pages live two levels below the project root.
+        page.push_str(&format!(
+            "\n- [`{}`](../../{})",
+            file_name(file),
+            display_path(file)
+        ));
+    }
+    page.push('\n');
+    toc::update(&page, toc_depth)
+}
+
+async fn document_module(
+    client: client::ApiClient,
+    language: Option<String>,
+    cache_path: path::PathBuf,
+    budget: budget::Budget,
+    module: Module,
+    documentation: Vec<docs::FileDocs>,
+    toc_depth: usize,
+) -> types::Result<ModulePage> {
+    let ranked_files = ranking::rank_files(&module.files, &module.documentation_files).paths();
+    let (files, _) = budget::fill(budget, &ranked_files).await?;
+    let summary = if files.is_empty() {
+        String::new()
+    } else {
+        summarization::summarize_cached(client, language, cache_path, files)
+            .await?
+            .0
+    };
+    Ok(ModulePage {
+        name: module.name.clone(),
+        page: render_page(&module, &summary, &documentation, toc_depth),
+        summary,
+    })
+}
+
+/// Summarizes every module and writes its page to `docs/modules`, quoting
+/// the doc comments of its files.
+pub async fn document_modules(
+    client: client::ApiClient,
+    detected_language: Option<String>,
+    listing: directory_listing::Listing,
+    budget: budget::Budget,
+    documentation: docs::Documentation,
+    toc_depth: usize,
+) -> types::Result<Vec<ModulePage>> {
+    let modules = detect_modules(&listing);
+    let cache_path = summarization::get_summary_cache_path()?;
+    let pages: Vec<ModulePage> = stream::iter(modules)
+        .map(|module| {
+            let module_docs = documentation.for_files(&module.files);
+            document_module(
+                client.clone(),
+                detected_language.clone(),
+                cache_path.clone(),
+                budget,
+                module,
+                module_docs,
+                toc_depth,
+            )
+        })
+        .buffered(MODULE_CONCURRENCY)
+        .try_collect()
+        .await?;
+    write_pages(&pages).await?;
+    Ok(pages)
+}
+
+/// Writes the module pages to `docs/modules`.
+pub async fn write_pages(pages: &[ModulePage]) -> types::Result<()> {
+    let modules_dir = path::Path::new(MODULES_DIR);
+    fs::create_dir_all(modules_dir).map_err(|e| Error::io(modules_dir, e))?;
+    for page in pages.iter() {
+        let page_path = modules_dir.join(page_file_name(&page.name));
+        util::write_utf8_file(page_path.to_string_lossy().to_string(), page.page.clone()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(files: &[&str]) -> directory_listing::Listing {
+        directory_listing::Listing {
+            files: files.iter().map(path::PathBuf::from).collect(),
+            documentation: Vec::new(),
+        }
+    }
+
+    fn module_names(files: &[&str]) -> Vec<String> {
+        detect_modules(&listing(files))
+            .into_iter()
+            .map(|module| module.name)
+            .collect()
+    }
+
+    #[test]
+    fn markers_make_modules() {
+        assert_eq!(
+            module_names(&["./main.rs", "./pkg/__init__.py", "./crate/Cargo.toml"]),
+            vec!["crate", "pkg"]
+        );
+    }
+
+    #[test]
+    fn unmarked_directories_need_source_files() {
+        assert_eq!(
+            module_names(&[
+                "./assets/logo.png",
+                "./assets/logo.svg",
+                "./assets/style.css",
+                "./docs/intro.md",
+                "./docs/usage.md",
+                "./tests/a.rs",
+                "./tests/b.rs",
+                "./tests/c.rs",
+                "./src/AI/Prompt.purs",
+                "./src/AI/Client.purs",
+                "./src/AI/Types.purs",
+            ]),
+            vec!["src/AI"]
+        );
+    }
+
+    #[test]
+    fn rust_module_files_mark_their_directory() {
+        assert_eq!(
+            module_names(&["./src/cmd.rs", "./src/cmd/scribe.rs", "./src/api/client.rs"]),
+            vec!["src/cmd"]
+        );
+    }
+
+    #[test]
+    fn page_names_flatten_paths() {
+        assert_eq!(page_file_name("src/cmd"), "src-cmd.md");
+    }
+}
@@ -0,0 +1,469 @@
+use console::style;
+use std::collections;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path;
+
+/// File names of program and library entrypoints.
+const ENTRYPOINTS: [&str; 18] = [
+    "main.rs",
+    "lib.rs",
+    "index.js",
+    "index.ts",
+    "main.js",
+    "main.ts",
+    "Main.purs",
+    "Main.hs",
+    "main.py",
+    "__main__.py",
+    "app.py",
+    "main.go",
+    "main.c",
+    "main.cpp",
+    "Main.java",
+    "Program.cs",
+    "main.swift",
+    "main.kt",
+];
+
+/// File names of project manifests.
+const MANIFESTS: [&str; 14] = [
+    "Cargo.toml",
+    "package.json",
+    "spago.dhall",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "Gemfile",
+    "composer.json",
+    "mix.exs",
+    "stack.yaml",
+    "CMakeLists.txt",
+    "Makefile",
+];
+
+/// File names of modules that usually re-export a package's public API.
+const API_MODULES: [&str; 6] = [
+    "mod.rs",
+    "__init__.py",
+    "index.mjs",
+    "api.rs",
+    "api.py",
+    "Api.purs",
+];
+
+const EXAMPLE_DIRECTORIES: [&str; 3] = ["examples", "example", "demo"];
+
+const TEST_DIRECTORIES: [&str; 5] = ["test", "tests", "__tests__", "spec", "specs"];
+
+const FIXTURE_DIRECTORIES: [&str; 6] = [
+    "fixtures",
+    "fixture",
+    "__fixtures__",
+    "testdata",
+    "mocks",
+    "__mocks__",
+];
+
+/// Files larger than this are mostly generated or data.
+const LARGE_FILE_BYTES: u64 = 100 * 1024;
+
+/// Only the start of a file is compared with the others.
+const FINGERPRINT_BYTES: u64 = 64 * 1024;
+
+/// Words per shingle, the unit of comparison between files.
+const SHINGLE_WORDS: usize = 5;
+
+/// Files with fewer shingles, like empty `__init__.py` files, are too small
+/// to tell whether they are copies and are never dropped.
+const MIN_SHINGLES: usize = 8;
+
+/// The MinHash signature is split into bands of rows, files that agree on a
+/// whole band are compared.
+const MINHASH_BANDS: usize = 16;
+const MINHASH_ROWS: usize = 4;
+
+/// Estimated share of shared shingles above which a file is a near-duplicate.
+const DUPLICATE_SIMILARITY: f64 = 0.9;
+
+/// A file's score together with the reasons that make it up.
+#[derive(Debug, Clone)]
+pub struct RankedFile {
+    pub path: path::PathBuf,
+    pub score: i32,
+    pub reasons: Vec<(String, i32)>,
+}
+
+impl RankedFile {
+    pub fn explanation(&self) -> String {
+        if self.reasons.is_empty() {
+            return "no particular role".to_string();
+        }
+        self.reasons
+            .iter()
+            .map(|(reason, points)| format!("{} {:+}", reason, points))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// The ranked files and the near-duplicates that were dropped, each with the
+/// file it duplicates.
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    pub files: Vec<RankedFile>,
+    pub duplicates: Vec<(path::PathBuf, path::PathBuf)>,
+}
+
+impl Ranking {
+    pub fn paths(&self) -> Vec<path::PathBuf> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
+}
+
+fn file_name(file: &path::Path) -> &str {
+    file.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+}
+
+fn has_directory(file: &path::Path, directories: &[&str]) -> bool {
+    file.parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|component| component.as_os_str().to_str())
+                .any(|component| directories.contains(&component))
+        })
+        .unwrap_or(false)
+}
+
+fn is_test_file(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name);
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("Test")
+        || stem.ends_with("Spec")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+/// Whether a file is a test, by its directory or its name.
+pub fn is_test(file: &path::Path) -> bool {
+    has_directory(file, &TEST_DIRECTORIES) || is_test_file(file_name(file))
+}
+
+/// Whether a file is an entrypoint or a project manifest.
+pub fn is_key_file(file: &path::Path) -> bool {
+    let name = file_name(file);
+    ENTRYPOINTS.contains(&name) || MANIFESTS.contains(&name) || name.ends_with(".cabal")
+}
+
+fn score(file: &path::Path, documentation: &[path::PathBuf]) -> RankedFile {
+    let name = file_name(file);
+    let mut reasons: Vec<(String, i32)> = Vec::new();
+
+    if ENTRYPOINTS.contains(&name) {
+        reasons.push(("entrypoint".to_string(), 50));
+    }
+    if MANIFESTS.contains(&name) || name.ends_with(".cabal") {
+        reasons.push(("manifest".to_string(), 40));
+    }
+    if name.to_lowercase().starts_with("readme") {
+        reasons.push(("readme".to_string(), 45));
+    }
+    if API_MODULES.contains(&name) {
+        reasons.push(("public API module".to_string(), 15));
+    }
+    if has_directory(file, &EXAMPLE_DIRECTORIES) {
+        reasons.push(("example".to_string(), 20));
+    }
+    if is_test(file) {
+        reasons.push(("test".to_string(), -40));
+    }
+    if documentation.iter().any(|doc| doc == file) {
+        reasons.push(("documentation".to_string(), -30));
+    }
+    if has_directory(file, &FIXTURE_DIRECTORIES) {
+        reasons.push(("fixture".to_string(), -50));
+    }
+    match file.metadata().map(|md| md.len()) {
+        Ok(0) => reasons.push(("empty".to_string(), -20)),
+        Ok(size) if size > LARGE_FILE_BYTES => reasons.push(("large".to_string(), -10)),
+        _ => {}
+    }
+    let depth = file
+        .components()
+        .filter(|component| matches!(component, path::Component::Normal(_)))
+        .count() as i32;
+    if depth > 1 {
+        reasons.push(("depth".to_string(), -3 * (depth - 1)));
+    }
+
+    RankedFile {
+        path: file.to_path_buf(),
+        score: reasons.iter().map(|(_, points)| points).sum(),
+        reasons,
+    }
+}
+
+/// MinHash signature of a file's word shingles. Whitespace is ignored, so
+/// copies that were only reformatted have the same signature.
+struct Fingerprint {
+    signature: Vec<u64>,
+}
+
+impl Fingerprint {
+    /// Estimated Jaccard similarity of the two files' shingles.
+    fn similarity(&self, other: &Fingerprint) -> f64 {
+        let equal = self
+            .signature
+            .iter()
+            .zip(other.signature.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+        equal as f64 / self.signature.len() as f64
+    }
+
+    fn bands(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.signature.chunks(MINHASH_ROWS).map(hash).enumerate()
+    }
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One of the MinHash functions, a splitmix64 finalizer seeded per row.
+fn mix(value: u64, seed: u64) -> u64 {
+    let mut z = value ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fingerprints the start of a text file, `None` for binary files and files
+/// too small to compare.
+fn fingerprint(file: &path::Path) -> Option<Fingerprint> {
+    let mut content = Vec::new();
+    fs::File::open(file)
+        .ok()?
+        .take(FINGERPRINT_BYTES)
+        .read_to_end(&mut content)
+        .ok()?;
+    if content.contains(&0) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&content);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let shingles: collections::HashSet<u64> = words.windows(SHINGLE_WORDS).map(hash).collect();
+    if shingles.len() < MIN_SHINGLES {
+        return None;
+    }
+    let signature = (0..(MINHASH_BANDS * MINHASH_ROWS) as u64)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| mix(*shingle, seed))
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect();
+    Some(Fingerprint { signature })
+}
+
+/// Ranks files by their role in the project, highest score first. Ties are
+/// broken by path so that the ranking doesn't depend on the listing order.
+/// Of near-duplicate files only the best ranked one is kept. Files in
+/// `documentation` are marked as such in `.gitattributes` and ranked lower.
+pub fn rank_files(files: &[path::PathBuf], documentation: &[path::PathBuf]) -> Ranking {
+    let mut scored: Vec<RankedFile> = files
+        .iter()
+        .map(|file| score(file, documentation))
+        .collect();
+    scored.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+
+    let mut fingerprints: Vec<(Fingerprint, path::PathBuf)> = Vec::new();
+    let mut buckets: collections::HashMap<(usize, u64), Vec<usize>> = collections::HashMap::new();
+    let mut ranking = Ranking {
+        files: Vec::new(),
+        duplicates: Vec::new(),
+    };
+    for file in scored.into_iter() {
+        let fingerprint = match fingerprint(&file.path) {
+            Some(fingerprint) => fingerprint,
+            None => {
+                ranking.files.push(file);
+                continue;
+            }
+        };
+        let candidates: collections::BTreeSet<usize> = fingerprint
+            .bands()
+            .filter_map(|band| buckets.get(&band))
+            .flatten()
+            .copied()
+            .collect();
+        // Candidates are in ranking order, the best ranked match is kept.
+        let duplicate_of = candidates
+            .into_iter()
+            .map(|index| &fingerprints[index])
+            .find(|(other, _)| fingerprint.similarity(other) >= DUPLICATE_SIMILARITY)
+            .map(|(_, original)| original.clone());
+        match duplicate_of {
+            Some(original) => ranking.duplicates.push((file.path, original)),
+            None => {
+                for band in fingerprint.bands() {
+                    buckets.entry(band).or_default().push(fingerprints.len());
+                }
+                fingerprints.push((fingerprint, file.path.clone()));
+                ranking.files.push(file);
+            }
+        }
+    }
+    ranking
+}
+
+/// Prints the ranking with the reasons behind every score.
+pub fn print_explanation(ranking: &Ranking) {
+    println!(
+        "{}",
+        style(format!("{:>6}  {}", "score", "file (reasons)"))
+            .bold()
+            .white()
+    );
+    for file in ranking.files.iter() {
+        println!(
+            "{}  {} {}",
+            style(format!("{:>6}", file.score)).blue(),
+            file.path.display(),
+            style(format!("({})", file.explanation())).dim().white()
+        );
+    }
+    for (duplicate, original) in ranking.duplicates.iter() {
+        println!(
+            "{}  {} {}",
+            style(format!("{:>6}", "-")).dim().white(),
+            duplicate.display(),
+            style(format!("(dropped, duplicate of {})", original.display()))
+                .dim()
+                .white()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(lines: usize, seed: &str) -> String {
+        (0..lines)
+            .map(|i| {
+                format!(
+                    "let value_{} = compute(\"{}\", {}) + offset;\n",
+                    i,
+                    seed,
+                    i * 7
+                )
+            })
+            .collect()
+    }
+
+    fn write(dir: &path::Path, name: &str, content: &str) -> path::PathBuf {
+        let file = dir.join(name);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, content).unwrap();
+        file
+    }
+
+    #[test]
+    fn recognizes_tests_and_key_files() {
+        assert!(is_test(path::Path::new("tests/cli.rs")));
+        assert!(is_test(path::Path::new("src/parser_test.go")));
+        assert!(is_test(path::Path::new("src/app.spec.ts")));
+        assert!(!is_test(path::Path::new("src/contest.rs")));
+        assert!(is_key_file(path::Path::new("src/main.rs")));
+        assert!(is_key_file(path::Path::new("app.cabal")));
+        assert!(!is_key_file(path::Path::new("src/util.rs")));
+    }
+
+    #[test]
+    fn ranks_entrypoints_over_tests_and_fixtures() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            write(
+                dir.path(),
+                "tests/fixtures/sample.rs",
+                &source(3, "fixture"),
+            ),
+            write(dir.path(), "src/util.rs", &source(3, "util")),
+            write(dir.path(), "src/main.rs", &source(3, "main")),
+            write(dir.path(), "tests/cli.rs", &source(3, "cli")),
+        ];
+        let ranked = rank_files(&files, &[]).paths();
+        assert_eq!(
+            ranked,
+            vec![
+                files[2].clone(),
+                files[1].clone(),
+                files[3].clone(),
+                files[0].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_empty_and_tiny_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![
+            write(dir.path(), "a/__init__.py", ""),
+            write(dir.path(), "b/__init__.py", ""),
+            write(dir.path(), "a/mod.rs", "pub mod cmd;\n"),
+            write(dir.path(), "b/mod.rs", "pub mod cmd;\n"),
+        ];
+        let ranking = rank_files(&files, &[]);
+        assert_eq!(ranking.files.len(), 4);
+        assert!(ranking.duplicates.is_empty());
+    }
+
+    #[test]
+    fn drops_reformatted_and_near_duplicate_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = source(60, "original");
+        let reformatted = original.replace("let ", "    let ").replace('\n', "\n\n");
+        let mut edited = original.clone();
+        edited.push_str("let extra = 1;\n");
+        let files = vec![
+            write(dir.path(), "src/lib.rs", &original),
+            write(dir.path(), "vendor/copy/lib.rs", &reformatted),
+            write(dir.path(), "vendor/edited/lib.rs", &edited),
+            write(dir.path(), "src/other.rs", &source(60, "other")),
+        ];
+
+        let ranking = rank_files(&files, &[]);
+
+        assert_eq!(ranking.paths(), vec![files[0].clone(), files[3].clone()]);
+        assert_eq!(
+            ranking.duplicates,
+            vec![
+                (files[1].clone(), files[0].clone()),
+                (files[2].clone(), files[0].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_binary_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut content = source(20, "binary").into_bytes();
+        content.push(0);
+        let file = dir.path().join("image.bin");
+        fs::write(&file, content).unwrap();
+        assert!(fingerprint(&file).is_none());
+    }
+}
@@ -0,0 +1,319 @@
+use crate::api::classification;
+use crate::cmd::badges;
+use crate::cmd::locale;
+use crate::cmd::modules;
+use crate::cmd::toc;
+use crate::cmd::usage;
+use crate::extraction;
+use itertools::Itertools;
+
+/// Generated content that goes into the README besides the classification.
+#[derive(Debug, Default)]
+pub struct Extras {
+    pub module_pages: Vec<modules::ModulePage>,
+    pub api_reference: Option<extraction::ApiReference>,
+    pub declared_usage: Vec<usage::DeclaredUsage>,
+    pub project: badges::ProjectFacts,
+    pub badge_settings: badges::BadgeSettings,
+    /// Deepest heading level in the table of contents, none below level 2.
+    pub toc_depth: usize,
+    /// Annotated tree of the directories and key files.
+    pub structure: Option<String>,
+    /// Mermaid diagram of the module graph.
+    pub architecture: Option<String>,
+    /// Languages the README is written in, linked from each other.
+    pub locales: Vec<&'static locale::Locale>,
+}
+
+fn present(section: &Option<String>) -> Option<&str> {
+    section
+        .as_deref()
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+}
+
+fn push_section(markdown: &mut String, title: &str, content: &str) {
+    markdown.push_str(&format!("\n\n## {}\n\n{}", title, content));
+}
+
+/// Renders the README from a classification. Sections are only emitted when
+/// the classification or the extras contain them, so that version 1
+/// responses render as before. Headings are taken from `locale`.
+pub fn render(
+    result: &classification::Classification,
+    extras: &Extras,
+    locale: &locale::Locale,
+) -> String {
+    let strings = &locale.strings;
+    let badges = badges::badges(result, &extras.project, &extras.badge_settings);
+    let mut markdown = badges::render(&badges, &extras.badge_settings);
+    if extras.locales.len() > 1 {
+        // A paragraph of its own, apart from the badges.
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+        markdown.push_str(&format!(
+            "\n{}\n",
+            locale::switcher(&extras.locales, locale)
+        ));
+    }
+    markdown.push_str(&format!("\n# {}\n\n{}", result.name, result.tldr));
+
+    if let Some(features) = result.features.as_ref().filter(|f| !f.is_empty()) {
+        let list = features
+            .iter()
+            .map(|feature| format!("- {}", feature.trim()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        push_section(&mut markdown, strings.features, &list);
+    }
+    if let Some(installation) = present(&result.installation) {
+        push_section(&mut markdown, strings.installation, installation);
+    }
+    // Commands declared by the project are preferred over generated ones.
+    if !extras.declared_usage.is_empty() {
+        push_section(
+            &mut markdown,
+            strings.usage,
+            &usage::render(&extras.declared_usage, strings),
+        );
+    } else if !result.usage.trim().is_empty() {
+        push_section(&mut markdown, strings.usage, &result.usage);
+    }
+    if let Some(configuration) = present(&result.configuration) {
+        push_section(&mut markdown, strings.configuration, configuration);
+    }
+    if let Some(examples) = present(&result.examples) {
+        push_section(&mut markdown, strings.examples, examples);
+    }
+    if let Some(structure) = extras.structure.as_ref() {
+        push_section(&mut markdown, strings.project_structure, structure);
+    }
+    if let Some(architecture) = extras.architecture.as_ref() {
+        push_section(&mut markdown, strings.architecture, architecture);
+    }
+    if let Some(reference) = extras
+        .api_reference
+        .as_ref()
+        .filter(|reference| reference.item_count() > 0)
+    {
+        push_section(
+            &mut markdown,
+            strings.api_reference,
+            &render_api_reference(reference),
+        );
+    }
+    if !extras.module_pages.is_empty() {
+        push_section(
+            &mut markdown,
+            strings.modules,
+            &render_module_index(&extras.module_pages),
+        );
+    }
+    if let Some(contributing) = present(&result.contributing) {
+        push_section(&mut markdown, strings.contributing, contributing);
+    }
+    toc::update(&markdown, extras.toc_depth)
+}
+
+/// Headings of the sections `render` writes, in the README language.
+fn generated_headings(strings: &locale::Strings) -> [&'static str; 10] {
+    [
+        strings.features,
+        strings.installation,
+        strings.usage,
+        strings.configuration,
+        strings.examples,
+        strings.project_structure,
+        strings.architecture,
+        strings.api_reference,
+        strings.modules,
+        strings.contributing,
+    ]
+}
+
+/// A level 2 section with its heading, or the part before the first one.
+struct Section<'a> {
+    heading: Option<&'a str>,
+    lines: Vec<&'a str>,
+}
+
+impl Section<'_> {
+    fn text(&self) -> String {
+        self.lines.join("\n").trim_end().to_string()
+    }
+}
+
+fn sections(markdown: &str) -> Vec<Section<'_>> {
+    let mut sections = vec![Section {
+        heading: None,
+        lines: Vec::new(),
+    }];
+    let mut in_code = false;
+    for line in markdown.lines() {
+        if toc::is_fence(line) {
+            in_code = !in_code;
+        }
+        match line.strip_prefix("## ").filter(|_| !in_code) {
+            Some(heading) => sections.push(Section {
+                heading: Some(heading.trim()),
+                lines: vec![line],
+            }),
+            None => sections
+                .last_mut()
+                .expect("there is always a section")
+                .lines
+                .push(line),
+        }
+    }
+    sections
+}
+
+/// Merges a freshly rendered README into the one of an earlier run. Sections
+/// with a heading `render` doesn't write are hand-written and stay, after
+/// the generated section they followed. Everything else, including the
+/// table of contents, comes from `generated`, and the table of contents is
+/// rebuilt so that it lists the hand-written sections too.
+pub fn merge(existing: &str, generated: &str, locale: &locale::Locale, toc_depth: usize) -> String {
+    let fresh = sections(generated);
+    let headings = generated_headings(&locale.strings);
+    let is_generated = |heading: &str| {
+        headings.contains(&heading) || fresh.iter().any(|section| section.heading == Some(heading))
+    };
+    // Hand-written sections by the heading of the fresh section they follow,
+    // `None` for the ones that come before any of them.
+    let mut hand_written: Vec<(Option<&str>, String)> = Vec::new();
+    let mut anchor: Option<&str> = None;
+    for section in sections(existing).iter() {
+        match section.heading {
+            Some(heading) if fresh.iter().any(|fresh| fresh.heading == Some(heading)) => {
+                anchor = Some(heading);
+            }
+            // Generated last time but not this time.
+            Some(heading) if is_generated(heading) => {}
+            Some(_) => hand_written.push((anchor, section.text())),
+            None => {}
+        }
+    }
+    if hand_written.is_empty() {
+        return generated.to_string();
+    }
+    let mut chunks: Vec<String> = Vec::new();
+    for section in fresh.iter() {
+        chunks.push(section.text());
+        chunks.extend(
+            hand_written
+                .iter()
+                .filter(|(after, _)| *after == section.heading)
+                .map(|(_, text)| text.clone()),
+        );
+    }
+    let mut merged = chunks.join("\n\n");
+    if generated.ends_with('\n') {
+        merged.push('\n');
+    }
+    toc::update(&merged, toc_depth)
+}
+
+fn render_api_reference(reference: &extraction::ApiReference) -> String {
+    reference
+        .files
+        .iter()
+        .map(|file| {
+            let items = file
+                .items
+                .iter()
+                .sorted_by_key(|item| item.kind)
+                .map(|item| {
+                    let signature = item.signature.replace('`', "'");
+                    // Methods are listed with their type, e.g. `Parser::new`.
+                    if item.name.contains("::") || item.name.contains('.') {
+                        format!("- `{}`: `{}`", item.name, signature)
+                    } else {
+                        format!("- `{}`", signature)
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            format!(
+                "### {}\n\n{}",
+                file.path.strip_prefix(".").unwrap_or(&file.path).display(),
+                items
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+fn render_module_index(pages: &[modules::ModulePage]) -> String {
+    pages
+        .iter()
+        .map(|page| {
+            format!(
+                "- [{}](modules/{})",
+                page.name,
+                modules::page_file_name(&page.name)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classification(json: &str) -> classification::Classification {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn version_one_renders_without_optional_sections() {
+        let result = classification(
+            r#"{"name": "cli", "tldr": "Does things.", "usage": "cli run", "version": null, "license": null}"#,
+        );
+        let markdown = render(&result, &Extras::default(), &locale::ENGLISH);
+        assert!(markdown.contains("# cli\n\nDoes things."));
+        assert!(markdown.contains("## Usage\n\ncli run"));
+        assert!(!markdown.contains("## Installation"));
+        assert!(!markdown.contains("## Features"));
+    }
+
+    #[test]
+    fn present_sections_render_in_order() {
+        let result = classification(
+            r#"{
+                "schema_version": 2,
+                "name": "cli", "tldr": "Does things.", "usage": "cli run",
+                "version": null, "license": null,
+                "features": ["fast"],
+                "installation": "cargo install cli",
+                "contributing": "  "
+            }"#,
+        );
+        let markdown = render(&result, &Extras::default(), &locale::ENGLISH);
+        let features = markdown.find("## Features\n\n- fast").unwrap();
+        let installation = markdown.find("## Installation").unwrap();
+        let usage = markdown.find("## Usage").unwrap();
+        assert!(features < installation && installation < usage);
+        assert!(!markdown.contains("## Contributing"));
+    }
+
+    #[test]
+    fn hand_written_sections_stay_in_place() {
+        let existing = "# cli\n\n<!-- toc -->\n- [Usage](#usage)\n<!-- /toc -->\n\nOld intro.\n\n## Usage\n\nold usage\n\n## Benchmarks\n\nFast.\n\n```sh\n## not a heading\n```\n\n## Installation\n\nold install\n\n## Thanks\n\nEveryone.\n";
+        let generated = "# cli\n\nNew intro.\n\n## Usage\n\nnew usage\n\n## License\n\nMIT\n";
+        let merged = merge(existing, generated, &locale::ENGLISH, 2);
+        assert_eq!(
+            merged,
+            "# cli\n\n<!-- toc -->\n- [Usage](#usage)\n- [Benchmarks](#benchmarks)\n- [Thanks](#thanks)\n- [License](#license)\n<!-- /toc -->\n\nNew intro.\n\n## Usage\n\nnew usage\n\n## Benchmarks\n\nFast.\n\n```sh\n## not a heading\n```\n\n## Thanks\n\nEveryone.\n\n## License\n\nMIT\n"
+        );
+    }
+
+    #[test]
+    fn generated_readmes_replace_generated_ones() {
+        let existing = "# cli\n\n## Usage\n\nold\n";
+        let generated = "# cli\n\n## Installation\n\nnew\n";
+        assert_eq!(merge(existing, generated, &locale::ENGLISH, 3), generated);
+    }
+}
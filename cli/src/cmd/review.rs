@@ -0,0 +1,277 @@
+use crate::api::classification;
+use crate::api::client;
+use crate::cmd::candidates;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::types;
+use console::style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Editor, Select};
+use serde::{Deserialize, Serialize};
+use std::collections;
+use std::path;
+
+/// Where the choices of the last review are kept, next to the run manifest.
+pub const REVIEW_FILE: &str = "docs/skriptorium-review.json";
+
+/// Candidates are listed on one line, cut off after this many characters.
+const MAX_CANDIDATE_WIDTH: usize = 70;
+
+/// What the user chose for a section.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "decision", content = "text", rename_all = "lowercase")]
+pub enum Decision {
+    /// The section with the text that was accepted or edited.
+    Keep(String),
+    Drop,
+}
+
+/// The decisions of a review by section name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Review {
+    pub sections: collections::BTreeMap<String, Decision>,
+}
+
+impl Review {
+    /// Gives a classification the sections as they were kept or dropped,
+    /// e.g. when a run is replayed.
+    pub fn apply(&self, classification: &mut classification::Classification) {
+        for (section, decision) in self.sections.iter() {
+            let text = match decision {
+                Decision::Keep(text) => Some(text.clone()),
+                Decision::Drop => None,
+            };
+            classification.set_section(section, text);
+        }
+    }
+
+    /// Drops the sections that were dropped in the review from another
+    /// classification, e.g. of a localized README.
+    pub fn apply_drops(&self, classification: &mut classification::Classification) {
+        for (section, decision) in self.sections.iter() {
+            if *decision == Decision::Drop {
+                classification.set_section(section, None);
+            }
+        }
+    }
+}
+
+/// The decisions of the last review, or none if there wasn't one.
+pub async fn read() -> types::Result<Review> {
+    let review_file = path::Path::new(REVIEW_FILE);
+    if !review_file.exists() {
+        return Ok(Review::default());
+    }
+    let json = util::read_utf8_file(review_file).await?;
+    serde_json::from_str(&json).map_err(|e| {
+        Error::config_caused_by(
+            &format!("Invalid review file {}.", REVIEW_FILE),
+            Some("Delete it to start the review from the generated sections."),
+            e,
+        )
+    })
+}
+
+pub async fn write(review: &Review) -> types::Result<()> {
+    let json = serde_json::to_string_pretty(review)
+        .map_err(|e| Error::config_caused_by("Couldn't serialize the review.", None, e))?;
+    util::write_utf8_file(REVIEW_FILE.to_string(), json).await
+}
+
+fn prompt_error(e: std::io::Error) -> Error {
+    Error::config_caused_by(
+        "Couldn't read the review choice.",
+        Some("--interactive needs a terminal."),
+        e,
+    )
+}
+
+fn print_section(section: &str, text: Option<&str>, remembered: bool) {
+    let origin = if remembered {
+        " (from the last review)"
+    } else {
+        ""
+    };
+    println!(
+        "\n{}{}",
+        style(section).bold().white(),
+        style(origin).dim().white()
+    );
+    match text {
+        Some(text) => {
+            for line in text.lines() {
+                println!("  {}", line);
+            }
+        }
+        None => println!("  {}", style("(dropped)").dim().white()),
+    }
+}
+
+/// Lets the user pick one of the candidates of a section, best first.
+fn choose_candidate(
+    theme: &ColorfulTheme,
+    section: &str,
+    alternatives: &mut candidates::SectionCandidates,
+) -> types::Result<String> {
+    let items: Vec<String> = alternatives
+        .candidates
+        .iter()
+        .map(|candidate| {
+            let text = candidate.text.split_whitespace().collect::<Vec<&str>>();
+            format!(
+                "{:+4}  {}",
+                candidate.score,
+                util::limit_string(&text.join(" "), MAX_CANDIDATE_WIDTH)
+            )
+        })
+        .collect();
+    alternatives.chosen = Select::with_theme(theme)
+        .with_prompt(format!("Which {} candidate?", section))
+        .items(&items)
+        .default(alternatives.chosen)
+        .interact()
+        .map_err(prompt_error)?;
+    Ok(alternatives.chosen().text.clone())
+}
+
+/// Shows each section of the classification and lets the user accept it,
+/// edit it in `$EDITOR`, have it regenerated, pick another candidate or drop
+/// it. Sections reviewed before start from the last decision.
+pub async fn review(
+    client: &client::ApiClient,
+    mut classification: classification::Classification,
+    previous: &Review,
+    section_candidates: &mut [candidates::SectionCandidates],
+) -> types::Result<(classification::Classification, Review)> {
+    let theme = ColorfulTheme::default();
+    let mut review = Review::default();
+    for name in classification::SECTIONS.iter() {
+        let generated = classification.section(name);
+        let remembered = previous.sections.get(*name);
+        let mut current = match remembered {
+            Some(Decision::Keep(text)) => Some(text.clone()),
+            Some(Decision::Drop) => None,
+            None => generated.clone(),
+        };
+        if current.is_none() && generated.is_none() {
+            continue;
+        }
+        let mut from_last_review = remembered.is_some();
+        let mut alternatives = section_candidates
+            .iter_mut()
+            .find(|candidates| candidates.section == *name)
+            .filter(|candidates| candidates.candidates.len() > 1);
+        let decision = loop {
+            print_section(name, current.as_deref(), from_last_review);
+            let mut choices = vec!["Accept", "Edit in $EDITOR", "Regenerate"];
+            // The title can't be left out.
+            if *name != "name" {
+                choices.push("Drop");
+            }
+            if from_last_review && generated.is_some() && current != generated {
+                choices.push("Use the generated version");
+            }
+            if alternatives.is_some() {
+                choices.push("Choose another candidate");
+            }
+            let choice = Select::with_theme(&theme)
+                .with_prompt(format!("Keep the {} section?", name))
+                .items(&choices)
+                .default(0)
+                .interact()
+                .map_err(prompt_error)?;
+            match choices[choice] {
+                "Accept" => match current.clone() {
+                    Some(text) => break Decision::Keep(text),
+                    None if *name != "name" => break Decision::Drop,
+                    None => println!(
+                        "  {}",
+                        style("The title can't be empty, edit it or use another one.").yellow()
+                    ),
+                },
+                "Edit in $EDITOR" => {
+                    let edited = Editor::new()
+                        .extension(".md")
+                        .edit(current.as_deref().unwrap_or(""))
+                        .map_err(|e| {
+                            Error::config_caused_by(
+                                "Couldn't open the editor.",
+                                Some("Set $EDITOR or $VISUAL to the editor to use."),
+                                e,
+                            )
+                        })?;
+                    // Closing the editor without saving keeps the text.
+                    if let Some(edited) = edited {
+                        current = Some(edited).filter(|text| !text.trim().is_empty());
+                    }
+                }
+                "Regenerate" => {
+                    println!("  {}", style("Regenerating...").dim().white());
+                    let regenerated = classification::regenerate_section(client, name).await?;
+                    match regenerated.section(name) {
+                        Some(text) => current = Some(text),
+                        None => println!(
+                            "  {}",
+                            style("The backend didn't write this section, it is left as it was.")
+                                .yellow()
+                        ),
+                    }
+                }
+                "Drop" => break Decision::Drop,
+                "Use the generated version" => current = generated.clone(),
+                "Choose another candidate" => {
+                    if let Some(alternatives) = alternatives.as_mut() {
+                        current = Some(choose_candidate(&theme, name, alternatives)?);
+                    }
+                }
+                _ => {}
+            }
+            from_last_review = false;
+        };
+        let text = match &decision {
+            Decision::Keep(text) => Some(text.clone()),
+            Decision::Drop => None,
+        };
+        classification.set_section(name, text);
+        review.sections.insert(name.to_string(), decision);
+    }
+    Ok((classification, review))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decisions_are_stored_by_section() {
+        let mut review = Review::default();
+        review
+            .sections
+            .insert("tldr".to_string(), Decision::Keep("Edited.".to_string()));
+        review.sections.insert("usage".to_string(), Decision::Drop);
+        let json = serde_json::to_string(&review).unwrap();
+        assert_eq!(
+            json,
+            r#"{"sections":{"tldr":{"decision":"keep","text":"Edited."},"usage":{"decision":"drop"}}}"#
+        );
+        let read: Review = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.sections["tldr"], Decision::Keep("Edited.".to_string()));
+    }
+
+    #[test]
+    fn drops_apply_to_other_classifications() {
+        let mut review = Review::default();
+        review.sections.insert("usage".to_string(), Decision::Drop);
+        review
+            .sections
+            .insert("tldr".to_string(), Decision::Keep("Kept.".to_string()));
+        let mut localized: classification::Classification = serde_json::from_str(
+            r#"{"name": "cli", "tldr": "Macht Dinge.", "usage": "cli run", "version": null, "license": null}"#,
+        )
+        .unwrap();
+        review.apply_drops(&mut localized);
+        assert_eq!(localized.section("usage"), None);
+        // Kept sections stay in the language of the localized README.
+        assert_eq!(localized.section("tldr").as_deref(), Some("Macht Dinge."));
+    }
+}
@@ -0,0 +1,308 @@
+use crate::api::classification;
+use crate::api::client;
+use crate::cmd::badges;
+use crate::cmd::candidates;
+use crate::cmd::locale;
+use crate::cmd::modules;
+use crate::cmd::readme;
+use crate::cmd::review;
+use crate::cmd::usage;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::extraction;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::path;
+
+pub const RUN_MANIFEST_FILE: &str = "docs/skriptorium-run.json";
+
+/// The README content that doesn't come from the classification, as the
+/// run found it, so that a replay renders the same README.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RecordedExtras {
+    pub module_pages: Vec<modules::ModulePage>,
+    pub api_reference: Option<extraction::ApiReference>,
+    pub declared_usage: Vec<usage::DeclaredUsage>,
+    pub project: badges::ProjectFacts,
+    pub structure: Option<String>,
+    pub architecture: Option<String>,
+}
+
+impl RecordedExtras {
+    pub fn new(extras: &readme::Extras) -> RecordedExtras {
+        RecordedExtras {
+            module_pages: extras.module_pages.clone(),
+            api_reference: extras.api_reference.clone(),
+            declared_usage: extras.declared_usage.clone(),
+            project: extras.project.clone(),
+            structure: extras.structure.clone(),
+            architecture: extras.architecture.clone(),
+        }
+    }
+
+    /// The extras for the README, with the badge and TOC settings of the replay.
+    pub fn into_extras(
+        self,
+        badge_settings: badges::BadgeSettings,
+        toc_depth: usize,
+        locales: Vec<&'static locale::Locale>,
+    ) -> readme::Extras {
+        readme::Extras {
+            module_pages: self.module_pages,
+            api_reference: self.api_reference,
+            declared_usage: self.declared_usage,
+            project: self.project,
+            badge_settings,
+            toc_depth,
+            structure: self.structure,
+            architecture: self.architecture,
+            locales,
+        }
+    }
+}
+
+/// Everything needed to reproduce a run: the settings it used, the exact
+/// requests it sent and what else went into the README.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunManifest {
+    pub version: String,
+    /// The seed of the file shuffle.
+    #[serde(default)]
+    pub seed: u64,
+    pub api_url: String,
+    pub language: Option<String>,
+    pub selected_files: Vec<String>,
+    pub requests: Vec<client::RecordedRequest>,
+    /// The section candidates that were chosen and rejected.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<candidates::CandidateRecord>,
+    /// The decisions of the interactive review, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<review::Review>,
+    #[serde(default)]
+    pub extras: RecordedExtras,
+}
+
+impl RunManifest {
+    pub fn new(
+        seed: u64,
+        client: &client::ApiClient,
+        language: Option<String>,
+        selected_files: &[path::PathBuf],
+        section_candidates: &[candidates::SectionCandidates],
+        review: Option<&review::Review>,
+        extras: &readme::Extras,
+    ) -> RunManifest {
+        RunManifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            seed,
+            api_url: client.base_url().to_string(),
+            language,
+            selected_files: selected_files
+                .iter()
+                .map(|file| file.to_string_lossy().to_string())
+                .collect(),
+            requests: client.recorded_requests(),
+            candidates: section_candidates
+                .iter()
+                .map(|candidates| candidates.record())
+                .collect(),
+            review: review.cloned(),
+            extras: RecordedExtras::new(extras),
+        }
+    }
+}
+
+pub async fn write(file_name: &str, manifest: &RunManifest) -> types::Result<()> {
+    let json = serde_json::to_string_pretty(manifest)
+        .map_err(|e| Error::config_caused_by("Couldn't serialize the run manifest.", None, e))?;
+    util::write_utf8_file(file_name.to_string(), json).await
+}
+
+pub async fn read(file_name: &path::Path) -> types::Result<RunManifest> {
+    let json = util::read_utf8_file(file_name).await?;
+    serde_json::from_str(&json).map_err(|e| {
+        Error::config_caused_by(
+            &format!("Invalid run manifest {}.", file_name.display()),
+            Some("--replay expects a manifest written by skriptorium scribe."),
+            e,
+        )
+    })
+}
+
+/// Sends the recorded requests again, in order, including the ones that were
+/// answered from the cache, and returns the classification of the last
+/// classification request of each locale together with the number of
+/// requests sent. The recorded candidates and review decisions are applied
+/// like in the original run.
+pub async fn replay(
+    client: client::ApiClient,
+    manifest: RunManifest,
+) -> types::Result<(
+    Vec<(&'static locale::Locale, classification::Classification)>,
+    usize,
+)> {
+    let mut results: Vec<(&'static locale::Locale, classification::Classification)> = Vec::new();
+    for request in manifest.requests.iter() {
+        // Regenerated sections are sent again, what the review made of them
+        // is in the recorded decisions.
+        if request.endpoint == "classification" && request.body.get("section").is_none() {
+            let result = client.post(&request.endpoint, &request.body).await?;
+            // Requests without a locale are the English README.
+            let locale = match request.body.get("locale").and_then(|code| code.as_str()) {
+                Some(code) => locale::find(code),
+                None => Some(&locale::ENGLISH),
+            };
+            if let Some(locale) = locale {
+                results.retain(|(known, _)| known.code != locale.code);
+                results.push((locale, result));
+            }
+        } else {
+            client
+                .post::<_, serde_json::Value>(&request.endpoint, &request.body)
+                .await?;
+        }
+    }
+    if results.is_empty() {
+        return Err(Error::config(
+            "The run manifest doesn't contain a classification request.",
+            Some("Replay a manifest of a run that got to the classification step."),
+        ));
+    }
+    // English comes first, like in the original run, with the candidates
+    // that were chosen then.
+    results.sort_by_key(|(locale, _)| locale.code != locale::ENGLISH.code);
+    for (locale, result) in results.iter_mut() {
+        if locale.code == locale::ENGLISH.code {
+            candidates::apply_records(result, &manifest.candidates);
+            if let Some(review) = manifest.review.as_ref() {
+                review.apply(result);
+            }
+        } else if let Some(review) = manifest.review.as_ref() {
+            review.apply_drops(result);
+        }
+    }
+    Ok((results, manifest.requests.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::stub::{self, Reply};
+
+    #[test]
+    fn manifests_without_extras_still_parse() {
+        let manifest: RunManifest = serde_json::from_str(
+            r#"{
+                "version": "0.1.0",
+                "api_url": "http://localhost",
+                "language": "Rust",
+                "selected_files": ["src/main.rs"],
+                "requests": [{"endpoint": "summary", "body": {}}],
+                "candidates": []
+            }"#,
+        )
+        .unwrap();
+        assert!(!manifest.requests[0].cached);
+        assert!(manifest.extras.declared_usage.is_empty());
+        assert!(manifest.extras.structure.is_none());
+    }
+
+    #[test]
+    fn extras_survive_the_manifest() {
+        let extras = readme::Extras {
+            declared_usage: vec![usage::DeclaredUsage {
+                manifest: "Cargo.toml".to_string(),
+                directory: ".".to_string(),
+                groups: vec![usage::CommandGroup {
+                    title: "Binaries".to_string(),
+                    commands: vec!["cargo run --bin cli".to_string()],
+                }],
+            }],
+            structure: Some("src/\n  main.rs".to_string()),
+            architecture: Some("```mermaid\ngraph TD\n```".to_string()),
+            toc_depth: 3,
+            ..readme::Extras::default()
+        };
+        let json = serde_json::to_string(&RecordedExtras::new(&extras)).unwrap();
+        let recorded: RecordedExtras = serde_json::from_str(&json).unwrap();
+        let replayed = recorded.into_extras(badges::BadgeSettings::default(), 2, vec![]);
+
+        assert_eq!(replayed.declared_usage.len(), 1);
+        assert_eq!(
+            replayed.declared_usage[0].groups[0].commands,
+            vec!["cargo run --bin cli"]
+        );
+        assert_eq!(replayed.structure, extras.structure);
+        assert_eq!(replayed.architecture, extras.architecture);
+        // The settings come from the replay, not from the recorded run.
+        assert_eq!(replayed.toc_depth, 2);
+    }
+
+    #[tokio::test]
+    async fn replay_resends_every_request_and_applies_the_review() {
+        let server = stub::serve(|_, _| {
+            Reply::ok(
+                br#"{"name": "cli", "tldr": "Does things.", "usage": "cli run", "version": null, "license": null}"#,
+            )
+        })
+        .await;
+        let requests: Vec<client::RecordedRequest> = serde_json::from_str(
+            r#"[
+                {"endpoint": "summary", "body": {"files": ["src/main.rs"]}, "cached": true},
+                {"endpoint": "classification", "body": {"language": "rs", "files": []}},
+                {"endpoint": "classification", "body": {"language": "rs", "section": "tldr"}},
+                {"endpoint": "classification", "body": {"language": "rs", "locale": "de"}}
+            ]"#,
+        )
+        .unwrap();
+        let mut review = review::Review::default();
+        review.sections.insert(
+            "tldr".to_string(),
+            review::Decision::Keep("Edited by hand.".to_string()),
+        );
+        review
+            .sections
+            .insert("usage".to_string(), review::Decision::Drop);
+        let manifest = RunManifest {
+            version: "0.1.0".to_string(),
+            seed: 7,
+            api_url: server.url.trim_end_matches('/').to_string(),
+            language: Some("rs".to_string()),
+            selected_files: vec!["src/main.rs".to_string()],
+            requests: requests.clone(),
+            candidates: Vec::new(),
+            review: Some(review),
+            extras: RecordedExtras::default(),
+        };
+        let client = client::ApiClient::with_base_url(stub::client(0), &manifest.api_url);
+
+        let (readmes, sent) = replay(client.clone(), manifest).await.unwrap();
+
+        assert_eq!(sent, 4);
+        assert_eq!(server.requests().len(), 4);
+        let replayed = client.recorded_requests();
+        assert_eq!(
+            replayed
+                .iter()
+                .map(|request| (&request.endpoint, &request.body))
+                .collect::<Vec<_>>(),
+            requests
+                .iter()
+                .map(|request| (&request.endpoint, &request.body))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(readmes[0].0.code, locale::ENGLISH.code);
+        assert_eq!(
+            readmes[0].1.section("tldr").as_deref(),
+            Some("Edited by hand.")
+        );
+        assert_eq!(readmes[0].1.section("usage"), None);
+        assert_eq!(readmes[1].0.code, "de");
+        assert_eq!(
+            readmes[1].1.section("tldr").as_deref(),
+            Some("Does things.")
+        );
+        assert_eq!(readmes[1].1.section("usage"), None);
+    }
+}
@@ -1,17 +1,36 @@
 extern crate clap;
 
 use crate::api::classification;
+use crate::api::client;
+use crate::cmd::architecture;
+use crate::cmd::badges;
+use crate::cmd::budget;
+use crate::cmd::candidates;
 use crate::cmd::directory_listing;
 use crate::cmd::file_selection;
 use crate::cmd::language_detection;
+use crate::cmd::locale;
+use crate::cmd::modules;
+use crate::cmd::ranking;
+use crate::cmd::readme;
+use crate::cmd::review;
+use crate::cmd::run_manifest;
+use crate::cmd::structure;
+use crate::cmd::summarization;
+use crate::cmd::toc;
+use crate::cmd::usage;
 use crate::cmd::util;
+use crate::detection;
+use crate::error::{self, Error};
+use crate::extraction;
+use crate::extraction::docs;
+use crate::http;
 use crate::types::Result;
 use console::style;
 use console::Emoji;
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
 use rand::prelude::*;
-use std::fmt;
 use std::future;
 use std::path;
 use std::time::Duration;
@@ -29,27 +48,20 @@ static CROSS_MARK: Emoji<'_, '_> = Emoji("🔥", "X");
 static CLASSIFIED: Emoji<'_, '_> = Emoji("🗄️ ", "C");
 static LANGUAGE: Emoji<'_, '_> = Emoji("🌍", "L");
 static FILES: Emoji<'_, '_> = Emoji("🗂", "L");
+static MODULES: Emoji<'_, '_> = Emoji("📚", "M");
+static API: Emoji<'_, '_> = Emoji("🔎", "A");
+static DOCS: Emoji<'_, '_> = Emoji("📜", "D");
+static ARCHITECTURE: Emoji<'_, '_> = Emoji("🏛", "G");
+static TRANSLATION: Emoji<'_, '_> = Emoji("🌐", "T");
 
-#[derive(Debug)]
-pub enum ScribeError {
-    MissingInputParameter,
-}
-
-impl std::error::Error for ScribeError {}
-
-impl fmt::Display for ScribeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ScribeError::MissingInputParameter => write!(f, "Input file or folder missing."),
-        }
-    }
-}
+/// How many truncated or omitted files are listed after classification.
+const MAX_LEFT_OUT_LINES: usize = 10;
 
 pub async fn create_task<F, Out>(
     task: F,
     running: String,
     success: fn(&Out) -> String,
-    failure: fn(&Box<dyn std::error::Error + Send + Sync>) -> String,
+    failure: fn(&Error) -> String,
 ) -> Result<Out>
 where
     F: future::Future<Output = Result<Out>> + Send + 'static,
@@ -67,10 +79,11 @@ where
     let classification_handle = tokio::spawn(async move {
         let result = task.await;
         let msg = match &result {
-            Ok(inner_result) => success(&inner_result),
+            Ok(inner_result) => success(inner_result),
             Err(err) => failure(err),
         };
-        tx.send(msg).await?;
+        // The receiver only stops listening once it got a message.
+        let _ = tx.send(msg).await;
         result
     });
 
@@ -81,10 +94,10 @@ where
                     pb.finish_with_message(msg);
                     break;
                 }
-                Err(err) => {
+                Err(_err) => {
                     pb.set_message(format!("{}", style(&running).dim().white()));
                     pb.inc(1);
-                    std::thread::sleep(Duration::from_millis(10));
+                    tokio::time::sleep(Duration::from_millis(10)).await;
                 }
             };
         }
@@ -94,18 +107,104 @@ where
     result
 }
 
+/// Lists which candidate was chosen for each section and why.
+fn print_candidates(section_candidates: &[candidates::SectionCandidates]) {
+    for candidates in section_candidates.iter() {
+        let chosen = candidates.chosen();
+        println!(
+            "      {} {}",
+            style(format!("- {}", candidates.section)).dim().white(),
+            style(format!(
+                "best of {} candidates, score {} ({})",
+                candidates.candidates.len(),
+                chosen.score,
+                chosen.explanation()
+            ))
+            .blue()
+        );
+    }
+}
+
+/// Lists the files that were cut or skipped to stay within the payload budget.
+fn print_left_out(report: &budget::PayloadReport) {
+    let lines: Vec<String> = report
+        .truncated
+        .iter()
+        .map(|truncation| {
+            format!(
+                "{} (truncated to {} of {} bytes)",
+                truncation.path.display(),
+                truncation.kept,
+                truncation.total
+            )
+        })
+        .chain(
+            report
+                .omitted
+                .iter()
+                .map(|(path, omission)| format!("{} ({})", path.display(), omission)),
+        )
+        .collect();
+    for line in lines.iter().take(MAX_LEFT_OUT_LINES) {
+        println!("      {}", style(line).dim().white());
+    }
+    if lines.len() > MAX_LEFT_OUT_LINES {
+        println!(
+            "      {}",
+            style(format!("...and {} more", lines.len() - MAX_LEFT_OUT_LINES))
+                .dim()
+                .white()
+        );
+    }
+}
+
+/// Warns about manifests that were left out of the Usage section.
+fn print_skipped_manifests(errors: &[Error]) {
+    for error in errors.iter() {
+        println!(
+            "{} {}",
+            style("Skipped a manifest for the Usage section.").yellow(),
+            style(error::report(error, false)).dim().white()
+        );
+    }
+}
+
 pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
-    let input_file = matches
+    let _input_file = matches
         .value_of("INPUT")
-        .ok_or(ScribeError::MissingInputParameter)?;
+        .ok_or_else(|| Error::config("Input file or folder missing.", None))?;
+    let http_settings = http::HttpSettings::from_matches(matches)?;
+    let http_client = http::build_client(&http_settings)?;
+    let payload_budget = budget::Budget::from_matches(matches)?;
+    let badge_settings = badges::BadgeSettings::from_matches(matches)?;
+    let toc_depth = toc::depth_from_matches(matches)?;
+    let architecture_settings = architecture::ArchitectureSettings::from_matches(matches)?;
+    let structure_depth = structure::depth_from_matches(matches)?;
+    let locales = locale::from_matches(matches)?;
+    let candidate_count = candidates::from_matches(matches)?;
     println!("{}  {}", PEN, style("Scribing now...").bold().white());
 
+    if let Some(manifest_file) = matches.value_of("replay") {
+        return replay(http_client, manifest_file, badge_settings, toc_depth).await;
+    }
+    let seed = match matches.value_of("seed") {
+        Some(seed) => seed.parse::<u64>().map_err(|e| {
+            Error::config_caused_by(
+                &format!("Invalid seed {}.", seed),
+                Some("--seed expects a non-negative number."),
+                e,
+            )
+        })?,
+        None => rand::random(),
+    };
+
     let running = format!(
         "{}",
         style("Scanning repo for source files...").dim().white()
     );
 
-    let success = |files: &Vec<path::PathBuf>| {
+    let success = |listing: &directory_listing::Listing| {
+        let files = &listing.files;
         if !files.is_empty() {
             format!(
                 "{}  {} {}",
@@ -123,22 +222,42 @@ pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
         }
     };
 
-    let failure = |e: &Box<dyn std::error::Error + Send + Sync>| {
+    let failure = |e: &Error| {
         format!(
-            "{} {}",
+            "{} {} {}",
             CROSS_MARK,
-            style("Unable to get relevant files 😢").dim().white()
+            style("Unable to get relevant files 😢").dim().white(),
+            style(e).red()
         )
     };
-    let relevant_files = create_task(
-        directory_listing::list_directories_async(),
+    let listing = create_task(
+        directory_listing::list_directories_async(matches.is_present("git-tracked")),
         running,
         success,
         failure,
     )
     .await?;
+    let relevant_files = listing.files.clone();
 
-    let running = format!("{}", style("Running language detection...").dim().white());
+    if matches.is_present("explain-selection") {
+        ranking::print_explanation(&ranking::rank_files(
+            &relevant_files,
+            &listing.documentation,
+        ));
+        return Ok(());
+    }
+
+    let detector = detection::load_detector(&http_client, matches.value_of("model-url")).await?;
+
+    let running = format!(
+        "{}",
+        style(format!(
+            "Running language detection ({})...",
+            detector.name()
+        ))
+        .dim()
+        .white()
+    );
 
     let success = |maybe_language: &Option<String>| match maybe_language {
         Some(language) => {
@@ -156,21 +275,39 @@ pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
             style("Unsure which language that is 🧐").dim().white()
         ),
     };
-    let failure = |e: &Box<dyn std::error::Error + Send + Sync>| {
+    let failure = |e: &Error| {
         format!(
-            "{} {}",
+            "{} {} {}",
             CROSS_MARK,
-            style("Unable to detect language 😢").dim().white()
+            style("Unable to detect language 😢").dim().white(),
+            style(e).red()
         )
     };
     let detected_language = create_task(
-        language_detection::language_detection(relevant_files.clone()),
+        language_detection::language_detection(detector, listing.code_files()),
         running,
         success,
         failure,
     )
     .await?;
 
+    let (declared_usage, skipped_manifests) = usage::detect(relevant_files.clone()).await;
+    print_skipped_manifests(&skipped_manifests);
+    let project = badges::detect(&relevant_files, &detected_language).await?;
+    let documentation = harvest_docs(relevant_files.clone()).await?;
+    let api_reference = if matches.is_present("api-reference") {
+        Some(extract_api_reference(relevant_files.clone()).await?)
+    } else {
+        None
+    };
+    let architecture = if matches.is_present("architecture") {
+        let graph = build_module_graph(relevant_files.clone()).await?;
+        architecture::render(&graph, &architecture_settings)
+    } else {
+        None
+    };
+    let context = classification_context(&documentation, api_reference.as_ref());
+
     // let selected_files = file_selection::select_files(&detected_language, &relevant_files).await?;
 
     // for file in selected_files.iter() {
@@ -179,7 +316,10 @@ pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
 
     let running = format!("{}", style("Running classification...").dim().white());
 
-    let success = |classification: &classification::Classification| {
+    let success = |(classification, report): &(
+        classification::Classification,
+        budget::PayloadReport,
+    )| {
         let tldr = &classification.tldr;
         let usage = &classification.usage;
         let name = &classification.name;
@@ -187,9 +327,11 @@ pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
         let license = &classification.license;
 
         format!(
-            "{} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}",
+            "{} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}\n      {} {}",
             CLASSIFIED,
             style("Classification successful:").dim().white(),
+            style("- payload").dim().white(),
+            style(report.summary()).blue(),
             style("- name").dim().white(),
             style(name.to_string()).blue(),
             style("- tldr").dim().white(),
@@ -212,52 +354,418 @@ pub async fn scribe<'a>(matches: &clap::ArgMatches<'a>) -> Result<()> {
                     .unwrap_or("".to_string())
             )
             .blue(),
+            style("- sections").dim().white(),
+            style(classification.sections().join(", ")).blue(),
         )
     };
-    let failure = |e: &Box<dyn std::error::Error + Send + Sync>| {
+    let failure = |e: &Error| {
         format!(
-            "{} {}",
+            "{} {} {}",
             CROSS_MARK,
-            style("Unable to classify code 😭").dim().white()
+            style("Unable to classify code 😭").dim().white(),
+            style(e).red()
+        )
+    };
+
+    // The files are ranked before they are sent, the shuffle only decides
+    // the order the ranking starts from, which the seed makes reproducible.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut shuffled_listing = listing.clone();
+    shuffled_listing.files.shuffle(&mut rng);
+
+    let api_client = client::ApiClient::new(http_client);
+    let (result, report) = if matches.is_present("map-reduce") {
+        create_task(
+            summarization::classify(
+                api_client.clone(),
+                detected_language.clone(),
+                shuffled_listing,
+                payload_budget,
+                context,
+                candidate_count,
+            ),
+            running,
+            success,
+            failure,
         )
+        .await?
+    } else {
+        create_task(
+            file_selection::classify(
+                api_client.clone(),
+                detected_language.clone(),
+                shuffled_listing,
+                payload_budget,
+                matches.is_present("local-selection"),
+                context,
+                candidate_count,
+            ),
+            running,
+            success,
+            failure,
+        )
+        .await?
+    };
+    print_left_out(&report);
+    let mut result = result;
+    let mut section_candidates = candidates::choose_best(&mut result, &relevant_files);
+    print_candidates(&section_candidates);
+    // Reviewed before anything is written, the choices are kept for the next run.
+    let (result, review) = if matches.is_present("interactive") {
+        let previous = review::read().await?;
+        let (result, review) =
+            review::review(&api_client, result, &previous, &mut section_candidates).await?;
+        review::write(&review).await?;
+        (result, Some(review))
+    } else {
+        (result, None)
     };
+    let mut readmes = vec![(&locale::ENGLISH, result)];
+    for locale in locales.iter().skip(1) {
+        let (locale, mut localized) = localize(&api_client, locale).await?;
+        candidates::choose_best(&mut localized, &relevant_files);
+        if let Some(review) = review.as_ref() {
+            review.apply_drops(&mut localized);
+        }
+        readmes.push((locale, localized));
+    }
 
-    // [TODO] Figure out where to best put this.
-    let mut rng = rand::thread_rng();
-    let mut shuffled_files: Vec<path::PathBuf> = relevant_files.clone();
-    shuffled_files.shuffle(&mut rng);
+    let module_pages = if matches.is_present("per-module") {
+        document_modules(
+            api_client.clone(),
+            detected_language.clone(),
+            listing.clone(),
+            payload_budget,
+            documentation.clone(),
+            toc_depth,
+        )
+        .await?
+    } else {
+        Vec::new()
+    };
+    let structure = if matches.is_present("structure") {
+        let descriptions = structure::Descriptions::new(&module_pages, &documentation);
+        structure::render(&relevant_files, structure_depth, &descriptions)
+    } else {
+        None
+    };
+
+    let extras = readme::Extras {
+        module_pages,
+        api_reference,
+        declared_usage,
+        project,
+        badge_settings,
+        toc_depth,
+        structure,
+        architecture,
+        locales,
+    };
+    write_readme(&readmes, &extras).await?;
+    let manifest = run_manifest::RunManifest::new(
+        seed,
+        &api_client,
+        detected_language,
+        &report.included,
+        &section_candidates,
+        review.as_ref(),
+        &extras,
+    );
+    run_manifest::write(run_manifest::RUN_MANIFEST_FILE, &manifest).await?;
+    println!(
+        "{} {}",
+        style("Run manifest written to").dim().white(),
+        style(run_manifest::RUN_MANIFEST_FILE).dim().white()
+    );
+    Ok(())
+}
+
+/// Writes a README per locale, the badges are drawn once from the first.
+async fn write_readme(
+    readmes: &[(&locale::Locale, classification::Classification)],
+    extras: &readme::Extras,
+) -> Result<()> {
+    if let Some((_, result)) = readmes.first().filter(|_| extras.badge_settings.offline) {
+        let badges = badges::badges(result, &extras.project, &extras.badge_settings);
+        badges::write_svgs(&badges, &extras.badge_settings).await?;
+    }
+    for (locale, result) in readmes.iter() {
+        let file_name = format!("docs/{}", locale.readme_file_name());
+        let mut markdown = readme::render(result, extras, locale);
+        // Hand-written sections of an earlier README are kept.
+        if path::Path::new(&file_name).is_file() {
+            let existing = util::read_utf8_file(path::Path::new(&file_name)).await?;
+            markdown = readme::merge(&existing, &markdown, locale, extras.toc_depth);
+        }
+        util::write_utf8_file(file_name, markdown).await?;
+    }
+    Ok(())
+}
 
-    let result: classification::Classification = create_task(
-        file_selection::classify(detected_language, shuffled_files),
+/// Classifies the selected files again with the README written in `locale`.
+async fn localize(
+    api_client: &client::ApiClient,
+    locale: &'static locale::Locale,
+) -> Result<(&'static locale::Locale, classification::Classification)> {
+    let running = format!(
+        "{}",
+        style(format!("Writing the {} README...", locale.name))
+            .dim()
+            .white()
+    );
+    let success = |(locale, _): &(&locale::Locale, classification::Classification)| {
+        format!(
+            "{} {} {}",
+            TRANSLATION,
+            style("Localized README:").dim().white(),
+            style(format!("{} ({})", locale.name, locale.readme_file_name())).blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to localize the README 😢").dim().white(),
+            style(e).red()
+        )
+    };
+    let api_client = api_client.clone();
+    create_task(
+        async move {
+            classification::classify_localized(&api_client, locale.code)
+                .await
+                .map(|result| (locale, result))
+        },
         running,
         success,
         failure,
     )
-    .await?;
-    let license_badge = result
-        .license
-        .as_ref()
-        .map(|license| {
-            format!(
-                "![{}](https://img.shields.io/badge/license-{}-blue)",
-                license, license
-            )
-        })
-        .unwrap_or("".to_string());
-    let version_badge = result
-        .version
-        .as_ref()
-        .map(|version| {
-            format!(
-                "![{}](https://img.shields.io/badge/version-{}-red)",
-                version, version
-            )
-        })
-        .unwrap_or("".to_string());
-    let markdown = format!(
-        "{} {}\n# {}\n\n{}\n\n## Usage\n\n{}",
-        version_badge, license_badge, result.name, result.tldr, result.usage
+    .await
+}
+
+/// Context sent along with the files. The authors' doc comments come first so
+/// that they are kept over the extracted signatures when the budget is tight.
+fn classification_context(
+    documentation: &docs::Documentation,
+    api_reference: Option<&extraction::ApiReference>,
+) -> Option<String> {
+    let api_reference = api_reference.filter(|reference| reference.item_count() > 0);
+    match (documentation.comment_count(), api_reference) {
+        (0, None) => None,
+        (0, Some(reference)) => Some(format!("API reference\n{}", reference.compact())),
+        (_, None) => Some(format!("Documentation\n{}", documentation.compact())),
+        (_, Some(reference)) => Some(format!(
+            "Documentation\n{}\nAPI reference\n{}",
+            documentation.compact(),
+            reference.compact()
+        )),
+    }
+}
+
+async fn harvest_docs(relevant_files: Vec<path::PathBuf>) -> Result<docs::Documentation> {
+    let running = format!("{}", style("Harvesting doc comments...").dim().white());
+    let success = |documentation: &docs::Documentation| {
+        format!(
+            "{} {} {}",
+            DOCS,
+            style("Doc comments:").dim().white(),
+            style(format!(
+                "{} comments in {} files",
+                documentation.comment_count(),
+                documentation.files.len()
+            ))
+            .blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to harvest doc comments 😢").dim().white(),
+            style(e).red()
+        )
+    };
+    create_task(
+        extraction::harvest_docs(relevant_files),
+        running,
+        success,
+        failure,
+    )
+    .await
+}
+
+async fn build_module_graph(
+    relevant_files: Vec<path::PathBuf>,
+) -> Result<extraction::imports::ModuleGraph> {
+    let running = format!("{}", style("Building the module graph...").dim().white());
+    let success = |graph: &extraction::imports::ModuleGraph| {
+        format!(
+            "{} {} {}",
+            ARCHITECTURE,
+            style("Module graph:").dim().white(),
+            style(format!(
+                "{} modules, {} imports",
+                graph.modules.len(),
+                graph.edges.len()
+            ))
+            .blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to build the module graph 😢").dim().white(),
+            style(e).red()
+        )
+    };
+    create_task(
+        extraction::imports::module_graph(relevant_files),
+        running,
+        success,
+        failure,
+    )
+    .await
+}
+
+async fn extract_api_reference(
+    relevant_files: Vec<path::PathBuf>,
+) -> Result<extraction::ApiReference> {
+    let running = format!("{}", style("Extracting public API...").dim().white());
+    let success = |reference: &extraction::ApiReference| {
+        format!(
+            "{} {} {}",
+            API,
+            style("API reference:").dim().white(),
+            style(format!(
+                "{} public items in {} files",
+                reference.item_count(),
+                reference.files.len()
+            ))
+            .blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to extract the public API 😢").dim().white(),
+            style(e).red()
+        )
+    };
+    create_task(
+        extraction::extract(relevant_files),
+        running,
+        success,
+        failure,
+    )
+    .await
+}
+
+async fn document_modules(
+    api_client: client::ApiClient,
+    detected_language: Option<String>,
+    listing: directory_listing::Listing,
+    payload_budget: budget::Budget,
+    documentation: docs::Documentation,
+    toc_depth: usize,
+) -> Result<Vec<modules::ModulePage>> {
+    let running = format!("{}", style("Documenting modules...").dim().white());
+    let success = |pages: &Vec<modules::ModulePage>| {
+        format!(
+            "{} {} {}",
+            MODULES,
+            style("Module pages:").dim().white(),
+            style(format!(
+                "{} pages written to {}",
+                pages.len(),
+                modules::MODULES_DIR
+            ))
+            .blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to document modules 😭").dim().white(),
+            style(e).red()
+        )
+    };
+    create_task(
+        modules::document_modules(
+            api_client,
+            detected_language,
+            listing,
+            payload_budget,
+            documentation,
+            toc_depth,
+        ),
+        running,
+        success,
+        failure,
+    )
+    .await
+}
+
+/// Sends the requests recorded in a run manifest again and writes the README
+/// from the replayed classification.
+async fn replay(
+    http_client: http::HttpClient,
+    manifest_file: &str,
+    badge_settings: badges::BadgeSettings,
+    toc_depth: usize,
+) -> Result<()> {
+    let manifest = run_manifest::read(path::Path::new(manifest_file)).await?;
+    let api_client = client::ApiClient::with_base_url(http_client, &manifest.api_url);
+    println!(
+        "{} {}",
+        style(format!(
+            "Replaying {} requests of seed {} against",
+            manifest.requests.len(),
+            manifest.seed
+        ))
+        .dim()
+        .white(),
+        style(&manifest.api_url).dim().white()
     );
-    util::write_utf8_file("docs/README.md".to_owned(), markdown).await?;
-    Ok(())
+
+    let running = format!("{}", style("Replaying run...").dim().white());
+    let success = |(readmes, requests): &(
+        Vec<(&locale::Locale, classification::Classification)>,
+        usize,
+    )| {
+        let name = readmes
+            .first()
+            .map(|(_, classification)| classification.name.clone())
+            .unwrap_or_default();
+        format!(
+            "{} {} {}",
+            CLASSIFIED,
+            style("Replay successful:").dim().white(),
+            style(format!("{} ({} requests)", name, requests)).blue()
+        )
+    };
+    let failure = |e: &Error| {
+        format!(
+            "{} {} {}",
+            CROSS_MARK,
+            style("Unable to replay run 😭").dim().white(),
+            style(e).red()
+        )
+    };
+    let recorded_extras = manifest.extras.clone();
+    let (readmes, _) = create_task(
+        run_manifest::replay(api_client, manifest),
+        running,
+        success,
+        failure,
+    )
+    .await?;
+    let locales = readmes.iter().map(|(locale, _)| *locale).collect();
+    let extras = recorded_extras.into_extras(badge_settings, toc_depth, locales);
+    if !extras.module_pages.is_empty() {
+        modules::write_pages(&extras.module_pages).await?;
+    }
+    write_readme(&readmes, &extras).await
 }
@@ -0,0 +1,344 @@
+extern crate clap;
+
+use crate::cmd::modules;
+use crate::cmd::ranking;
+use crate::cmd::util;
+use crate::error::Error;
+use crate::extraction::docs;
+use crate::types;
+use std::collections;
+use std::path;
+
+/// The listing goes three levels deep, so files are at most two directories
+/// below the root.
+pub const DEFAULT_STRUCTURE_DEPTH: usize = 2;
+
+/// Descriptions longer than this are cut off.
+const MAX_DESCRIPTION_LENGTH: usize = 70;
+
+/// Descriptions of directories whose name says what they hold.
+const CONVENTIONAL_DIRECTORIES: [(&str, &str); 22] = [
+    ("src", "Source code"),
+    ("lib", "Library code"),
+    ("bin", "Executables"),
+    ("cmd", "Commands"),
+    ("test", "Tests"),
+    ("tests", "Tests"),
+    ("__tests__", "Tests"),
+    ("spec", "Tests"),
+    ("benches", "Benchmarks"),
+    ("bench", "Benchmarks"),
+    ("examples", "Examples"),
+    ("example", "Examples"),
+    ("docs", "Documentation"),
+    ("doc", "Documentation"),
+    ("scripts", "Scripts"),
+    ("tools", "Development tools"),
+    ("assets", "Static assets"),
+    ("static", "Static assets"),
+    ("public", "Static assets"),
+    ("fixtures", "Test fixtures"),
+    ("config", "Configuration"),
+    ("migrations", "Database migrations"),
+];
+
+/// Files whose module docs describe the directory they are in.
+const MODULE_FILES: [&str; 7] = [
+    "mod.rs",
+    "lib.rs",
+    "main.rs",
+    "__init__.py",
+    "index.js",
+    "index.ts",
+    "index.mjs",
+];
+
+/// The deepest directory level shown, from `--structure-depth`.
+pub fn depth_from_matches(matches: &clap::ArgMatches) -> types::Result<usize> {
+    let depth = match matches.value_of("structure-depth") {
+        Some(depth) => depth,
+        None => return Ok(DEFAULT_STRUCTURE_DEPTH),
+    };
+    let hint = Some("--structure-depth expects a positive number.");
+    let message = format!("Invalid structure depth {}.", depth);
+    match depth.parse::<usize>() {
+        Ok(0) => Err(Error::config(&message, hint)),
+        Ok(depth) => Ok(depth),
+        Err(e) => Err(Error::config_caused_by(&message, hint, e)),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Directory {
+    directories: collections::BTreeMap<String, Directory>,
+    files: collections::BTreeSet<String>,
+}
+
+/// The listed files as a tree of directories, with the leading `./` removed.
+/// Files in hidden directories like `.github` are left out.
+fn build_tree(files: &[path::PathBuf]) -> Directory {
+    let mut root = Directory::default();
+    for file in files.iter() {
+        let file = file.strip_prefix(".").unwrap_or(file);
+        let components: Vec<String> = file
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        if components
+            .iter()
+            .any(|component| component.starts_with('.'))
+        {
+            continue;
+        }
+        if let Some((name, parents)) = components.split_last() {
+            let directory = parents.iter().fold(&mut root, |directory, parent| {
+                directory.directories.entry(parent.clone()).or_default()
+            });
+            directory.files.insert(name.clone());
+        }
+    }
+    root
+}
+
+/// The first sentence of a summary, which has to fit on a tree line.
+fn first_sentence(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let sentence = match text.find(". ") {
+        Some(end) => &text[..end],
+        None => text.trim_end_matches('.'),
+    };
+    util::limit_string(sentence, MAX_DESCRIPTION_LENGTH)
+}
+
+/// Where the one-line descriptions of directories come from.
+pub struct Descriptions<'a> {
+    /// Module summaries from the backend, by directory.
+    pub summaries: collections::BTreeMap<String, &'a str>,
+    pub documentation: &'a docs::Documentation,
+}
+
+impl<'a> Descriptions<'a> {
+    pub fn new(
+        module_pages: &'a [modules::ModulePage],
+        documentation: &'a docs::Documentation,
+    ) -> Descriptions<'a> {
+        Descriptions {
+            summaries: module_pages
+                .iter()
+                .filter(|page| !page.summary.trim().is_empty())
+                .map(|page| (page.name.clone(), page.summary.as_str()))
+                .collect(),
+            documentation,
+        }
+    }
+
+    /// The module doc comment of a directory, from the file that defines
+    /// the module, like `mod.rs` or `__init__.py` inside it or `cmd.rs` next
+    /// to it for `cmd/`. Docs of other files describe only those files.
+    fn module_doc(&self, directory: &str) -> Option<String> {
+        let directory = path::Path::new(".").join(directory);
+        let module_files: Vec<path::PathBuf> = MODULE_FILES
+            .iter()
+            .map(|name| directory.join(name))
+            .chain(std::iter::once(directory.with_extension("rs")))
+            .collect();
+        self.documentation
+            .files
+            .iter()
+            .filter(|file| module_files.contains(&file.path))
+            .flat_map(|file| file.docs.iter())
+            .find(|doc| doc.level != docs::DocLevel::Item)
+            .map(|doc| doc.summary())
+    }
+
+    /// A description from the backend, the authors' module docs, the
+    /// directory name or else the kinds of files in it.
+    fn describe(&self, directory_path: &str, name: &str, directory: &Directory) -> String {
+        if let Some(summary) = self.summaries.get(directory_path) {
+            return first_sentence(summary);
+        }
+        if let Some(doc) = self.module_doc(directory_path) {
+            return first_sentence(&doc);
+        }
+        if let Some((_, description)) = CONVENTIONAL_DIRECTORIES
+            .iter()
+            .find(|(conventional, _)| *conventional == name)
+        {
+            return description.to_string();
+        }
+        file_kinds(directory)
+    }
+}
+
+/// Counts the files below a directory by extension, e.g. `4 .rs, 1 .toml files`.
+fn file_kinds(directory: &Directory) -> String {
+    fn count(directory: &Directory, counts: &mut collections::BTreeMap<String, usize>) {
+        for file in directory.files.iter() {
+            let extension = path::Path::new(file)
+                .extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_else(|| "other".to_string());
+            *counts.entry(extension).or_insert(0) += 1;
+        }
+        for child in directory.directories.values() {
+            count(child, counts);
+        }
+    }
+    let mut counts = collections::BTreeMap::new();
+    count(directory, &mut counts);
+    let total: usize = counts.values().sum();
+    let mut kinds: Vec<(String, usize)> = counts.into_iter().collect();
+    kinds.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let kinds = kinds
+        .iter()
+        .map(|(extension, count)| format!("{} {}", count, extension))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("{} {}", kinds, if total == 1 { "file" } else { "files" })
+}
+
+/// Tree lines without their descriptions, which are aligned afterwards.
+fn tree_lines(
+    directory: &Directory,
+    directory_path: &str,
+    prefix: &str,
+    depth: usize,
+    max_depth: usize,
+    descriptions: &Descriptions,
+    lines: &mut Vec<(String, Option<String>)>,
+) {
+    // Directories first, then the files that are worth pointing out.
+    let files: Vec<&String> = directory
+        .files
+        .iter()
+        .filter(|file| ranking::is_key_file(&path::Path::new(directory_path).join(file)))
+        .collect();
+    let count = directory.directories.len() + files.len();
+    let entries = directory
+        .directories
+        .iter()
+        .map(|(name, child)| (name, Some(child)))
+        .chain(files.into_iter().map(|name| (name, None)));
+    for (i, (name, child)) in entries.enumerate() {
+        let last = i + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        match child {
+            Some(child) => {
+                let child_path = if directory_path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", directory_path, name)
+                };
+                lines.push((
+                    format!("{}{}{}/", prefix, branch, name),
+                    Some(descriptions.describe(&child_path, name, child)),
+                ));
+                if depth < max_depth {
+                    let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                    tree_lines(
+                        child,
+                        &child_path,
+                        &child_prefix,
+                        depth + 1,
+                        max_depth,
+                        descriptions,
+                        lines,
+                    );
+                }
+            }
+            None => lines.push((format!("{}{}{}", prefix, branch, name), None)),
+        }
+    }
+}
+
+/// Renders the directories of the listed files down to `max_depth` and the
+/// entrypoints and manifests in them as a tree, each directory followed by a
+/// one-line description. Returns nothing for projects without directories.
+pub fn render(
+    files: &[path::PathBuf],
+    max_depth: usize,
+    descriptions: &Descriptions,
+) -> Option<String> {
+    let root = build_tree(files);
+    if root.directories.is_empty() || max_depth == 0 {
+        return None;
+    }
+    let mut lines = Vec::new();
+    tree_lines(&root, "", "", 1, max_depth, descriptions, &mut lines);
+    let width = lines
+        .iter()
+        .map(|(entry, _)| entry.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut tree = vec!["```text".to_string(), ".".to_string()];
+    tree.extend(
+        lines
+            .into_iter()
+            .map(|(entry, description)| match description {
+                Some(description) => {
+                    let padding = " ".repeat(width - entry.chars().count());
+                    format!("{}{}  # {}", entry, padding, description)
+                }
+                None => entry,
+            }),
+    );
+    tree.push("```".to_string());
+    Some(tree.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<path::PathBuf> {
+        paths.iter().map(path::PathBuf::from).collect()
+    }
+
+    #[test]
+    fn first_sentences_fit_on_a_line() {
+        assert_eq!(
+            first_sentence("Reads the\n  config. Then more."),
+            "Reads the config"
+        );
+        assert_eq!(first_sentence("One sentence."), "One sentence");
+        assert!(first_sentence(&"word ".repeat(40)).ends_with("..."));
+    }
+
+    #[test]
+    fn tree_describes_directories() {
+        let listed = files(&[
+            "./Cargo.toml",
+            "./src/main.rs",
+            "./src/cmd/mod.rs",
+            "./src/cmd/scribe.rs",
+            "./data/a.json",
+            "./data/b.json",
+            "./data/c.csv",
+            "./.github/workflows/ci.yml",
+        ]);
+        let mut documentation = docs::Documentation::default();
+        documentation.files.push(docs::FileDocs {
+            path: path::PathBuf::from("./src/cmd/mod.rs"),
+            docs: docs::harvest_file(
+                path::Path::new("src/cmd/mod.rs"),
+                "//! The subcommands. One per file.\n",
+            ),
+        });
+        let descriptions = Descriptions::new(&[], &documentation);
+        assert_eq!(
+            render(&listed, 2, &descriptions).unwrap(),
+            [
+                "```text",
+                ".",
+                "├── data/        # 2 .json, 1 .csv files",
+                "├── src/         # Source code",
+                "│   ├── cmd/     # The subcommands",
+                "│   └── main.rs",
+                "└── Cargo.toml",
+                "```",
+            ]
+            .join("\n")
+        );
+        assert_eq!(render(&files(&["./README.md"]), 2, &descriptions), None);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::api::classification;
+use crate::api::client;
+use crate::api::summary;
+use crate::cmd::budget;
+use crate::cmd::directory_listing;
+use crate::cmd::ranking;
+use crate::cmd::util;
+use crate::dirs;
+use crate::error::Error;
+use crate::types;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use std::collections;
+use std::fs;
+use std::path;
+
+/// Bumped whenever the summary requests change, so that stale summaries
+/// aren't reused.
+const CACHE_VERSION: &str = "1";
+
+/// How many summary requests are in flight at once.
+const SUMMARY_CONCURRENCY: usize = 4;
+
+const SUMMARY_PREFIX: &str = "summary of ";
+
+pub fn get_summary_cache_path() -> types::Result<path::PathBuf> {
+    let mut summary_cache_dir_buf = dirs::get_data_dir()?;
+    summary_cache_dir_buf.push("cache");
+    summary_cache_dir_buf.push("summaries");
+
+    let summary_cache_dir = summary_cache_dir_buf.as_path();
+    if !summary_cache_dir.exists() {
+        fs::create_dir_all(summary_cache_dir).map_err(|e| Error::io(summary_cache_dir, e))?;
+    }
+    Ok(summary_cache_dir_buf)
+}
+
+/// Summaries are keyed by the exact request contents, so a chunk is only
+/// summarized again once one of its files changed.
+fn cache_key(language: &Option<String>, chunk: &[budget::FileContent]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(CACHE_VERSION);
+    hasher.update([0]);
+    hasher.update(language.as_deref().unwrap_or_default());
+    for (name, content) in chunk.iter() {
+        hasher.update([0]);
+        hasher.update(name.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(content);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Summarizes a chunk, reusing the cached summary if there is one. Returns
+/// whether the summary came from the cache.
+pub async fn summarize_cached(
+    client: client::ApiClient,
+    language: Option<String>,
+    cache_path: path::PathBuf,
+    chunk: Vec<budget::FileContent>,
+) -> types::Result<(String, bool)> {
+    let cache_file = cache_path.join(cache_key(&language, &chunk));
+    if cache_file.exists() {
+        let summary = util::read_utf8_file(&cache_file).await?;
+        summary::record_cached(&client, language, &chunk);
+        return Ok((summary, true));
+    }
+    let summary = summary::summarize(&client, language, &chunk).await?.summary;
+    util::write_utf8_file(cache_file.to_string_lossy().to_string(), summary.clone()).await?;
+    Ok((summary, false))
+}
+
+/// Name under which a chunk's summary is sent on: the directories it covers.
+fn chunk_label(chunk: &[budget::FileContent]) -> String {
+    let directories: Vec<String> = chunk
+        .iter()
+        .filter_map(|(name, _)| name.as_ref())
+        .map(|name| match name.strip_prefix(SUMMARY_PREFIX) {
+            Some(directories) => directories.to_string(),
+            None => path::Path::new(name)
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .unwrap_or_default(),
+        })
+        .unique()
+        .collect();
+    format!(
+        "{}{}",
+        SUMMARY_PREFIX,
+        util::limit_string(&directories.join(", "), 80)
+    )
+}
+
+/// Summarizes all chunks, returning the labelled summaries and how many of
+/// them were cached.
+async fn summarize_chunks(
+    client: &client::ApiClient,
+    language: &Option<String>,
+    cache_path: &path::Path,
+    chunks: Vec<Vec<budget::FileContent>>,
+) -> types::Result<(Vec<budget::FileContent>, usize)> {
+    let results: Vec<(String, String, bool)> = stream::iter(chunks)
+        .map(|chunk| {
+            let label = chunk_label(&chunk);
+            let task = summarize_cached(
+                client.clone(),
+                language.clone(),
+                cache_path.to_path_buf(),
+                chunk,
+            );
+            async move {
+                let (summary, cached) = task.await?;
+                Ok::<_, Error>((label, summary, cached))
+            }
+        })
+        .buffered(SUMMARY_CONCURRENCY)
+        .try_collect()
+        .await?;
+    let cached = results.iter().filter(|(_, _, cached)| *cached).count();
+    let summaries = results
+        .into_iter()
+        .map(|(label, summary, _)| (Some(label), summary))
+        .collect();
+    Ok((summaries, cached))
+}
+
+/// Groups files by their directory, keeping the ranking within each group.
+fn group_by_directory(ranked_files: &[path::PathBuf]) -> Vec<Vec<path::PathBuf>> {
+    let mut groups: collections::BTreeMap<path::PathBuf, Vec<path::PathBuf>> =
+        collections::BTreeMap::new();
+    for file in ranked_files.iter() {
+        let directory = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        groups.entry(directory).or_default().push(file.clone());
+    }
+    groups.into_values().collect()
+}
+
+/// Summarizes the summaries again until they fit into one request. Returns
+/// the summaries together with the number of requests and cache hits.
+async fn reduce(
+    client: &client::ApiClient,
+    language: &Option<String>,
+    cache_path: &path::Path,
+    budget: budget::Budget,
+    mut summaries: Vec<budget::FileContent>,
+) -> types::Result<(Vec<budget::FileContent>, usize, usize)> {
+    let mut requests = 0;
+    let mut cached = 0;
+    loop {
+        let mut packed = budget::pack(budget, summaries.clone());
+        if packed.len() <= 1 {
+            return Ok((summaries, requests, cached));
+        }
+        if packed.len() >= summaries.len() {
+            // Every summary fills a request on its own, so pair them up to
+            // still halve their number in each round.
+            packed = budget::pair(budget, summaries.clone());
+        }
+        requests += packed.len();
+        let (next_summaries, next_cached) =
+            summarize_chunks(client, language, cache_path, packed).await?;
+        summaries = next_summaries;
+        cached += next_cached;
+    }
+}
+
+/// Classifies a repository that is too large for a single request. Every
+/// directory is summarized in chunks that fit the budget, the summaries are
+/// summarized again until they fit into one request, which then produces
+/// the classification.
+pub async fn classify(
+    client: client::ApiClient,
+    detected_language: Option<String>,
+    listing: directory_listing::Listing,
+    budget: budget::Budget,
+    context: Option<String>,
+    candidates: Option<usize>,
+) -> types::Result<(classification::Classification, budget::PayloadReport)> {
+    let ranked_files = ranking::rank_files(&listing.files, &listing.documentation).paths();
+    let groups = group_by_directory(&ranked_files);
+    let (chunks, mut report) = budget::chunk(budget, &groups).await?;
+    let cache_path = get_summary_cache_path()?;
+
+    let (summaries, cached) =
+        summarize_chunks(&client, &detected_language, &cache_path, chunks).await?;
+    let (context, final_budget) = budget::with_context(budget, context);
+    let (summaries, requests, more_cached) = reduce(
+        &client,
+        &detected_language,
+        &cache_path,
+        final_budget,
+        summaries,
+    )
+    .await?;
+    report.requests += requests;
+    let cached = cached + more_cached;
+
+    // `reduce` only returns once the summaries fit into a single chunk.
+    let files = budget::pack(final_budget, summaries)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    report.requests += 1;
+    report.cached = cached;
+    let classification =
+        classification::classify(&client, detected_language, files, context, candidates).await?;
+    Ok((classification, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::stub::{self, Reply};
+
+    #[tokio::test]
+    async fn reduces_summaries_that_each_fill_the_budget() {
+        let server = stub::serve(|_, _| Reply::ok(br#"{"summary":"short"}"#)).await;
+        let client =
+            client::ApiClient::with_base_url(stub::client(0), server.url.trim_end_matches('/'));
+        let cache = tempfile::tempdir().unwrap();
+        let budget = budget::Budget::Tokens(100);
+        let summaries: Vec<budget::FileContent> = (0..3)
+            .map(|i| {
+                (
+                    Some(format!("summary of dir{}", i)),
+                    format!("{} ", i).repeat(200),
+                )
+            })
+            .collect();
+
+        let (reduced, requests, cached) = reduce(&client, &None, cache.path(), budget, summaries)
+            .await
+            .unwrap();
+
+        // 3 summaries are paired into 2 requests, their 2 summaries fit into one.
+        assert_eq!(requests, 2);
+        assert_eq!(cached, 0);
+        assert_eq!(reduced.len(), 2);
+        assert_eq!(budget::pack(budget, reduced).len(), 1);
+        assert!(server.requests().iter().all(|path| path == "/summarize"));
+    }
+
+    #[test]
+    fn groups_files_by_directory() {
+        let files: Vec<path::PathBuf> = ["src/b.rs", "README.md", "src/a.rs"]
+            .iter()
+            .map(path::PathBuf::from)
+            .collect();
+        assert_eq!(
+            group_by_directory(&files),
+            vec![
+                vec![path::PathBuf::from("README.md")],
+                vec![
+                    path::PathBuf::from("src/b.rs"),
+                    path::PathBuf::from("src/a.rs")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn labels_chunks_by_their_directories() {
+        let chunk: Vec<budget::FileContent> = vec![
+            (Some("src/cmd/a.rs".to_string()), String::new()),
+            (Some("src/cmd/b.rs".to_string()), String::new()),
+            (Some("summary of src/api".to_string()), String::new()),
+        ];
+        assert_eq!(chunk_label(&chunk), "summary of src/cmd, src/api");
+    }
+}
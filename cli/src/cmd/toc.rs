@@ -0,0 +1,250 @@
+extern crate clap;
+
+use crate::error::Error;
+use crate::types;
+use std::collections;
+
+pub const DEFAULT_TOC_DEPTH: usize = 3;
+
+/// Documents with fewer headings than this don't get a table of contents.
+const MIN_TOC_ENTRIES: usize = 3;
+
+const TOC_START: &str = "<!-- toc -->";
+const TOC_END: &str = "<!-- /toc -->";
+
+/// The deepest heading level listed, from `--toc-depth`. Level 1 is the
+/// title, so a depth of 1 turns the table of contents off.
+pub fn depth_from_matches(matches: &clap::ArgMatches) -> types::Result<usize> {
+    let depth = match matches.value_of("toc-depth") {
+        Some(depth) => depth,
+        None => return Ok(DEFAULT_TOC_DEPTH),
+    };
+    let hint = Some("--toc-depth expects a heading level from 1 to 6.");
+    let message = format!("Invalid table of contents depth {}.", depth);
+    match depth.parse::<usize>() {
+        Ok(depth) if (1..=6).contains(&depth) => Ok(depth),
+        Ok(_) => Err(Error::config(&message, hint)),
+        Err(e) => Err(Error::config_caused_by(&message, hint, e)),
+    }
+}
+
+/// Whether a line opens or closes a fenced code block.
+pub fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+struct Heading {
+    level: usize,
+    text: String,
+    slug: String,
+}
+
+/// The text of a heading as GitHub renders it: links and images are
+/// reduced to their text, code spans lose their backticks.
+fn plain_text(text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        let close = rest[start..].find("](").map(|i| start + i);
+        let end = close.and_then(|close| rest[close..].find(')').map(|i| close + i));
+        match (close, end) {
+            (Some(close), Some(end)) => {
+                plain.push_str(rest[..start].trim_end_matches('!'));
+                plain.push_str(&rest[start + 1..close]);
+                rest = &rest[end + 1..];
+            }
+            _ => break,
+        }
+    }
+    plain.push_str(rest);
+    plain
+}
+
+/// The anchor GitHub generates for a heading: lowercase, punctuation
+/// removed and spaces replaced by dashes.
+fn github_slug(text: &str) -> String {
+    plain_text(text)
+        .replace('`', "")
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// ATX headings outside of code blocks, with unique slugs. Repeated slugs
+/// get a counter like on GitHub, `usage`, `usage-1`, `usage-2`.
+fn headings(lines: &[&str]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut seen: collections::HashMap<String, usize> = collections::HashMap::new();
+    let mut in_code = false;
+    for line in lines.iter() {
+        if is_fence(line) {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let trimmed = line.trim_start();
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        let text = &trimmed[level..];
+        if level == 0 || level > 6 || !text.starts_with(' ') {
+            continue;
+        }
+        let text = text.trim().trim_end_matches('#').trim().to_string();
+        let base = github_slug(&text);
+        let count = seen.entry(base.clone()).or_insert(0);
+        let slug = if *count == 0 {
+            base.clone()
+        } else {
+            format!("{}-{}", base, count)
+        };
+        *count += 1;
+        headings.push(Heading { level, text, slug });
+    }
+    headings
+}
+
+/// Removes a table of contents from an earlier run, with the blank line
+/// that was inserted next to it.
+fn remove_toc(lines: Vec<&str>) -> Vec<&str> {
+    let start = lines.iter().position(|line| line.trim() == TOC_START);
+    let end = lines.iter().position(|line| line.trim() == TOC_END);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => {
+            let blank = |i: usize| lines.get(i).map(|line| line.trim().is_empty());
+            let (start, end) = if start > 0 && blank(start - 1) == Some(true) {
+                (start - 1, end)
+            } else if blank(end + 1) == Some(true) {
+                (start, end + 1)
+            } else {
+                (start, end)
+            };
+            lines[..start]
+                .iter()
+                .chain(lines[end + 1..].iter())
+                .copied()
+                .collect()
+        }
+        _ => lines,
+    }
+}
+
+/// The line of the first level 1 heading outside of code blocks.
+fn title_line(lines: &[&str]) -> Option<usize> {
+    let mut in_code = false;
+    lines.iter().position(|line| {
+        if is_fence(line) {
+            in_code = !in_code;
+        }
+        !in_code && line.starts_with("# ")
+    })
+}
+
+/// Inserts a table of contents of the headings from level 2 to `max_depth`
+/// after the title, replacing the one of an earlier run. Documents with only
+/// a few headings are left without one.
+pub fn update(markdown: &str, max_depth: usize) -> String {
+    let lines = remove_toc(markdown.lines().collect());
+    let entries: Vec<Heading> = headings(&lines)
+        .into_iter()
+        .filter(|heading| heading.level >= 2 && heading.level <= max_depth)
+        .collect();
+    let mut document: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    if entries.len() >= MIN_TOC_ENTRIES {
+        let mut toc = vec![TOC_START.to_string()];
+        toc.extend(entries.iter().map(|heading| {
+            format!(
+                "{}- [{}](#{})",
+                "  ".repeat(heading.level - 2),
+                plain_text(&heading.text),
+                heading.slug
+            )
+        }));
+        toc.push(TOC_END.to_string());
+        // After the title, or on top of documents without one.
+        match title_line(&lines) {
+            Some(title) => {
+                toc.insert(0, String::new());
+                document.splice(title + 1..title + 1, toc);
+            }
+            None => {
+                toc.push(String::new());
+                document.splice(0..0, toc);
+            }
+        }
+    }
+    let mut updated = document.join("\n");
+    if markdown.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(args: &[&str]) -> types::Result<usize> {
+        let app = clap::App::new("test").arg(
+            clap::Arg::with_name("toc-depth")
+                .long("toc-depth")
+                .takes_value(true),
+        );
+        let matches = app.get_matches_from(std::iter::once("test").chain(args.iter().copied()));
+        depth_from_matches(&matches)
+    }
+
+    #[test]
+    fn github_slugs() {
+        assert_eq!(github_slug("Getting Started"), "getting-started");
+        assert_eq!(github_slug("What's `new`?"), "whats-new");
+        assert_eq!(github_slug("[Docs](https://docs.rs) & API"), "docs--api");
+        assert_eq!(github_slug("snake_case-name"), "snake_case-name");
+        assert_eq!(github_slug("Über uns"), "über-uns");
+    }
+
+    #[test]
+    fn depth_is_a_heading_level() {
+        assert_eq!(depth(&[]).unwrap(), DEFAULT_TOC_DEPTH);
+        assert_eq!(depth(&["--toc-depth=1"]).unwrap(), 1);
+        assert_eq!(depth(&["--toc-depth=6"]).unwrap(), 6);
+        assert!(depth(&["--toc-depth=0"]).is_err());
+        assert!(depth(&["--toc-depth=7"]).is_err());
+        assert!(depth(&["--toc-depth=two"]).is_err());
+    }
+
+    #[test]
+    fn update_inserts_after_the_title() {
+        let markdown = "# cli\n\nIntro.\n\n## Usage\n\n## Usage\n\n### Flags\n\n## License\n";
+        assert_eq!(
+            update(markdown, 3),
+            "# cli\n\n<!-- toc -->\n- [Usage](#usage)\n- [Usage](#usage-1)\n  - [Flags](#flags)\n- [License](#license)\n<!-- /toc -->\n\nIntro.\n\n## Usage\n\n## Usage\n\n### Flags\n\n## License\n"
+        );
+    }
+
+    #[test]
+    fn update_replaces_an_earlier_toc() {
+        let markdown = "# cli\n\nIntro.\n\n## A\n\n## B\n\n## C\n";
+        let once = update(markdown, 2);
+        assert_eq!(update(&once, 2), once);
+        // Too few headings left, the old one goes away.
+        assert_eq!(update(&once, 1), markdown);
+    }
+
+    #[test]
+    fn code_blocks_are_skipped() {
+        let markdown =
+            "```sh\n# not a title\n## not a section\n```\n\n# cli\n\n## A\n\n## B\n\n## C\n";
+        let updated = update(markdown, 2);
+        assert!(!updated.contains("not-a-section"));
+        assert!(updated.contains("# cli\n\n<!-- toc -->\n- [A](#a)"));
+        assert!(updated.starts_with("```sh\n# not a title\n"));
+    }
+}
@@ -0,0 +1,435 @@
+use crate::cmd::locale;
+use crate::cmd::util;
+use crate::error::{BoxError, Error};
+use crate::types;
+use serde::de;
+use serde::{Deserialize, Serialize};
+use std::collections;
+use std::path;
+
+/// Manifests are only read at the root and one directory below, deeper ones
+/// tend to be examples or fixtures.
+const MAX_MANIFEST_DEPTH: usize = 2;
+
+/// How many Makefile or justfile targets are listed.
+const MAX_TARGETS: usize = 12;
+
+/// Commands of one kind, e.g. the ones that install the project.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandGroup {
+    pub title: String,
+    pub commands: Vec<String>,
+}
+
+/// The commands a manifest declares, run from the manifest's directory.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeclaredUsage {
+    pub manifest: String,
+    pub directory: String,
+    pub groups: Vec<CommandGroup>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+    #[serde(default)]
+    bin: Vec<CargoTarget>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoPackage {
+    name: String,
+    autobins: Option<bool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoTarget {
+    name: Option<String>,
+}
+
+/// `bin` is either a map of binary names to scripts or a single script that
+/// is named after the package.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum PackageBin {
+    Named(collections::BTreeMap<String, String>),
+    Single(de::IgnoredAny),
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageJson {
+    name: Option<String>,
+    bin: Option<PackageBin>,
+    #[serde(default)]
+    scripts: collections::BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyProject {
+    project: Option<PyProjectProject>,
+    tool: Option<PyProjectTool>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyProjectProject {
+    #[serde(default)]
+    scripts: collections::BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyProjectTool {
+    poetry: Option<PyProjectProject>,
+}
+
+fn display_path(file: &path::Path) -> String {
+    file.strip_prefix(".")
+        .unwrap_or(file)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn group(title: &str, commands: Vec<String>) -> Option<CommandGroup> {
+    if commands.is_empty() {
+        None
+    } else {
+        Some(CommandGroup {
+            title: title.to_string(),
+            commands,
+        })
+    }
+}
+
+fn parse_error(manifest: &path::Path, error: impl Into<BoxError>) -> Error {
+    Error::config_caused_by(
+        &format!("Couldn't parse {}.", manifest.display()),
+        Some("Fix the manifest, the Usage section is generated from it."),
+        error,
+    )
+}
+
+/// The binaries Cargo finds without a `[[bin]]` table: `src/main.rs` named
+/// after the package, `src/bin/<name>.rs` and `src/bin/<name>/main.rs`.
+fn discovered_binaries(directory: &path::Path, package_name: &str) -> Vec<String> {
+    let mut binaries = Vec::new();
+    if directory.join("src/main.rs").is_file() {
+        binaries.push(package_name.to_string());
+    }
+    let entries = match std::fs::read_dir(directory.join("src/bin")) {
+        Ok(entries) => entries,
+        Err(_) => return binaries,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let entry_path = entry.path();
+        let name = if entry_path.is_dir() {
+            entry_path
+                .join("main.rs")
+                .is_file()
+                .then(|| entry.file_name())
+        } else if entry_path.extension().and_then(|e| e.to_str()) == Some("rs") {
+            entry_path.file_stem().map(|stem| stem.to_os_string())
+        } else {
+            None
+        };
+        if let Some(name) = name {
+            binaries.push(name.to_string_lossy().to_string());
+        }
+    }
+    binaries
+}
+
+fn cargo_usage(manifest: &path::Path, source: &str) -> types::Result<Vec<CommandGroup>> {
+    let cargo: CargoManifest = toml::from_str(source).map_err(|e| parse_error(manifest, e))?;
+    // Workspace manifests without a package declare nothing to run.
+    let package = match cargo.package {
+        Some(package) => package,
+        None => return Ok(Vec::new()),
+    };
+    let directory = manifest.parent().unwrap_or_else(|| path::Path::new("."));
+    let mut binaries: Vec<String> = cargo.bin.into_iter().filter_map(|bin| bin.name).collect();
+    if package.autobins != Some(false) {
+        binaries.extend(discovered_binaries(directory, &package.name));
+    }
+    binaries.sort();
+    binaries.dedup();
+    // Library crates are only built.
+    if binaries.is_empty() {
+        return Ok(vec![CommandGroup {
+            title: "Build".to_string(),
+            commands: vec!["cargo build".to_string()],
+        }]);
+    }
+    Ok(vec![
+        CommandGroup {
+            title: "Install".to_string(),
+            commands: vec!["cargo install --path .".to_string()],
+        },
+        CommandGroup {
+            title: "Run".to_string(),
+            commands: binaries,
+        },
+    ])
+}
+
+fn npm_usage(manifest: &path::Path, source: &str) -> types::Result<Vec<CommandGroup>> {
+    let package: PackageJson =
+        serde_json::from_str(source).map_err(|e| parse_error(manifest, e))?;
+    let binaries: Vec<String> = match package.bin {
+        // Scoped packages install their binary without the scope.
+        Some(PackageBin::Single(_)) => package
+            .name
+            .iter()
+            .filter_map(|name| name.rsplit('/').next())
+            .map(|name| name.to_string())
+            .collect(),
+        Some(PackageBin::Named(binaries)) => binaries.into_keys().collect(),
+        None => Vec::new(),
+    };
+    let install = if binaries.is_empty() {
+        "npm install"
+    } else {
+        "npm install --global ."
+    };
+    Ok(vec![
+        group("Install", vec![install.to_string()]),
+        group("Run", binaries),
+        group(
+            "Scripts",
+            package
+                .scripts
+                .keys()
+                .map(|script| format!("npm run {}", script))
+                .collect(),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect())
+}
+
+fn python_usage(manifest: &path::Path, source: &str) -> types::Result<Vec<CommandGroup>> {
+    let pyproject: PyProject = toml::from_str(source).map_err(|e| parse_error(manifest, e))?;
+    let scripts: Vec<String> = pyproject
+        .project
+        .into_iter()
+        .chain(pyproject.tool.and_then(|tool| tool.poetry))
+        .flat_map(|project| project.scripts.into_keys())
+        .collect();
+    Ok(vec![
+        group("Install", vec!["pip install .".to_string()]),
+        group("Run", scripts),
+    ]
+    .into_iter()
+    .flatten()
+    .collect())
+}
+
+/// Rule heads of a Makefile or justfile: the words before the colon of lines
+/// that start with a name, leaving out variables and special targets.
+fn rule_heads(source: &str) -> Vec<Vec<&str>> {
+    source
+        .lines()
+        .filter(|line| !line.starts_with(|c: char| c.is_whitespace() || c == '.' || c == '#'))
+        .filter_map(|line| {
+            let (head, rest) = line.split_once(':')?;
+            // Just parameters can have quoted defaults, `name="value"` or
+            // `name='value'`.
+            if rest.starts_with('=')
+                || (head.contains(['=', '$', '%']) && !head.contains(['"', '\'']))
+            {
+                return None;
+            }
+            Some(head.trim_start_matches('@').split_whitespace().collect())
+        })
+        .filter(|words: &Vec<&str>| !words.is_empty())
+        .take(MAX_TARGETS)
+        .collect()
+}
+
+/// `make <target>` for the first target of every rule.
+fn make_usage(source: &str) -> Vec<CommandGroup> {
+    let commands = rule_heads(source)
+        .into_iter()
+        .map(|words| format!("make {}", words[0]))
+        .collect();
+    group("Tasks", commands).into_iter().collect()
+}
+
+/// `just <recipe>` with placeholders for the parameters without a default.
+fn just_usage(source: &str) -> Vec<CommandGroup> {
+    let commands = rule_heads(source)
+        .into_iter()
+        .map(|words| {
+            let mut command = format!("just {}", words[0]);
+            for parameter in words[1..].iter().filter(|word| !word.contains('=')) {
+                command.push_str(&format!(" <{}>", parameter.trim_start_matches(['+', '*'])));
+            }
+            command
+        })
+        .collect();
+    group("Tasks", commands).into_iter().collect()
+}
+
+async fn declared_usage(manifest: &path::Path) -> types::Result<Option<DeclaredUsage>> {
+    let file_name = manifest
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    let is_manifest = matches!(
+        file_name,
+        "Cargo.toml" | "package.json" | "pyproject.toml" | "Makefile" | "makefile" | "justfile"
+    );
+    if !is_manifest {
+        return Ok(None);
+    }
+    let source = util::read_utf8_file(manifest).await?;
+    let groups = match file_name {
+        "Cargo.toml" => cargo_usage(manifest, &source)?,
+        "package.json" => npm_usage(manifest, &source)?,
+        "pyproject.toml" => python_usage(manifest, &source)?,
+        "justfile" => just_usage(&source),
+        _ => make_usage(&source),
+    };
+    if groups.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(DeclaredUsage {
+        manifest: display_path(manifest),
+        directory: manifest.parent().map(display_path).unwrap_or_default(),
+        groups,
+    }))
+}
+
+/// Reads the install and run commands that the project's manifests declare:
+/// Cargo binaries, npm binaries and scripts, Python console scripts and
+/// Makefile or justfile targets. Manifests that can't be read or parsed are
+/// skipped and returned as errors, the others still make it into the README.
+pub async fn detect(relevant_files: Vec<path::PathBuf>) -> (Vec<DeclaredUsage>, Vec<Error>) {
+    let mut manifests: Vec<path::PathBuf> = relevant_files
+        .into_iter()
+        .filter(|file| {
+            path::Path::new(&display_path(file)).components().count() <= MAX_MANIFEST_DEPTH
+        })
+        .collect();
+    manifests.sort();
+    let mut usages = Vec::new();
+    let mut skipped = Vec::new();
+    for manifest in manifests.iter() {
+        match declared_usage(manifest).await {
+            Ok(Some(usage)) => usages.push(usage),
+            Ok(None) => {}
+            Err(error) => skipped.push(error),
+        }
+    }
+    (usages, skipped)
+}
+
+/// Renders the declared commands, one shell block per manifest, with the
+/// group titles in the README language.
+pub fn render(usages: &[DeclaredUsage], strings: &locale::Strings) -> String {
+    usages
+        .iter()
+        .map(|usage| {
+            let mut block = Vec::new();
+            if !usage.directory.is_empty() {
+                block.push(format!("cd {}", usage.directory));
+            }
+            for group in usage.groups.iter() {
+                block.push(format!("# {}", strings.command_group(&group.title)));
+                block.extend(group.commands.iter().cloned());
+            }
+            format!(
+                "{}\n\n```sh\n{}\n```",
+                strings.from_manifest.replacen("{}", &usage.manifest, 1),
+                block.join("\n")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn rule_heads_skip_variables_and_special_targets() {
+        let makefile = "CC := gcc\n.PHONY: build\nbuild: src/main.c\n\tgcc src/main.c\n# test: comment\n%.o: %.c\ntest lint: build\nVERSION=1\n";
+        assert_eq!(
+            rule_heads(makefile),
+            vec![vec!["build"], vec!["test", "lint"]]
+        );
+        let justfile =
+            "set shell := [\"bash\", \"-c\"]\n@release version target=\"x86\":\n  echo\n";
+        assert_eq!(
+            rule_heads(justfile),
+            vec![vec!["release", "version", "target=\"x86\""]]
+        );
+    }
+
+    #[test]
+    fn just_recipes_get_placeholders() {
+        let groups = just_usage("deploy env +flags='':\n  echo\n");
+        assert_eq!(groups[0].commands, vec!["just deploy <env>"]);
+    }
+
+    #[test]
+    fn cargo_discovers_binaries() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::create_dir_all(directory.path().join("src/bin/server")).unwrap();
+        fs::write(directory.path().join("src/main.rs"), "").unwrap();
+        fs::write(directory.path().join("src/bin/migrate.rs"), "").unwrap();
+        fs::write(directory.path().join("src/bin/server/main.rs"), "").unwrap();
+        let manifest = directory.path().join("Cargo.toml");
+
+        let groups = cargo_usage(&manifest, "[package]\nname = \"app\"\n").unwrap();
+        assert_eq!(groups[1].commands, vec!["app", "migrate", "server"]);
+
+        let groups = cargo_usage(
+            &manifest,
+            "[package]\nname = \"app\"\nautobins = false\n\n[[bin]]\nname = \"tool\"\n",
+        )
+        .unwrap();
+        assert_eq!(groups[1].commands, vec!["tool"]);
+    }
+
+    #[test]
+    fn cargo_libraries_are_built() {
+        let directory = tempfile::tempdir().unwrap();
+        let groups = cargo_usage(
+            &directory.path().join("Cargo.toml"),
+            "[package]\nname = \"lib\"\n",
+        )
+        .unwrap();
+        assert_eq!(groups[0].commands, vec!["cargo build"]);
+    }
+
+    #[test]
+    fn npm_single_bin_is_named_after_the_package() {
+        let groups = npm_usage(
+            path::Path::new("package.json"),
+            r#"{"name": "@scope/tool", "bin": "cli.js", "scripts": {"test": "jest"}}"#,
+        )
+        .unwrap();
+        let commands: Vec<&str> = groups
+            .iter()
+            .flat_map(|group| group.commands.iter().map(|c| c.as_str()))
+            .collect();
+        assert_eq!(
+            commands,
+            vec!["npm install --global .", "tool", "npm run test"]
+        );
+    }
+
+    #[tokio::test]
+    async fn broken_manifests_are_skipped() {
+        let (usages, skipped) = detect(vec![
+            path::PathBuf::from("./missing-manifest-dir/Cargo.toml"),
+            path::PathBuf::from("./README.md"),
+        ])
+        .await;
+        assert!(usages.is_empty());
+        assert_eq!(skipped.len(), 1);
+        assert!(npm_usage(path::Path::new("package.json"), "{").is_err());
+    }
+}
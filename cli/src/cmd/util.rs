@@ -1,27 +1,42 @@
+use crate::error::Error;
 use crate::types::Result;
+use std::io;
 use std::path;
 use tokio;
 use tokio::fs;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 pub async fn read_utf8_file(file_name: &path::Path) -> Result<String> {
-    let mut file = fs::File::open(file_name).await?;
+    let mut file = fs::File::open(file_name)
+        .await
+        .map_err(|e| Error::io(file_name, e))?;
     let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).await?;
+    file.read_to_end(&mut buffer)
+        .await
+        .map_err(|e| Error::io(file_name, e))?;
 
-    let utf8_file = String::from_utf8(buffer)?;
+    let utf8_file = String::from_utf8(buffer)
+        .map_err(|e| Error::io(file_name, io::Error::new(io::ErrorKind::InvalidData, e)))?;
     Ok(utf8_file)
 }
 
 pub async fn write_utf8_file(file_name: String, content: String) -> Result<()> {
-    let mut buffer = fs::File::create(file_name).await?;
-    buffer.write_all(content.as_bytes()).await?;
+    let mut buffer = fs::File::create(&file_name)
+        .await
+        .map_err(|e| Error::io(&file_name, e))?;
+    buffer
+        .write_all(content.as_bytes())
+        .await
+        .map_err(|e| Error::io(&file_name, e))?;
+    // tokio writes in the background, unflushed data is lost if the process
+    // exits right after.
+    buffer.flush().await.map_err(|e| Error::io(&file_name, e))?;
 
     Ok(())
 }
 
 pub fn limit_string(text: &str, length: usize) -> String {
-    if (text.len() > length) {
+    if text.len() > length {
         let text_trimmed: String = text.chars().take(length).collect();
         let mut trimmed = String::new();
         trimmed.push_str(text_trimmed.as_str());
@@ -0,0 +1,41 @@
+use crate::detection::{ClassificationResult, LanguageDetector};
+use crate::error::Error;
+use crate::guesslang;
+use crate::http;
+use crate::types;
+use std::path;
+
+pub struct GuessLangDetector {
+    settings: guesslang::classification::GuessLangSettings,
+}
+
+impl GuessLangDetector {
+    pub async fn load(
+        client: &http::HttpClient,
+        model_url: Option<&str>,
+    ) -> types::Result<GuessLangDetector> {
+        let model_path = guesslang::model_downloader::retrieve_model(
+            client,
+            model_url.unwrap_or(guesslang::classification::DEFAULT_MODEL_URL),
+            &guesslang::classification::MODEL_FILES,
+        )
+        .await?;
+        let settings = guesslang::classification::load_settings(model_path).await?;
+        Ok(GuessLangDetector { settings })
+    }
+}
+
+impl LanguageDetector for GuessLangDetector {
+    fn name(&self) -> &'static str {
+        "guesslang"
+    }
+
+    fn classify(
+        &self,
+        _path: &path::Path,
+        snippet: &str,
+    ) -> types::Result<Vec<ClassificationResult>> {
+        guesslang::classification::classify(&self.settings, snippet.to_string())
+            .map_err(|e| Error::detection("guesslang classification failed.", e))
+    }
+}
@@ -0,0 +1,197 @@
+use crate::detection::{ClassificationResult, LanguageDetector};
+use crate::types;
+use std::path;
+
+const EXTENSIONS: [(&str, &str); 69] = [
+    ("asm", "asm"),
+    ("s", "asm"),
+    ("bat", "bat"),
+    ("cmd", "bat"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cs", "cs"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("clj", "clj"),
+    ("cljs", "clj"),
+    ("cmake", "cmake"),
+    ("cbl", "cbl"),
+    ("cob", "cbl"),
+    ("coffee", "coffee"),
+    ("css", "css"),
+    ("csv", "csv"),
+    ("dart", "dart"),
+    ("dm", "dm"),
+    ("ex", "ex"),
+    ("exs", "ex"),
+    ("erl", "erl"),
+    ("f90", "f90"),
+    ("go", "go"),
+    ("groovy", "groovy"),
+    ("hs", "hs"),
+    // guesslang has no PureScript class and reports it as Haskell.
+    ("purs", "hs"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("ini", "ini"),
+    ("java", "java"),
+    ("js", "js"),
+    ("mjs", "js"),
+    ("cjs", "js"),
+    ("jsx", "js"),
+    ("json", "json"),
+    ("jl", "jl"),
+    ("kt", "kt"),
+    ("lisp", "lisp"),
+    ("lua", "lua"),
+    ("md", "md"),
+    ("m", "matlab"),
+    ("mm", "mm"),
+    ("ml", "ml"),
+    ("pas", "pas"),
+    ("pm", "pm"),
+    ("pl", "pm"),
+    ("php", "php"),
+    ("ps1", "ps1"),
+    ("pro", "prolog"),
+    ("py", "py"),
+    ("r", "r"),
+    ("rb", "rb"),
+    ("rs", "rs"),
+    ("scala", "scala"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("sql", "sql"),
+    ("swift", "swift"),
+    ("tex", "tex"),
+    ("toml", "toml"),
+    ("ts", "ts"),
+    ("tsx", "ts"),
+    ("v", "v"),
+    ("vba", "vba"),
+    ("xml", "xml"),
+    ("yaml", "yaml"),
+];
+
+const FILE_NAMES: [(&str, &str); 4] = [
+    ("Dockerfile", "dockerfile"),
+    ("Makefile", "makefile"),
+    ("makefile", "makefile"),
+    ("CMakeLists.txt", "cmake"),
+];
+
+const INTERPRETERS: [(&str, &str); 8] = [
+    ("python", "py"),
+    ("node", "js"),
+    ("bash", "sh"),
+    ("sh", "sh"),
+    ("zsh", "sh"),
+    ("ruby", "rb"),
+    ("perl", "pm"),
+    ("php", "php"),
+];
+
+/// Pure-Rust detector that looks at file names, extensions and shebangs.
+/// It is used when the crate is built without the `guesslang` feature.
+pub struct HeuristicDetector;
+
+fn lookup(table: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(candidate, _)| *candidate == key)
+        .map(|(_, identifier)| *identifier)
+}
+
+impl HeuristicDetector {
+    fn by_file_name(&self, path: &path::Path) -> Option<&'static str> {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| lookup(&FILE_NAMES, name))
+    }
+
+    fn by_extension(&self, path: &path::Path) -> Option<&'static str> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| lookup(&EXTENSIONS, &extension.to_lowercase()))
+    }
+
+    fn by_shebang(&self, snippet: &str) -> Option<&'static str> {
+        let first_line = snippet.lines().next()?;
+        let command = first_line.strip_prefix("#!")?;
+        let mut parts = command.split_whitespace();
+        let program = parts.next()?;
+        let interpreter = if program.ends_with("/env") {
+            parts.next()?
+        } else {
+            program.rsplit('/').next()?
+        };
+        INTERPRETERS
+            .iter()
+            .find(|(prefix, _)| interpreter.starts_with(prefix))
+            .map(|(_, identifier)| *identifier)
+    }
+}
+
+impl LanguageDetector for HeuristicDetector {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    fn classify(
+        &self,
+        path: &path::Path,
+        snippet: &str,
+    ) -> types::Result<Vec<ClassificationResult>> {
+        let detected = self
+            .by_file_name(path)
+            .or_else(|| self.by_extension(path))
+            .or_else(|| self.by_shebang(snippet));
+        Ok(detected
+            .map(|identifier| ClassificationResult {
+                identifier: identifier.to_string(),
+                score: 1.0,
+            })
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(path: &str, snippet: &str) -> Option<String> {
+        HeuristicDetector
+            .classify(path::Path::new(path), snippet)
+            .unwrap()
+            .first()
+            .map(|result| result.identifier.clone())
+    }
+
+    #[test]
+    fn detects_by_file_name_before_extension() {
+        assert_eq!(
+            detect("build/Dockerfile", ""),
+            Some("dockerfile".to_string())
+        );
+        assert_eq!(detect("CMakeLists.txt", ""), Some("cmake".to_string()));
+    }
+
+    #[test]
+    fn detects_by_extension_ignoring_case() {
+        assert_eq!(detect("src/main.rs", ""), Some("rs".to_string()));
+        assert_eq!(detect("src/Main.PURS", ""), Some("hs".to_string()));
+    }
+
+    #[test]
+    fn detects_by_shebang() {
+        assert_eq!(
+            detect("bin/run", "#!/usr/bin/env python3\nprint()"),
+            Some("py".to_string())
+        );
+        assert_eq!(detect("bin/run", "#!/bin/bash -e"), Some("sh".to_string()));
+        assert_eq!(detect("bin/run", "echo"), None);
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(feature = "guesslang")]
+mod guesslang;
+#[cfg(not(any(feature = "guesslang", feature = "onnx")))]
+mod heuristic;
+#[cfg(all(feature = "onnx", not(feature = "guesslang")))]
+mod onnx;
+
+use crate::http;
+use crate::types;
+use std::path;
+use std::sync;
+
+pub struct ClassificationResult {
+    pub identifier: String,
+    #[cfg_attr(not(any(feature = "guesslang", feature = "onnx")), allow(dead_code))]
+    pub score: f32,
+}
+
+/// Detects the language of a single source file. Identifiers follow the
+/// guesslang naming (`rs`, `js`, `hs`, ...) so that they can be resolved
+/// with `language_display_name_or_default` and sent to the backend as is.
+pub trait LanguageDetector: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the candidate languages sorted by descending score.
+    fn classify(
+        &self,
+        path: &path::Path,
+        snippet: &str,
+    ) -> types::Result<Vec<ClassificationResult>>;
+}
+
+/// Shared by the TensorFlow and ONNX backends so that both order equal
+/// scores the same way.
+#[cfg(any(feature = "guesslang", feature = "onnx"))]
+pub fn sort_classifications(classifications: &[(String, f32)]) -> Vec<ClassificationResult> {
+    let mut mapped: Vec<ClassificationResult> = classifications
+        .iter()
+        .map(|(identifier, score)| ClassificationResult {
+            identifier: identifier.to_string(),
+            score: *score,
+        })
+        .collect();
+    mapped.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    mapped
+}
+
+#[cfg(feature = "guesslang")]
+pub async fn load_detector(
+    client: &http::HttpClient,
+    model_url: Option<&str>,
+) -> types::Result<sync::Arc<dyn LanguageDetector>> {
+    let detector = guesslang::GuessLangDetector::load(client, model_url).await?;
+    Ok(sync::Arc::new(detector))
+}
+
+#[cfg(all(feature = "onnx", not(feature = "guesslang")))]
+pub async fn load_detector(
+    client: &http::HttpClient,
+    model_url: Option<&str>,
+) -> types::Result<sync::Arc<dyn LanguageDetector>> {
+    let detector = onnx::OnnxDetector::load(client, model_url).await?;
+    Ok(sync::Arc::new(detector))
+}
+
+#[cfg(not(any(feature = "guesslang", feature = "onnx")))]
+pub async fn load_detector(
+    _client: &http::HttpClient,
+    _model_url: Option<&str>,
+) -> types::Result<sync::Arc<dyn LanguageDetector>> {
+    Ok(sync::Arc::new(heuristic::HeuristicDetector))
+}
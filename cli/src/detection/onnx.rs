@@ -0,0 +1,53 @@
+use crate::detection::{ClassificationResult, LanguageDetector};
+use crate::error::Error;
+use crate::guesslang;
+use crate::http;
+use crate::types;
+use std::path;
+
+pub struct OnnxDetector {
+    settings: guesslang::onnx::OnnxSettings,
+}
+
+impl OnnxDetector {
+    pub async fn load(
+        client: &http::HttpClient,
+        model_url: Option<&str>,
+    ) -> types::Result<OnnxDetector> {
+        // The exported model isn't hosted anywhere, so there is no default
+        // URL to fall back to.
+        let model_path = match model_url {
+            Some(model_url) => {
+                guesslang::model_downloader::retrieve_model(
+                    client,
+                    model_url,
+                    &guesslang::onnx::MODEL_FILES,
+                )
+                .await?
+            }
+            None => guesslang::model_downloader::cached_model(&guesslang::onnx::MODEL_FILES)?
+                .ok_or_else(|| {
+                    Error::config(
+                        "The ONNX language model isn't available.",
+                        Some("Export it with cli/scripts/export_guesslang_onnx.py and pass the output folder, or a URL serving it, with --model-url. It is kept for later runs."),
+                    )
+                })?,
+        };
+        let settings = guesslang::onnx::load_settings(model_path).await?;
+        Ok(OnnxDetector { settings })
+    }
+}
+
+impl LanguageDetector for OnnxDetector {
+    fn name(&self) -> &'static str {
+        "guesslang-onnx"
+    }
+
+    fn classify(
+        &self,
+        _path: &path::Path,
+        snippet: &str,
+    ) -> types::Result<Vec<ClassificationResult>> {
+        guesslang::onnx::classify(&self.settings, snippet)
+    }
+}
@@ -0,0 +1,319 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path;
+use tokio::task;
+
+pub type BoxError = Box<dyn error::Error + Send + Sync>;
+
+/// Crate-wide error. `Display` only describes what failed; the underlying
+/// error is exposed through `source()` so that `report` can show the cause
+/// or the full chain.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: path::PathBuf,
+        source: io::Error,
+    },
+    Http {
+        url: Option<String>,
+        status: Option<u16>,
+        source: BoxError,
+    },
+    #[cfg_attr(not(any(feature = "guesslang", feature = "onnx")), allow(dead_code))]
+    Model {
+        message: String,
+        source: Option<BoxError>,
+    },
+    Detection {
+        message: String,
+        source: Option<BoxError>,
+    },
+    Extraction {
+        message: String,
+        source: Option<BoxError>,
+    },
+    Git {
+        message: String,
+        source: Option<BoxError>,
+    },
+    Config {
+        message: String,
+        hint: Option<String>,
+        source: Option<BoxError>,
+    },
+    Task(task::JoinError),
+}
+
+impl Error {
+    pub fn io<P: AsRef<path::Path>>(path: P, source: io::Error) -> Error {
+        Error::Io {
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn http<E: Into<BoxError>>(url: &str, status: Option<u16>, source: E) -> Error {
+        Error::Http {
+            url: Some(url.to_string()),
+            status,
+            source: source.into(),
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "guesslang", feature = "onnx")), allow(dead_code))]
+    pub fn model<E: Into<BoxError>>(message: &str, source: E) -> Error {
+        Error::Model {
+            message: message.to_string(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn detection<E: Into<BoxError>>(message: &str, source: E) -> Error {
+        Error::Detection {
+            message: message.to_string(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn extraction<E: Into<BoxError>>(message: &str, source: E) -> Error {
+        Error::Extraction {
+            message: message.to_string(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn git<E: Into<BoxError>>(message: &str, source: E) -> Error {
+        Error::Git {
+            message: message.to_string(),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn config(message: &str, hint: Option<&str>) -> Error {
+        Error::Config {
+            message: message.to_string(),
+            hint: hint.map(|h| h.to_string()),
+            source: None,
+        }
+    }
+
+    pub fn config_caused_by<E: Into<BoxError>>(
+        message: &str,
+        hint: Option<&str>,
+        source: E,
+    ) -> Error {
+        Error::Config {
+            message: message.to_string(),
+            hint: hint.map(|h| h.to_string()),
+            source: Some(source.into()),
+        }
+    }
+
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::Io { path, source } => match source.kind() {
+                io::ErrorKind::NotFound => Some(format!(
+                    "Check that {} exists and that you are running skriptorium from the project root.",
+                    path.display()
+                )),
+                io::ErrorKind::PermissionDenied => {
+                    Some(format!("Check the permissions of {}.", path.display()))
+                }
+                io::ErrorKind::InvalidData => {
+                    Some("Only UTF-8 encoded files are supported.".to_string())
+                }
+                _ => None,
+            },
+            Error::Http {
+                status: None, url, ..
+            } => Some(format!(
+                "Check that {} is reachable. Use --proxy and --ca-bundle if you are behind a proxy.",
+                url.as_deref().unwrap_or("the server")
+            )),
+            Error::Http {
+                status: Some(429), ..
+            } => Some("The server is rate limiting requests, please try again later.".to_string()),
+            Error::Http {
+                status: Some(status),
+                ..
+            } if *status >= 500 => {
+                Some("The server failed to handle the request, please try again later.".to_string())
+            }
+            Error::Http { .. } => None,
+            Error::Model { .. } => Some(
+                "Delete the cached model in the skriptorium data directory to download it again, or use --model-url to point to a mirror."
+                    .to_string(),
+            ),
+            Error::Detection { .. } => None,
+            Error::Extraction { .. } => None,
+            Error::Git { .. } => None,
+            Error::Config { hint, .. } => hint.clone(),
+            Error::Task(_) => None,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Http { source, .. } => Some(source.as_ref()),
+            Error::Model { source, .. }
+            | Error::Detection { source, .. }
+            | Error::Extraction { source, .. }
+            | Error::Git { source, .. }
+            | Error::Config { source, .. } => source
+                .as_ref()
+                .map(|s| s.as_ref() as &(dyn error::Error + 'static)),
+            Error::Task(source) => Some(source),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, .. } => write!(f, "Couldn't access {}.", path.display()),
+            Error::Http { url, status, .. } => {
+                let url = url.as_deref().unwrap_or("server");
+                match status {
+                    Some(status) => {
+                        write!(f, "Request to {} failed with status {}.", url, status)
+                    }
+                    None => write!(f, "Request to {} failed.", url),
+                }
+            }
+            Error::Model { message, .. } => write!(f, "{}", message),
+            Error::Detection { message, .. } => write!(f, "{}", message),
+            Error::Extraction { message, .. } => write!(f, "{}", message),
+            Error::Git { message, .. } => write!(f, "{}", message),
+            Error::Config { message, .. } => write!(f, "{}", message),
+            Error::Task(_) => write!(f, "A background task failed."),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        Error::Http {
+            url: error.url().map(|url| url.to_string()),
+            status: error.status().map(|status| status.as_u16()),
+            source: Box::new(error),
+        }
+    }
+}
+
+impl From<task::JoinError> for Error {
+    fn from(error: task::JoinError) -> Self {
+        Error::Task(error)
+    }
+}
+
+fn causes(error: &dyn error::Error) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut current = error.source();
+    while let Some(cause) = current {
+        causes.push(cause.to_string());
+        current = cause.source();
+    }
+    causes
+}
+
+/// Renders an error for the terminal: the error itself, its root cause (or
+/// every cause when `verbose` is set) and a hint on how to fix it.
+pub fn report(error: &Error, verbose: bool) -> String {
+    let mut lines = vec![format!("Error: {}", error)];
+    let causes = causes(error);
+    if verbose {
+        if !causes.is_empty() {
+            lines.push("  Caused by:".to_string());
+            for (i, cause) in causes.iter().enumerate() {
+                lines.push(format!("    {}: {}", i, cause));
+            }
+        }
+    } else if let Some(root_cause) = causes.last() {
+        lines.push(format!("  Cause: {}", root_cause));
+    }
+    if let Some(hint) = error.hint() {
+        lines.push(format!("  Hint: {}", hint));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn missing_config() -> Error {
+        Error::config_caused_by(
+            "Couldn't read the configuration.",
+            Some("Run skriptorium init first."),
+            Error::io(
+                "skriptorium.toml",
+                io::Error::new(io::ErrorKind::NotFound, "no such file"),
+            ),
+        )
+    }
+
+    #[test]
+    fn display_describes_what_failed() {
+        assert_eq!(
+            Error::io("README.md", io::Error::from(io::ErrorKind::NotFound)).to_string(),
+            "Couldn't access README.md."
+        );
+        assert_eq!(
+            Error::http("https://example.com", Some(503), "unavailable").to_string(),
+            "Request to https://example.com failed with status 503."
+        );
+        assert_eq!(
+            Error::http("https://example.com", None, "reset").to_string(),
+            "Request to https://example.com failed."
+        );
+        assert_eq!(
+            missing_config().to_string(),
+            "Couldn't read the configuration."
+        );
+    }
+
+    #[test]
+    fn hints_depend_on_the_kind_of_failure() {
+        let not_found = Error::io("model.json", io::Error::from(io::ErrorKind::NotFound));
+        assert!(not_found.hint().unwrap().contains("model.json exists"));
+        let unreachable = Error::http("https://example.com", None, "reset");
+        assert!(unreachable.hint().unwrap().contains("--proxy"));
+        let rate_limited = Error::http("https://example.com", Some(429), "slow down");
+        assert!(rate_limited.hint().unwrap().contains("rate limiting"));
+        assert_eq!(
+            Error::http("https://example.com", Some(404), "missing").hint(),
+            None
+        );
+        assert_eq!(
+            missing_config().hint().as_deref(),
+            Some("Run skriptorium init first.")
+        );
+    }
+
+    #[test]
+    fn report_shows_the_root_cause_or_the_whole_chain() {
+        let error = missing_config();
+        assert_eq!(
+            report(&error, false),
+            "Error: Couldn't read the configuration.\n  \
+             Cause: no such file\n  \
+             Hint: Run skriptorium init first."
+        );
+        assert_eq!(
+            report(&error, true),
+            "Error: Couldn't read the configuration.\n  \
+             Caused by:\n    \
+             0: Couldn't access skriptorium.toml.\n    \
+             1: no such file\n  \
+             Hint: Run skriptorium init first."
+        );
+        assert_eq!(
+            report(&Error::config("Input file or folder missing.", None), true),
+            "Error: Input file or folder missing."
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! Doc comments the authors already wrote: `//!` and `///` in Rust, JSDoc in
+//! JavaScript and TypeScript, docstrings in Python and `-- |` in PureScript.
+//! Comments are read line by line, an item's doc comment belongs to the
+//! declaration that follows it.
+
+use crate::extraction::one_line;
+use std::path;
+
+/// What a doc comment describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DocLevel {
+    Crate,
+    Module,
+    Item,
+}
+
+/// A doc comment with the comment markers removed. Item docs carry the
+/// declaration they document, collapsed onto one line.
+#[derive(Debug, Clone)]
+pub struct DocComment {
+    pub level: DocLevel,
+    pub item: Option<String>,
+    pub text: String,
+}
+
+impl DocComment {
+    /// The first paragraph, which is usually a one sentence summary.
+    pub fn summary(&self) -> String {
+        one_line(self.text.split("\n\n").next().unwrap_or(""))
+    }
+}
+
+/// The doc comments of a single file, in source order.
+#[derive(Debug, Clone)]
+pub struct FileDocs {
+    pub path: path::PathBuf,
+    pub docs: Vec<DocComment>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Documentation {
+    pub files: Vec<FileDocs>,
+}
+
+impl Documentation {
+    pub fn comment_count(&self) -> usize {
+        self.files.iter().map(|file| file.docs.len()).sum()
+    }
+
+    /// The docs of the given files, e.g. of one module.
+    pub fn for_files(&self, files: &[path::PathBuf]) -> Vec<FileDocs> {
+        self.files
+            .iter()
+            .filter(|file| files.contains(&file.path))
+            .cloned()
+            .collect()
+    }
+
+    /// Context for the backend: crate docs first, then module docs, then
+    /// item docs, so that the broadest descriptions survive truncation.
+    pub fn compact(&self) -> String {
+        let mut compact = String::new();
+        for level in [DocLevel::Crate, DocLevel::Module, DocLevel::Item] {
+            for file in self.files.iter() {
+                let docs: Vec<&DocComment> =
+                    file.docs.iter().filter(|doc| doc.level == level).collect();
+                if docs.is_empty() {
+                    continue;
+                }
+                compact.push_str(&format!("{}\n", file.path.display()));
+                for doc in docs {
+                    match &doc.item {
+                        Some(item) => {
+                            compact.push_str(&format!("  {}: {}\n", item, one_line(&doc.text)))
+                        }
+                        None => compact.push_str(&format!("  {}\n", one_line(&doc.text))),
+                    }
+                }
+            }
+        }
+        compact
+    }
+}
+
+/// Harvests the doc comments of a file, chosen by its extension. Files in
+/// unsupported languages have none.
+pub fn harvest_file(file: &path::Path, source: &str) -> Vec<DocComment> {
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    let lines: Vec<&str> = source.lines().collect();
+    let docs = match extension {
+        "rs" => rust_docs(file, &lines),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => jsdoc_docs(&lines),
+        "py" => python_docs(&lines),
+        "purs" => purescript_docs(&lines),
+        _ => Vec::new(),
+    };
+    docs.into_iter()
+        .filter(|doc| !doc.text.is_empty())
+        .collect()
+}
+
+/// Joins comment lines, keeping blank lines as paragraph breaks.
+fn join_lines(lines: &[String]) -> String {
+    lines.join("\n").trim().to_string()
+}
+
+/// The first declaration after line `from`, skipping blank lines, attributes
+/// and decorators. Declarations are cut before their body, which starts at
+/// `body_start`.
+fn declaration_after(lines: &[&str], from: usize, body_start: &str) -> Option<String> {
+    let line = lines
+        .iter()
+        .skip(from)
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with("#[") && !line.starts_with('@'))?;
+    let declaration = one_line(line)
+        .split(body_start)
+        .next()
+        .unwrap_or("")
+        .trim_end_matches(|c: char| c == '{' || c == ';' || c == ':' || c.is_whitespace())
+        .to_string();
+    if declaration.is_empty() {
+        None
+    } else {
+        Some(declaration)
+    }
+}
+
+fn rust_docs(file: &path::Path, lines: &[&str]) -> Vec<DocComment> {
+    let is_crate_root = matches!(
+        file.file_name().and_then(|name| name.to_str()),
+        Some("lib.rs") | Some("main.rs")
+    );
+    let mut docs = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_start();
+        let marker = if line.starts_with("//!") {
+            "//!"
+        } else if line.starts_with("///") && !line.starts_with("////") {
+            "///"
+        } else {
+            i += 1;
+            continue;
+        };
+        let mut text = Vec::new();
+        while i < lines.len() && lines[i].trim_start().starts_with(marker) {
+            let comment = lines[i].trim_start().trim_start_matches(marker);
+            text.push(comment.strip_prefix(' ').unwrap_or(comment).to_string());
+            i += 1;
+        }
+        if marker == "//!" {
+            docs.push(DocComment {
+                level: if is_crate_root {
+                    DocLevel::Crate
+                } else {
+                    DocLevel::Module
+                },
+                item: None,
+                text: join_lines(&text),
+            });
+        } else if let Some(item) = declaration_after(lines, i, " {") {
+            docs.push(DocComment {
+                level: DocLevel::Item,
+                item: Some(item),
+                text: join_lines(&text),
+            });
+        }
+    }
+    docs
+}
+
+/// JSDoc tags that describe the whole file, longer tags before their prefixes.
+const JSDOC_FILE_TAGS: [&str; 4] = ["@fileoverview", "@file", "@overview", "@module"];
+
+fn jsdoc_docs(lines: &[&str]) -> Vec<DocComment> {
+    let mut docs = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].trim_start().starts_with("/**") {
+            i += 1;
+            continue;
+        }
+        let mut comment = Vec::new();
+        loop {
+            let line = lines[i].trim();
+            let end = line.ends_with("*/");
+            let line = line
+                .trim_start_matches("/**")
+                .trim_end_matches("*/")
+                .trim_start_matches('*');
+            comment.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+            i += 1;
+            if end || i >= lines.len() {
+                break;
+            }
+        }
+
+        let mut is_file_doc = false;
+        let mut text = Vec::new();
+        for line in comment {
+            match JSDOC_FILE_TAGS.iter().find(|tag| line.starts_with(*tag)) {
+                Some(tag) => {
+                    is_file_doc = true;
+                    text.push(line[tag.len()..].trim().to_string());
+                }
+                // Parameter and return tags are already in the signature.
+                None if line.starts_with('@') => {}
+                None => text.push(line.to_string()),
+            }
+        }
+        if is_file_doc {
+            docs.push(DocComment {
+                level: DocLevel::Module,
+                item: None,
+                text: join_lines(&text),
+            });
+        } else if let Some(item) = declaration_after(lines, i, " {") {
+            docs.push(DocComment {
+                level: DocLevel::Item,
+                item: Some(item),
+                text: join_lines(&text),
+            });
+        }
+    }
+    docs
+}
+
+/// Reads the docstring that starts on line `i`, if there is one. Returns its
+/// text and the line after it.
+fn docstring(lines: &[&str], i: usize) -> Option<(String, usize)> {
+    let first = lines.get(i)?.trim();
+    let first = first
+        .strip_prefix('r')
+        .or_else(|| first.strip_prefix('u'))
+        .unwrap_or(first);
+    let quotes = ["\"\"\"", "'''"]
+        .into_iter()
+        .find(|quotes| first.starts_with(quotes))?;
+    let rest = &first[quotes.len()..];
+    if let Some(end) = rest.find(quotes) {
+        return Some((rest[..end].trim().to_string(), i + 1));
+    }
+    let mut text = vec![rest.trim().to_string()];
+    let mut j = i + 1;
+    while j < lines.len() {
+        let line = lines[j].trim();
+        if let Some(end) = line.find(quotes) {
+            text.push(line[..end].to_string());
+            return Some((join_lines(&text), j + 1));
+        }
+        text.push(line.to_string());
+        j += 1;
+    }
+    None
+}
+
+fn next_code_line(lines: &[&str], from: usize) -> usize {
+    (from..lines.len())
+        .find(|&i| {
+            let line = lines[i].trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .unwrap_or(lines.len())
+}
+
+fn python_docs(lines: &[&str]) -> Vec<DocComment> {
+    let mut docs = Vec::new();
+    let mut i = next_code_line(lines, 0);
+    if let Some((text, next)) = docstring(lines, i) {
+        docs.push(DocComment {
+            level: DocLevel::Module,
+            item: None,
+            text,
+        });
+        i = next;
+    }
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let definition = line
+            .strip_prefix("async ")
+            .unwrap_or(line)
+            .strip_prefix("def ")
+            .or_else(|| line.strip_prefix("class "));
+        let name = match definition {
+            Some(definition) => definition
+                .split(|c: char| c == '(' || c == ':' || c.is_whitespace())
+                .next()
+                .unwrap_or(""),
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        // Signatures can span several lines, the body starts after the colon.
+        let mut signature = vec![line];
+        while !signature.last().unwrap_or(&"").ends_with(':') && i + 1 < lines.len() {
+            i += 1;
+            signature.push(lines[i].trim());
+        }
+        i += 1;
+        if name.starts_with('_') {
+            continue;
+        }
+        if let Some((text, next)) = docstring(lines, next_code_line(lines, i)) {
+            docs.push(DocComment {
+                level: DocLevel::Item,
+                item: Some(
+                    one_line(&signature.join(" "))
+                        .trim_end_matches(':')
+                        .to_string(),
+                ),
+                text,
+            });
+            i = next;
+        }
+    }
+    docs
+}
+
+fn purescript_docs(lines: &[&str]) -> Vec<DocComment> {
+    let mut docs = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].trim_start().starts_with("-- |") {
+            i += 1;
+            continue;
+        }
+        let mut text = Vec::new();
+        let first = lines[i].trim_start().trim_start_matches("-- |");
+        text.push(first.trim().to_string());
+        i += 1;
+        // The comment continues on the following `--` lines.
+        while i < lines.len() && lines[i].trim_start().starts_with("--") {
+            let comment = lines[i].trim_start().trim_start_matches("--");
+            // Some authors repeat the `|` on every line.
+            let comment = comment.strip_prefix(" |").unwrap_or(comment);
+            text.push(comment.strip_prefix(' ').unwrap_or(comment).to_string());
+            i += 1;
+        }
+        // Braces are records in PureScript, bodies start at `where`.
+        match declaration_after(lines, i, " where") {
+            Some(declaration) if declaration.starts_with("module ") => docs.push(DocComment {
+                level: DocLevel::Module,
+                item: None,
+                text: join_lines(&text),
+            }),
+            Some(declaration) => docs.push(DocComment {
+                level: DocLevel::Item,
+                item: Some(declaration),
+                text: join_lines(&text),
+            }),
+            None => {}
+        }
+    }
+    docs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn harvest(file: &str, source: &str) -> Vec<(DocLevel, Option<String>, String)> {
+        harvest_file(path::Path::new(file), source)
+            .into_iter()
+            .map(|doc| (doc.level, doc.item, doc.text))
+            .collect()
+    }
+
+    #[test]
+    fn rust_crate_and_item_docs() {
+        let source = "//! The CLI.\n//!\n//! Details.\n\n/// Runs it.\n#[inline]\npub fn run(args: &[String]) -> i32 {\n    0\n}\n//// Not a doc comment.\nfn hidden() {}\n";
+        assert_eq!(
+            harvest("src/main.rs", source),
+            vec![
+                (DocLevel::Crate, None, "The CLI.\n\nDetails.".to_string()),
+                (
+                    DocLevel::Item,
+                    Some("pub fn run(args: &[String]) -> i32".to_string()),
+                    "Runs it.".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            harvest("src/cmd/mod.rs", "//! Commands.\n")[0].0,
+            DocLevel::Module
+        );
+    }
+
+    #[test]
+    fn jsdoc_file_and_item_docs() {
+        let source = "/**\n * @fileoverview The client.\n */\n\n/**\n * Sends a request.\n * @param body the payload\n */\nexport function send(body) {\n}\n";
+        assert_eq!(
+            harvest("client.js", source),
+            vec![
+                (DocLevel::Module, None, "The client.".to_string()),
+                (
+                    DocLevel::Item,
+                    Some("export function send(body)".to_string()),
+                    "Sends a request.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_docstrings() {
+        let source = "#!/usr/bin/env python3\n\"\"\"Exports the model.\"\"\"\n\ndef export(\n    path,\n):\n    \"\"\"Writes the model.\n\n    Overwrites existing files.\n    \"\"\"\n\ndef _private():\n    \"\"\"Hidden.\"\"\"\n";
+        assert_eq!(
+            harvest("export.py", source),
+            vec![
+                (DocLevel::Module, None, "Exports the model.".to_string()),
+                (
+                    DocLevel::Item,
+                    Some("def export(path)".to_string()),
+                    "Writes the model.\n\nOverwrites existing files.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn purescript_module_and_item_docs() {
+        let source = "-- | The API types.\nmodule Api.Types where\n\n-- | A request.\n-- | With files.\ntype Request = { files :: Array String }\n";
+        assert_eq!(
+            harvest("Types.purs", source),
+            vec![
+                (DocLevel::Module, None, "The API types.".to_string()),
+                (
+                    DocLevel::Item,
+                    Some("type Request = { files :: Array String }".to_string()),
+                    "A request.\nWith files.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn compact_puts_broad_docs_first() {
+        let documentation = Documentation {
+            files: vec![
+                FileDocs {
+                    path: path::PathBuf::from("src/cmd/mod.rs"),
+                    docs: harvest_file(
+                        path::Path::new("src/cmd/mod.rs"),
+                        "/// Runs.\npub fn run() {}\n",
+                    ),
+                },
+                FileDocs {
+                    path: path::PathBuf::from("src/main.rs"),
+                    docs: harvest_file(path::Path::new("src/main.rs"), "//! The CLI.\n"),
+                },
+            ],
+        };
+        assert_eq!(
+            documentation.compact(),
+            "src/main.rs\n  The CLI.\nsrc/cmd/mod.rs\n  pub fn run(): Runs.\n"
+        );
+    }
+}
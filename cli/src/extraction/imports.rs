@@ -0,0 +1,961 @@
+//! The module graph of a project: which modules there are and which ones
+//! they import. Rust modules are found by following `mod` declarations from
+//! the crate roots and linked by their `use crate::...` paths. JavaScript,
+//! TypeScript, Python and PureScript modules are the listed files, linked by
+//! the imports that resolve to one of them.
+
+use crate::cmd::ranking;
+use crate::extraction::read_source;
+use crate::types;
+use std::collections;
+use std::path;
+
+/// A module, named by its path segments below a root: the crate directory
+/// for Rust, the language otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModuleId {
+    pub root: String,
+    pub segments: Vec<String>,
+    pub separator: &'static str,
+}
+
+impl ModuleId {
+    /// The module name, e.g. `cmd::scribe`. A crate root is named after the
+    /// crate's directory.
+    pub fn label(&self) -> String {
+        if self.segments.is_empty() {
+            self.root.clone()
+        } else {
+            self.segments.join(self.separator)
+        }
+    }
+
+    /// The ancestor at `depth` segments, or the module itself if it is
+    /// shallower.
+    pub fn collapse(&self, depth: usize) -> ModuleId {
+        ModuleId {
+            segments: self.segments.iter().take(depth).cloned().collect(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModuleGraph {
+    pub modules: collections::BTreeSet<ModuleId>,
+    /// Modules that are tests, by their file or their name.
+    pub test_modules: collections::BTreeSet<ModuleId>,
+    /// Importing module and imported module.
+    pub edges: collections::BTreeSet<(ModuleId, ModuleId)>,
+}
+
+impl ModuleGraph {
+    fn add_module(&mut self, id: ModuleId, file: &path::Path) {
+        if ranking::is_test(file) || id.segments.last().map(|s| s.as_str()) == Some("tests") {
+            self.test_modules.insert(id.clone());
+        }
+        self.modules.insert(id);
+    }
+
+    fn add_edge(&mut self, from: &ModuleId, to: ModuleId) {
+        if *from != to {
+            self.edges.insert((from.clone(), to));
+        }
+    }
+}
+
+fn display_path(file: &path::Path) -> path::PathBuf {
+    file.strip_prefix(".").unwrap_or(file).to_path_buf()
+}
+
+fn extension(file: &path::Path) -> &str {
+    file.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+}
+
+fn file_name(file: &path::Path) -> &str {
+    file.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("")
+}
+
+/// Path segments of a file without its extension, e.g. `src/util/index`.
+fn path_segments(file: &path::Path) -> Vec<String> {
+    display_path(&file.with_extension(""))
+        .components()
+        .filter_map(|component| component.as_os_str().to_str())
+        .map(|component| component.to_string())
+        .collect()
+}
+
+/// Removes `//` comments, enough to not mistake commented out code for
+/// declarations.
+fn strip_line_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(start) => &line[..start],
+        None => line,
+    }
+}
+
+/// `mod name;` declarations of a Rust file, inline modules are skipped.
+fn rust_mod_declarations(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .map(|line| strip_line_comment(line).trim())
+        .filter_map(|line| {
+            let line = line.strip_suffix(';')?;
+            let after_visibility = match line.strip_prefix("pub") {
+                Some(rest) if rest.starts_with('(') => rest.split_once(')')?.1,
+                Some(rest) => rest,
+                None => line,
+            };
+            let name = after_visibility.trim().strip_prefix("mod ")?.trim();
+            Some(name.trim_start_matches("r#").to_string())
+        })
+        .collect()
+}
+
+/// An inline `mod name { ... }` block.
+#[derive(Debug, Clone, PartialEq)]
+struct InlineModule {
+    name: String,
+    /// Whether it is `#[cfg(test)]` or inside such a module.
+    test: bool,
+}
+
+/// A `use` statement collapsed onto one line without `use` and the
+/// semicolon, with the inline modules it is in, outermost first.
+#[derive(Debug, Clone, PartialEq)]
+struct UseStatement {
+    inline_modules: Vec<InlineModule>,
+    tree: String,
+}
+
+/// How much a line changes the brace depth, not counting braces in string
+/// and character literals.
+fn brace_delta(line: &str) -> i32 {
+    let chars: Vec<char> = line.chars().collect();
+    let mut delta = 0;
+    let mut in_string = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if in_string => i += 1,
+            '"' => in_string = !in_string,
+            // Character literals like '{' and '\'', not lifetimes.
+            '\'' if !in_string => {
+                if chars.get(i + 2) == Some(&'\'') {
+                    i += 2;
+                } else if chars.get(i + 1) == Some(&'\\') && chars.get(i + 3) == Some(&'\'') {
+                    i += 3;
+                }
+            }
+            '{' if !in_string => delta += 1,
+            '}' if !in_string => delta -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    delta
+}
+
+fn strip_visibility(line: &str) -> &str {
+    match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => {
+            rest.split_once(')').map(|(_, rest)| rest).unwrap_or("")
+        }
+        Some(rest) => rest,
+        None => line,
+    }
+    .trim_start()
+}
+
+/// `use` statements of a Rust file. Statements in inline modules, like
+/// `mod tests { use super::*; }`, belong to those modules and not to the
+/// file's module.
+fn rust_use_statements(source: &str) -> Vec<UseStatement> {
+    let mut statements = Vec::new();
+    let mut current: Option<String> = None;
+    let mut inline_modules: Vec<(InlineModule, i32)> = Vec::new();
+    let mut depth = 0;
+    let mut cfg_test = false;
+    for line in source.lines().map(|line| strip_line_comment(line).trim()) {
+        let mut line = line;
+        if current.is_none() {
+            while line.starts_with("#[") {
+                match line.split_once(']') {
+                    Some((attribute, rest)) => {
+                        cfg_test = cfg_test || attribute.contains("cfg(test)");
+                        line = rest.trim_start();
+                    }
+                    None => break,
+                }
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let item = strip_visibility(line);
+            if let Some((name, _)) = item
+                .strip_prefix("mod ")
+                .and_then(|rest| rest.split_once('{'))
+            {
+                let test =
+                    cfg_test || inline_modules.last().map(|(module, _)| module.test) == Some(true);
+                inline_modules.push((
+                    InlineModule {
+                        name: name.trim().trim_start_matches("r#").to_string(),
+                        test,
+                    },
+                    depth + 1,
+                ));
+            } else if let Some(tree) = item.strip_prefix("use ") {
+                current = Some(tree.to_string());
+            }
+            cfg_test = false;
+        } else if let Some(statement) = current.as_mut() {
+            statement.push(' ');
+            statement.push_str(line);
+        }
+        if let Some(statement) = current.as_ref() {
+            if let Some(end) = statement.find(';') {
+                statements.push(UseStatement {
+                    inline_modules: inline_modules
+                        .iter()
+                        .map(|(module, _)| module.clone())
+                        .collect(),
+                    tree: statement[..end].to_string(),
+                });
+                current = None;
+            }
+        }
+        depth += brace_delta(line);
+        while inline_modules
+            .last()
+            .map(|(_, body_depth)| *body_depth > depth)
+            .unwrap_or(false)
+        {
+            inline_modules.pop();
+        }
+    }
+    statements
+}
+
+/// Splits at the commas that aren't nested in braces.
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+}
+
+/// Expands a use tree like `crate::cmd::{budget, ranking::Ranking}` into
+/// its paths.
+fn expand_use_tree(prefix: &[String], tree: &str, paths: &mut Vec<Vec<String>>) {
+    let tree = tree.trim();
+    if tree.is_empty() {
+        return;
+    }
+    if let Some(open) = tree.find('{') {
+        let head: Vec<String> = tree[..open]
+            .trim_end_matches("::")
+            .split("::")
+            .map(|segment| segment.trim().to_string())
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let close = tree.rfind('}').unwrap_or(tree.len());
+        let mut nested = prefix.to_vec();
+        nested.extend(head);
+        for part in split_top_level(&tree[open + 1..close]) {
+            expand_use_tree(&nested, part, paths);
+        }
+        return;
+    }
+    let tree = tree.split(" as ").next().unwrap_or(tree);
+    let mut path = prefix.to_vec();
+    for segment in tree.split("::").map(|segment| segment.trim()) {
+        match segment {
+            "self" | "*" | "" => {}
+            segment => path.push(segment.to_string()),
+        }
+    }
+    paths.push(path);
+}
+
+/// The file of `mod name;` declared in `file`, if it exists.
+fn rust_mod_file(file: &path::Path, name: &str) -> Option<path::PathBuf> {
+    let directory = match file_name(file) {
+        "mod.rs" | "main.rs" | "lib.rs" => file.parent()?.to_path_buf(),
+        _ => file.with_extension(""),
+    };
+    [
+        directory.join(format!("{}.rs", name)),
+        directory.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+/// Resolves a use path from `module` to the deepest module of the crate
+/// it names. External crates resolve to nothing.
+fn resolve_rust_path(graph: &ModuleGraph, module: &ModuleId, path: &[String]) -> Option<ModuleId> {
+    let mut segments: Vec<String> = match path.first().map(|s| s.as_str()) {
+        Some("crate") => Vec::new(),
+        Some("self") => module.segments.clone(),
+        Some("super") => {
+            let supers = path
+                .iter()
+                .take_while(|segment| *segment == "super")
+                .count();
+            let parent = module.segments.len().saturating_sub(supers);
+            module.segments[..parent].to_vec()
+        }
+        // Paths relative to the current module, e.g. `use cmd::scribe` in
+        // the crate root.
+        _ => module.segments.clone(),
+    };
+    let rest = path
+        .iter()
+        .skip_while(|segment| matches!(segment.as_str(), "crate" | "self" | "super"));
+    let base_len = segments.len();
+    segments.extend(rest.cloned());
+    (base_len..=segments.len()).rev().find_map(|len| {
+        let candidate = ModuleId {
+            segments: segments[..len].to_vec(),
+            ..module.clone()
+        };
+        let is_module = graph.modules.contains(&candidate);
+        // The module a relative path starts from isn't what it names.
+        let names_something = len > base_len
+            || matches!(
+                path.first().map(|s| s.as_str()),
+                Some("crate") | Some("super")
+            );
+        if is_module && names_something {
+            Some(candidate)
+        } else {
+            None
+        }
+    })
+}
+
+async fn rust_graph(files: &[path::PathBuf], graph: &mut ModuleGraph) -> types::Result<()> {
+    let crate_roots = files.iter().filter(|file| {
+        matches!(file_name(file), "main.rs" | "lib.rs")
+            && file
+                .parent()
+                .map(|parent| file_name(parent) == "src")
+                .unwrap_or(false)
+    });
+    let mut uses: Vec<(ModuleId, Vec<String>)> = Vec::new();
+    for crate_root in crate_roots {
+        let crate_name = crate_root
+            .parent()
+            .and_then(|src| src.parent())
+            .map(|directory| file_name(directory).to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "crate".to_string());
+        let root_id = ModuleId {
+            root: crate_name,
+            segments: Vec::new(),
+            separator: "::",
+        };
+        let mut pending = vec![(root_id, crate_root.clone())];
+        while let Some((id, file)) = pending.pop() {
+            if graph.modules.contains(&id) {
+                continue;
+            }
+            let source = match read_source(&file).await? {
+                Some(source) => source,
+                None => continue,
+            };
+            graph.add_module(id.clone(), &file);
+            for name in rust_mod_declarations(&source) {
+                if let Some(child_file) = rust_mod_file(&file, &name) {
+                    let mut child = id.clone();
+                    child.segments.push(name);
+                    pending.push((child, child_file));
+                }
+            }
+            for statement in rust_use_statements(&source) {
+                let mut module = id.clone();
+                for inline_module in statement.inline_modules.iter() {
+                    module.segments.push(inline_module.name.clone());
+                    graph.add_module(module.clone(), &file);
+                    if inline_module.test {
+                        graph.test_modules.insert(module.clone());
+                    }
+                }
+                let mut paths = Vec::new();
+                expand_use_tree(&[], &statement.tree, &mut paths);
+                uses.extend(paths.into_iter().map(|path| (module.clone(), path)));
+            }
+        }
+    }
+    for (module, path) in uses {
+        if let Some(target) = resolve_rust_path(graph, &module, &path) {
+            graph.add_edge(&module, target);
+        }
+    }
+    Ok(())
+}
+
+/// Quoted module specifiers after `from`, `import` and `require(`.
+fn javascript_specifiers(source: &str) -> Vec<String> {
+    let mut specifiers = Vec::new();
+    for keyword in ["from ", "import ", "import(", "require("] {
+        for (start, _) in source.match_indices(keyword) {
+            let rest = source[start + keyword.len()..].trim_start();
+            let quote = match rest.chars().next() {
+                Some(quote) if quote == '\'' || quote == '"' || quote == '`' => quote,
+                _ => continue,
+            };
+            if let Some(end) = rest[1..].find(quote) {
+                specifiers.push(rest[1..1 + end].to_string());
+            }
+        }
+    }
+    specifiers
+}
+
+/// Joins a relative path to a directory, resolving `.` and `..`.
+fn join_relative(directory: &path::Path, relative: &str) -> path::PathBuf {
+    let mut joined = display_path(directory);
+    for part in relative.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                joined.pop();
+            }
+            part => joined.push(part),
+        }
+    }
+    joined
+}
+
+const JAVASCRIPT_EXTENSIONS: [&str; 6] = ["js", "jsx", "mjs", "cjs", "ts", "tsx"];
+
+fn javascript_graph(
+    files: &[path::PathBuf],
+    graph: &mut ModuleGraph,
+) -> Vec<(ModuleId, path::PathBuf)> {
+    let modules: Vec<&path::PathBuf> = files
+        .iter()
+        .filter(|file| JAVASCRIPT_EXTENSIONS.contains(&extension(file)))
+        .collect();
+    for file in modules.iter() {
+        graph.add_module(javascript_id(file), file);
+    }
+    modules
+        .into_iter()
+        .map(|file| (javascript_id(file), file.clone()))
+        .collect()
+}
+
+fn javascript_id(file: &path::Path) -> ModuleId {
+    ModuleId {
+        root: "javascript".to_string(),
+        segments: path_segments(file),
+        separator: "/",
+    }
+}
+
+/// The listed module a relative specifier points to, trying the extensions
+/// and `index` files that bundlers try.
+fn resolve_javascript(
+    known: &collections::HashSet<path::PathBuf>,
+    file: &path::Path,
+    specifier: &str,
+) -> Option<path::PathBuf> {
+    if !specifier.starts_with('.') {
+        return None;
+    }
+    let target = join_relative(file.parent()?, specifier);
+    let mut candidates = vec![target.clone()];
+    for extension in JAVASCRIPT_EXTENSIONS {
+        candidates.push(target.with_extension(extension));
+        candidates.push(target.join(format!("index.{}", extension)));
+    }
+    candidates
+        .into_iter()
+        .find(|candidate| known.contains(candidate))
+}
+
+/// Dotted name of a Python module, e.g. `pkg.util` for `pkg/util.py` and
+/// `pkg` for `pkg/__init__.py`.
+fn python_segments(file: &path::Path) -> Vec<String> {
+    let mut segments = path_segments(file);
+    if segments.last().map(|s| s.as_str()) == Some("__init__") {
+        segments.pop();
+    }
+    segments
+}
+
+/// Imported module names of a Python file, relative ones with their dots.
+/// `from a import b` yields `a.b` too, since `b` may be a submodule.
+fn python_imports(source: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    for line in source.lines().map(|line| line.trim()) {
+        if let Some(names) = line.strip_prefix("import ") {
+            for name in names.split(',') {
+                let name = name.split(" as ").next().unwrap_or("").trim();
+                if !name.is_empty() {
+                    imports.push(name.to_string());
+                }
+            }
+        } else if let Some(rest) = line.strip_prefix("from ") {
+            let (module, names) = match rest.split_once(" import ") {
+                Some(parts) => parts,
+                None => continue,
+            };
+            let module = module.trim();
+            imports.push(module.to_string());
+            for name in names
+                .trim_matches(|c| c == '(' || c == ')' || c == '\\')
+                .split(',')
+            {
+                let name = name.split(" as ").next().unwrap_or("").trim();
+                if !name.is_empty() && name != "*" {
+                    let separator = if module.ends_with('.') { "" } else { "." };
+                    imports.push(format!("{}{}{}", module, separator, name));
+                }
+            }
+        }
+    }
+    imports
+}
+
+/// Directories that absolute imports start from: the directory above each
+/// top-level package, the directory of modules outside of packages, like
+/// `src` in `src` layouts, and the root.
+fn python_source_roots(files: &[&path::PathBuf]) -> Vec<Vec<String>> {
+    let packages: collections::HashSet<Vec<String>> = files
+        .iter()
+        .filter(|file| file_name(file) == "__init__.py")
+        .map(|file| python_segments(file))
+        .collect();
+    let mut roots: Vec<Vec<String>> = vec![Vec::new()];
+    for file in files.iter() {
+        let mut directory = path_segments(file);
+        directory.pop();
+        while !directory.is_empty() && packages.contains(&directory) {
+            directory.pop();
+        }
+        if !roots.contains(&directory) {
+            roots.push(directory);
+        }
+    }
+    roots
+}
+
+/// Resolves an imported name to a listed module. Relative names start from
+/// the importing file's package, absolute ones from one of the source roots.
+fn resolve_python(
+    modules: &[Vec<String>],
+    roots: &[Vec<String>],
+    file: &path::Path,
+    import: &str,
+) -> Option<Vec<String>> {
+    let dots = import.chars().take_while(|c| *c == '.').count();
+    let name: Vec<String> = import[dots..]
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.to_string())
+        .collect();
+    if dots > 0 {
+        let mut package = python_segments(file);
+        if file_name(file) != "__init__.py" {
+            package.pop();
+        }
+        for _ in 1..dots {
+            package.pop();
+        }
+        package.extend(name);
+        return modules.iter().find(|module| **module == package).cloned();
+    }
+    if name.is_empty() {
+        return None;
+    }
+    roots.iter().find_map(|root| {
+        let mut module = root.clone();
+        module.extend(name.iter().cloned());
+        modules.iter().find(|known| **known == module).cloned()
+    })
+}
+
+/// Name of a PureScript module from its header and the modules it imports.
+fn purescript_imports(source: &str) -> (Option<String>, Vec<String>) {
+    let mut module = None;
+    let mut imports = Vec::new();
+    for line in source.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("module") if module.is_none() => {
+                module = words
+                    .next()
+                    .and_then(|name| name.split('(').next())
+                    .map(str::to_string);
+            }
+            Some("import") => {
+                if let Some(name) = words.next().and_then(|name| name.split('(').next()) {
+                    imports.push(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    (module, imports)
+}
+
+fn dotted_id(root: &str, name: &str) -> ModuleId {
+    ModuleId {
+        root: root.to_string(),
+        segments: name.split('.').map(|segment| segment.to_string()).collect(),
+        separator: ".",
+    }
+}
+
+/// Builds the module graph of the listed files, skipping files that aren't
+/// UTF-8.
+pub async fn module_graph(files: Vec<path::PathBuf>) -> types::Result<ModuleGraph> {
+    let mut sorted_files = files;
+    sorted_files.sort();
+    let mut graph = ModuleGraph::default();
+    rust_graph(&sorted_files, &mut graph).await?;
+
+    let known: collections::HashSet<path::PathBuf> =
+        sorted_files.iter().map(|file| display_path(file)).collect();
+    for (id, file) in javascript_graph(&sorted_files, &mut graph) {
+        let source = match read_source(&file).await? {
+            Some(source) => source,
+            None => continue,
+        };
+        for specifier in javascript_specifiers(&source) {
+            if let Some(target) = resolve_javascript(&known, &display_path(&file), &specifier) {
+                graph.add_edge(&id, javascript_id(&target));
+            }
+        }
+    }
+
+    let python_files: Vec<&path::PathBuf> = sorted_files
+        .iter()
+        .filter(|file| extension(file) == "py")
+        .collect();
+    let python_modules: Vec<Vec<String>> = python_files
+        .iter()
+        .map(|file| python_segments(file))
+        .collect();
+    let python_roots = python_source_roots(&python_files);
+    for file in python_files.iter() {
+        let id = ModuleId {
+            root: "python".to_string(),
+            segments: python_segments(file),
+            separator: ".",
+        };
+        graph.add_module(id.clone(), file);
+        let source = match read_source(file).await? {
+            Some(source) => source,
+            None => continue,
+        };
+        for import in python_imports(&source) {
+            if let Some(segments) = resolve_python(&python_modules, &python_roots, file, &import) {
+                let target = ModuleId {
+                    segments,
+                    ..id.clone()
+                };
+                graph.add_edge(&id, target);
+            }
+        }
+    }
+
+    let mut purescript_modules: Vec<(ModuleId, Vec<String>)> = Vec::new();
+    for file in sorted_files.iter().filter(|file| extension(file) == "purs") {
+        let source = match read_source(file).await? {
+            Some(source) => source,
+            None => continue,
+        };
+        if let (Some(name), imports) = purescript_imports(&source) {
+            let id = dotted_id("purescript", &name);
+            graph.add_module(id.clone(), file);
+            purescript_modules.push((id, imports));
+        }
+    }
+    for (id, imports) in purescript_modules {
+        for import in imports {
+            let target = dotted_id("purescript", &import);
+            if graph.modules.contains(&target) {
+                graph.add_edge(&id, target);
+            }
+        }
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments(dotted: &str) -> Vec<String> {
+        dotted.split('.').map(|s| s.to_string()).collect()
+    }
+
+    fn expand(tree: &str) -> Vec<String> {
+        let mut paths = Vec::new();
+        expand_use_tree(&[], tree, &mut paths);
+        paths.into_iter().map(|path| path.join("::")).collect()
+    }
+
+    #[test]
+    fn use_trees_expand_to_paths() {
+        assert_eq!(
+            expand("crate::cmd::{budget, ranking::{self, Ranking}, util as u}"),
+            vec![
+                "crate::cmd::budget",
+                "crate::cmd::ranking",
+                "crate::cmd::ranking::Ranking",
+                "crate::cmd::util"
+            ]
+        );
+        assert_eq!(expand("super::*"), vec!["super"]);
+        assert_eq!(expand(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn use_statements_in_inline_modules_belong_to_them() {
+        let source = r#"
+use crate::cmd::{budget, ranking};
+pub(crate) use super::util;
+
+fn braces() -> (char, &'static str) {
+    ('{', "}}")
+}
+
+pub mod stub {
+    use std::sync;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    mod nested {
+        use crate::http::stub;
+    }
+}
+"#;
+        let statements: Vec<(Vec<(String, bool)>, String)> = rust_use_statements(source)
+            .into_iter()
+            .map(|statement| {
+                (
+                    statement
+                        .inline_modules
+                        .into_iter()
+                        .map(|module| (module.name, module.test))
+                        .collect(),
+                    statement.tree,
+                )
+            })
+            .collect();
+        assert_eq!(
+            statements,
+            vec![
+                (vec![], "crate::cmd::{budget, ranking}".to_string()),
+                (vec![], "super::util".to_string()),
+                (vec![("stub".to_string(), false)], "std::sync".to_string()),
+                (vec![("tests".to_string(), true)], "super::*".to_string()),
+                (
+                    vec![("tests".to_string(), true), ("nested".to_string(), true)],
+                    "crate::http::stub".to_string()
+                ),
+            ]
+        );
+    }
+
+    fn write(directory: &path::Path, file: &str, content: &str) -> path::PathBuf {
+        let path = directory.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn labels(graph: &ModuleGraph) -> Vec<(String, String)> {
+        graph
+            .edges
+            .iter()
+            .map(|(from, to)| (from.label(), to.label()))
+            .collect()
+    }
+
+    fn edge(from: &str, to: &str) -> (String, String) {
+        (from.to_string(), to.to_string())
+    }
+
+    #[tokio::test]
+    async fn rust_modules_are_linked_by_their_use_paths() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        let files = vec![
+            write(root, "cli/src/main.rs", "mod cmd;\nmod http;\nuse cmd::scribe;\n"),
+            write(
+                root,
+                "cli/src/cmd/mod.rs",
+                "pub mod directory_listing;\npub mod file_selection;\n\
+                 pub mod language_detection;\npub mod scribe;\n",
+            ),
+            write(
+                root,
+                "cli/src/cmd/scribe.rs",
+                "use crate::cmd::{\n    directory_listing,\n    file_selection::{self, classify},\n};\n\
+                 use super::language_detection;\nuse std::path;\n\n\
+                 #[cfg(test)]\nmod tests {\n    use super::*;\n    use crate::http::stub;\n}\n",
+            ),
+            write(root, "cli/src/cmd/directory_listing.rs", "use std::fs;\n"),
+            write(root, "cli/src/cmd/file_selection.rs", "use crate::http;\n"),
+            write(root, "cli/src/cmd/language_detection.rs", ""),
+            write(
+                root,
+                "cli/src/http.rs",
+                "#[cfg(test)]\npub mod stub {\n    use std::sync;\n}\n",
+            ),
+        ];
+        let graph = module_graph(files).await.unwrap();
+
+        assert_eq!(
+            labels(&graph),
+            vec![
+                edge("cli", "cmd::scribe"),
+                edge("cmd::file_selection", "http"),
+                edge("cmd::scribe", "cmd::directory_listing"),
+                edge("cmd::scribe", "cmd::file_selection"),
+                edge("cmd::scribe", "cmd::language_detection"),
+                edge("cmd::scribe::tests", "cmd::scribe"),
+                edge("cmd::scribe::tests", "http::stub"),
+            ]
+        );
+        let test_modules: Vec<String> = graph.test_modules.iter().map(ModuleId::label).collect();
+        assert_eq!(test_modules, vec!["cmd::scribe::tests", "http::stub"]);
+    }
+
+    #[tokio::test]
+    async fn javascript_and_purescript_imports_resolve_to_listed_modules() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        let main = write(
+            root,
+            "web/main.ts",
+            "import { render } from './render';\nimport React from 'react';\n\
+             const util = require(\"./util/index.js\");\n",
+        );
+        let render = write(
+            root,
+            "web/render.tsx",
+            "export const render = () => null;\n",
+        );
+        let util = write(root, "web/util/index.js", "import('../main');\n");
+        let files = vec![
+            main.clone(),
+            render.clone(),
+            util.clone(),
+            write(
+                root,
+                "api/src/Main.purs",
+                "module Main where\n\nimport Prelude\nimport Api.Types (Request)\n",
+            ),
+            write(
+                root,
+                "api/src/Api/Types.purs",
+                "module Api.Types (Request) where\n",
+            ),
+        ];
+        let graph = module_graph(files).await.unwrap();
+
+        let edges: Vec<&(ModuleId, ModuleId)> = graph
+            .edges
+            .iter()
+            .filter(|(from, _)| from.root == "javascript")
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                &(javascript_id(&main), javascript_id(&render)),
+                &(javascript_id(&main), javascript_id(&util)),
+                &(javascript_id(&util), javascript_id(&main)),
+            ]
+        );
+        assert!(graph.edges.contains(&(
+            dotted_id("purescript", "Main"),
+            dotted_id("purescript", "Api.Types")
+        )));
+        assert_eq!(
+            purescript_imports("module Api.Types(Request) where\nimport Data.Maybe(Maybe)\n"),
+            (
+                Some("Api.Types".to_string()),
+                vec!["Data.Maybe".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn python_imports_include_submodules() {
+        let source = "import os, pkg.util as u\nfrom . import models\nfrom .db import (Session, engine)\nfrom x import *\n";
+        assert_eq!(
+            python_imports(source),
+            vec![
+                "os",
+                "pkg.util",
+                ".",
+                ".models",
+                ".db",
+                ".db.Session",
+                ".db.engine",
+                "x"
+            ]
+        );
+    }
+
+    #[test]
+    fn python_resolves_from_source_roots() {
+        let files: Vec<path::PathBuf> = [
+            "./src/pkg/__init__.py",
+            "./src/pkg/util.py",
+            "./src/pkg/db/__init__.py",
+            "./src/pkg/db/models.py",
+            "./scripts/util.py",
+            "./setup.py",
+        ]
+        .iter()
+        .map(path::PathBuf::from)
+        .collect();
+        let file_refs: Vec<&path::PathBuf> = files.iter().collect();
+        let modules: Vec<Vec<String>> = files.iter().map(|file| python_segments(file)).collect();
+        let roots = python_source_roots(&file_refs);
+        assert_eq!(roots, vec![vec![], segments("src"), segments("scripts")]);
+
+        let models = path::Path::new("./src/pkg/db/models.py");
+        let resolve = |file: &path::Path, import: &str| {
+            resolve_python(&modules, &roots, file, import).map(|module| module.join("."))
+        };
+        assert_eq!(resolve(models, "pkg.util").as_deref(), Some("src.pkg.util"));
+        assert_eq!(resolve(models, "..util").as_deref(), Some("src.pkg.util"));
+        assert_eq!(resolve(models, ".").as_deref(), Some("src.pkg.db"));
+        // `util` is a top-level module of `scripts`, not `pkg.util`.
+        assert_eq!(
+            resolve(path::Path::new("./setup.py"), "util").as_deref(),
+            Some("scripts.util")
+        );
+        // Names that merely end like a module don't match it.
+        assert_eq!(resolve(models, "db.models"), None);
+        assert_eq!(resolve(models, "os"), None);
+    }
+}
@@ -0,0 +1,150 @@
+pub mod docs;
+pub mod imports;
+mod purescript;
+mod syntax;
+
+use crate::cmd::util;
+use crate::error::Error;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path;
+
+/// Kinds of public items, in the order they are listed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ItemKind {
+    Module,
+    Trait,
+    Type,
+    Function,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemKind::Function => write!(f, "function"),
+            ItemKind::Type => write!(f, "type"),
+            ItemKind::Trait => write!(f, "trait"),
+            ItemKind::Module => write!(f, "module"),
+        }
+    }
+}
+
+/// A public item with its signature, i.e. its declaration without the body.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiItem {
+    pub kind: ItemKind,
+    pub name: String,
+    pub signature: String,
+}
+
+/// The public items of a single file, in source order.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileApi {
+    pub path: path::PathBuf,
+    pub items: Vec<ApiItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ApiReference {
+    pub files: Vec<FileApi>,
+}
+
+impl ApiReference {
+    pub fn item_count(&self) -> usize {
+        self.files.iter().map(|file| file.items.len()).sum()
+    }
+
+    /// One signature per line, grouped by file, as context for the backend.
+    pub fn compact(&self) -> String {
+        let mut compact = String::new();
+        for file in self.files.iter() {
+            compact.push_str(&format!("{}\n", file.path.display()));
+            for item in file.items.iter() {
+                compact.push_str(&format!("  {}\n", item.signature));
+            }
+        }
+        compact
+    }
+}
+
+/// Collapses the whitespace of a declaration so that it fits on one line.
+fn one_line(declaration: &str) -> String {
+    declaration
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        // Parameter lists that were spread over several lines.
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .trim_end_matches(',')
+        .to_string()
+}
+
+/// Extracts the public items of a file, chosen by its extension. Files in
+/// unsupported languages have no items.
+pub fn extract_file(file: &path::Path, source: &str) -> types::Result<Vec<ApiItem>> {
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    match extension {
+        "rs" => syntax::extract(syntax::Grammar::Rust, source),
+        "js" | "jsx" | "mjs" | "cjs" => syntax::extract(syntax::Grammar::JavaScript, source),
+        "ts" => syntax::extract(syntax::Grammar::TypeScript, source),
+        "tsx" => syntax::extract(syntax::Grammar::Tsx, source),
+        "py" => syntax::extract(syntax::Grammar::Python, source),
+        "go" => syntax::extract(syntax::Grammar::Go, source),
+        "purs" => Ok(purescript::extract(source)),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Reads a source file, `None` if it isn't UTF-8.
+pub async fn read_source(file: &path::Path) -> types::Result<Option<String>> {
+    match util::read_utf8_file(file).await {
+        Ok(source) => Ok(Some(source)),
+        Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::InvalidData => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Extracts the public API of all files, skipping files that aren't UTF-8.
+pub async fn extract(files: Vec<path::PathBuf>) -> types::Result<ApiReference> {
+    let mut sorted_files = files;
+    sorted_files.sort();
+    let mut reference = ApiReference::default();
+    for file in sorted_files.into_iter() {
+        let source = match read_source(&file).await? {
+            Some(source) => source,
+            None => continue,
+        };
+        let items = extract_file(&file, &source)?;
+        if !items.is_empty() {
+            reference.files.push(FileApi { path: file, items });
+        }
+    }
+    Ok(reference)
+}
+
+/// Harvests the doc comments of all files, skipping files that aren't UTF-8.
+pub async fn harvest_docs(files: Vec<path::PathBuf>) -> types::Result<docs::Documentation> {
+    let mut sorted_files = files;
+    sorted_files.sort();
+    let mut documentation = docs::Documentation::default();
+    for file in sorted_files.into_iter() {
+        let source = match read_source(&file).await? {
+            Some(source) => source,
+            None => continue,
+        };
+        let file_docs = docs::harvest_file(&file, &source);
+        if !file_docs.is_empty() {
+            documentation.files.push(docs::FileDocs {
+                path: file,
+                docs: file_docs,
+            });
+        }
+    }
+    Ok(documentation)
+}
@@ -0,0 +1,162 @@
+//! PureScript has no tree-sitter grammar on crates.io, so its public API is
+//! read from the layout instead: top-level declarations start in the first
+//! column and the module header lists the exports.
+
+use crate::extraction::{one_line, ApiItem, ItemKind};
+
+/// The names in the module's export list, `None` if everything is exported.
+fn exports(source: &str) -> Option<Vec<String>> {
+    let header_start = source.find("module ")?;
+    let header_end = header_start + source[header_start..].find(" where")?;
+    let header = &source[header_start..header_end];
+    let list_start = header.find('(')?;
+    let list_end = header.rfind(')')?;
+    let list = header.get(list_start + 1..list_end)?;
+    Some(
+        list.split(',')
+            .map(|export| {
+                // `Type(..)` exports the type, `class Foo` exports the class.
+                let export = export.trim().trim_start_matches("class ").trim();
+                export
+                    .split(|c: char| c == '(' || c.is_whitespace())
+                    .next()
+                    .unwrap_or("")
+                    .to_string()
+            })
+            .filter(|export| !export.is_empty())
+            .collect(),
+    )
+}
+
+/// Top-level declarations with the lines that continue them, i.e. the
+/// following lines that are indented.
+fn declarations(source: &str) -> Vec<String> {
+    let mut declarations: Vec<String> = Vec::new();
+    for line in source.lines() {
+        if line.starts_with(|c: char| c.is_whitespace()) {
+            if let Some(last) = declarations.last_mut() {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+        } else if !line.trim().is_empty() && !line.starts_with("--") {
+            declarations.push(line.to_string());
+        }
+    }
+    declarations
+}
+
+fn item(declaration: &str) -> Option<(ItemKind, String, String)> {
+    let declaration = one_line(declaration);
+    let mut words = declaration.split_whitespace();
+    match words.next()? {
+        "data" | "newtype" | "type" => {
+            let name = words.next()?.to_string();
+            // The constructors are left out, like bodies in other languages.
+            let signature = declaration
+                .split(" =")
+                .next()
+                .unwrap_or(&declaration)
+                .to_string();
+            Some((ItemKind::Type, name, signature))
+        }
+        "class" => {
+            let signature = declaration
+                .split(" where")
+                .next()
+                .unwrap_or(&declaration)
+                .to_string();
+            // The class name follows the superclass constraints, if any.
+            let head = signature.rsplit("<=").next().unwrap_or(&signature);
+            let name = head
+                .split_whitespace()
+                .find(|word| *word != "class")?
+                .to_string();
+            Some((ItemKind::Trait, name, signature))
+        }
+        "foreign" => {
+            let name = words.nth(1)?.to_string();
+            Some((ItemKind::Function, name, declaration.clone()))
+        }
+        // A type signature, `name :: Type`, and not a definition whose
+        // `where` clause happens to contain one.
+        name if words.next() == Some("::") => {
+            Some((ItemKind::Function, name.to_string(), declaration.clone()))
+        }
+        _ => None,
+    }
+}
+
+pub fn extract(source: &str) -> Vec<ApiItem> {
+    let exported = exports(source);
+    declarations(source)
+        .iter()
+        .filter_map(|declaration| item(declaration))
+        .filter(|(_, name, _)| {
+            exported
+                .as_ref()
+                .map(|exported| exported.contains(name))
+                .unwrap_or(true)
+        })
+        .map(|(kind, name, signature)| ApiItem {
+            kind,
+            name,
+            signature,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(source: &str) -> Vec<(ItemKind, String)> {
+        extract(source)
+            .into_iter()
+            .map(|item| (item.kind, item.name))
+            .collect()
+    }
+
+    #[test]
+    fn export_list_limits_the_items() {
+        let source = r#"module Api.Types (Request(..), class Encode, summarize) where
+
+data Request = Request { files :: Array String }
+
+class Monad m <= Encode m where
+  encode :: m String
+
+summarize
+  :: Request
+  -> String
+summarize _ = helper
+  where
+  helper :: String
+  helper = ""
+
+internal :: Int
+internal = 1
+"#;
+        assert_eq!(
+            names(source),
+            vec![
+                (ItemKind::Type, "Request".to_string()),
+                (ItemKind::Trait, "Encode".to_string()),
+                (ItemKind::Function, "summarize".to_string()),
+            ]
+        );
+        let summarize = extract(source).pop().unwrap();
+        assert_eq!(summarize.signature, "summarize :: Request -> String");
+    }
+
+    #[test]
+    fn modules_without_export_list_export_everything() {
+        let source = "module Main where\n\n-- | Entry point.\nmain :: Effect Unit\nmain = pure unit\n\nforeign import now :: Effect Number\n";
+        assert_eq!(
+            names(source),
+            vec![
+                (ItemKind::Function, "main".to_string()),
+                (ItemKind::Function, "now".to_string()),
+            ]
+        );
+    }
+}
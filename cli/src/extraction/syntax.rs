@@ -0,0 +1,653 @@
+//! Public API extraction from tree-sitter syntax trees. A signature is the
+//! source of a declaration up to its body, collapsed onto one line.
+
+use crate::error::Error;
+use crate::extraction::{one_line, ApiItem, ItemKind};
+use crate::types;
+use tree_sitter::{Node, Parser};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Grammar {
+    Rust,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Python,
+    Go,
+}
+
+impl Grammar {
+    fn language(&self) -> tree_sitter::Language {
+        match self {
+            Grammar::Rust => tree_sitter_rust::language(),
+            Grammar::JavaScript => tree_sitter_javascript::language(),
+            Grammar::TypeScript => tree_sitter_typescript::language_typescript(),
+            Grammar::Tsx => tree_sitter_typescript::language_tsx(),
+            Grammar::Python => tree_sitter_python::language(),
+            Grammar::Go => tree_sitter_go::language(),
+        }
+    }
+}
+
+pub fn extract(grammar: Grammar, source: &str) -> types::Result<Vec<ApiItem>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(grammar.language())
+        .map_err(|e| Error::extraction(&format!("Couldn't load the {:?} grammar.", grammar), e))?;
+    let tree = match parser.parse(source, None) {
+        Some(tree) => tree,
+        None => return Ok(Vec::new()),
+    };
+    let root = tree.root_node();
+    let mut items = Vec::new();
+    match grammar {
+        Grammar::Rust => rust_items(root, source, "", &mut items),
+        Grammar::JavaScript | Grammar::TypeScript | Grammar::Tsx => {
+            javascript_items(root, source, &mut items)
+        }
+        Grammar::Python => python_items(root, source, "", &mut items),
+        Grammar::Go => go_items(root, source, &mut items),
+    }
+    Ok(items)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn field_text<'a>(node: Node, field: &str, source: &'a str) -> Option<&'a str> {
+    node.child_by_field_name(field)
+        .map(|child| text(child, source))
+}
+
+/// The declaration up to the start of `body`, or all of it without a body.
+fn signature(node: Node, body: Option<Node>, source: &str) -> String {
+    let end = body
+        .map(|body| body.start_byte())
+        .unwrap_or_else(|| node.end_byte());
+    let declaration = source.get(node.start_byte()..end).unwrap_or("");
+    one_line(declaration)
+        .trim_end_matches(|c: char| c == '{' || c == ';' || c == ':' || c.is_whitespace())
+        .to_string()
+}
+
+fn named_children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor).collect()
+}
+
+fn push(items: &mut Vec<ApiItem>, kind: ItemKind, name: String, signature: String) {
+    items.push(ApiItem {
+        kind,
+        name,
+        signature,
+    });
+}
+
+fn is_rust_public(node: Node, source: &str) -> bool {
+    named_children(node)
+        .into_iter()
+        .any(|child| child.kind() == "visibility_modifier" && text(child, source) == "pub")
+}
+
+/// Bodies that are cut off the signature. Tuple struct fields are kept.
+fn rust_body(node: Node) -> Option<Node> {
+    node.child_by_field_name("body").filter(|body| {
+        matches!(
+            body.kind(),
+            "block" | "declaration_list" | "field_declaration_list" | "enum_variant_list"
+        )
+    })
+}
+
+fn rust_items(node: Node, source: &str, prefix: &str, items: &mut Vec<ApiItem>) {
+    for child in named_children(node) {
+        let kind = match child.kind() {
+            "function_item" | "function_signature_item" => Some(ItemKind::Function),
+            "struct_item" | "enum_item" | "type_item" | "union_item" => Some(ItemKind::Type),
+            "trait_item" => Some(ItemKind::Trait),
+            "mod_item" => Some(ItemKind::Module),
+            "impl_item" => {
+                // Methods of inherent impls, trait methods are covered by the trait.
+                if child.child_by_field_name("trait").is_none() {
+                    if let (Some(type_name), Some(body)) = (
+                        field_text(child, "type", source),
+                        child.child_by_field_name("body"),
+                    ) {
+                        rust_items(body, source, &format!("{}{}::", prefix, type_name), items);
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        let kind = match kind {
+            Some(kind) if is_rust_public(child, source) => kind,
+            _ => continue,
+        };
+        let name = format!(
+            "{}{}",
+            prefix,
+            field_text(child, "name", source).unwrap_or_default()
+        );
+        push(
+            items,
+            kind,
+            name.clone(),
+            signature(child, rust_body(child), source),
+        );
+        if kind == ItemKind::Module {
+            if let Some(body) = child.child_by_field_name("body") {
+                rust_items(body, source, &format!("{}::", name), items);
+            }
+        }
+    }
+}
+
+/// Methods of an exported class, private and protected ones left out.
+fn javascript_class_members(class_name: &str, body: Node, source: &str, items: &mut Vec<ApiItem>) {
+    for member in named_children(body) {
+        if !matches!(
+            member.kind(),
+            "method_definition" | "method_signature" | "abstract_method_signature"
+        ) {
+            continue;
+        }
+        let hidden = named_children(member).into_iter().any(|child| {
+            child.kind() == "accessibility_modifier" && text(child, source) != "public"
+        });
+        let name = match member.child_by_field_name("name") {
+            Some(name) if name.kind() != "private_property_identifier" && !hidden => {
+                text(name, source)
+            }
+            _ => continue,
+        };
+        push(
+            items,
+            ItemKind::Function,
+            format!("{}.{}", class_name, name),
+            signature(member, member.child_by_field_name("body"), source),
+        );
+    }
+}
+
+/// Anonymous default exports like `export default function () {}`, named
+/// `default` since that is how they are imported.
+fn javascript_default_export(export: Node, value: Node, source: &str, items: &mut Vec<ApiItem>) {
+    let kind = match value.kind() {
+        "function" | "function_expression" | "arrow_function" | "generator_function" => {
+            ItemKind::Function
+        }
+        "class" => ItemKind::Type,
+        _ => return,
+    };
+    let body = value.child_by_field_name("body");
+    push(
+        items,
+        kind,
+        "default".to_string(),
+        signature(export, body, source)
+            .trim_end_matches("=>")
+            .trim_end()
+            .to_string(),
+    );
+    if let (ItemKind::Type, Some(body)) = (kind, body) {
+        javascript_class_members("default", body, source, items);
+    }
+}
+
+fn javascript_items(node: Node, source: &str, items: &mut Vec<ApiItem>) {
+    for child in named_children(node) {
+        if child.kind() != "export_statement" {
+            continue;
+        }
+        let declaration = match child.child_by_field_name("declaration") {
+            Some(declaration) => declaration,
+            None => {
+                if let Some(value) = child.child_by_field_name("value") {
+                    javascript_default_export(child, value, source, items);
+                }
+                continue;
+            }
+        };
+        let kind = match declaration.kind() {
+            "function_declaration" | "generator_function_declaration" | "function_signature" => {
+                ItemKind::Function
+            }
+            "class_declaration"
+            | "abstract_class_declaration"
+            | "type_alias_declaration"
+            | "enum_declaration" => ItemKind::Type,
+            "interface_declaration" => ItemKind::Trait,
+            "internal_module" | "module" => ItemKind::Module,
+            "lexical_declaration" => {
+                // Only exported constants that hold functions are part of the API.
+                for declarator in named_children(declaration) {
+                    let value = match declarator.child_by_field_name("value") {
+                        Some(value) if matches!(value.kind(), "arrow_function" | "function") => {
+                            value
+                        }
+                        _ => continue,
+                    };
+                    let name = field_text(declarator, "name", source).unwrap_or_default();
+                    let declaration_signature =
+                        signature(declarator, value.child_by_field_name("body"), source);
+                    push(
+                        items,
+                        ItemKind::Function,
+                        name.to_string(),
+                        format!("export const {}", declaration_signature)
+                            .trim_end_matches("=>")
+                            .trim_end()
+                            .to_string(),
+                    );
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let name = field_text(declaration, "name", source).unwrap_or_default();
+        let body = declaration.child_by_field_name("body");
+        push(
+            items,
+            kind,
+            name.to_string(),
+            signature(child, body, source),
+        );
+        if let Some(body) = body.filter(|body| body.kind() == "class_body") {
+            javascript_class_members(name, body, source, items);
+        }
+    }
+}
+
+fn python_items(node: Node, source: &str, prefix: &str, items: &mut Vec<ApiItem>) {
+    for child in named_children(node) {
+        let definition = if child.kind() == "decorated_definition" {
+            match child.child_by_field_name("definition") {
+                Some(definition) => definition,
+                None => continue,
+            }
+        } else {
+            child
+        };
+        let kind = match definition.kind() {
+            "function_definition" => ItemKind::Function,
+            "class_definition" => ItemKind::Type,
+            _ => continue,
+        };
+        let name = field_text(definition, "name", source).unwrap_or_default();
+        if name.starts_with('_') {
+            continue;
+        }
+        let body = definition.child_by_field_name("body");
+        push(
+            items,
+            kind,
+            format!("{}{}", prefix, name),
+            signature(definition, body, source),
+        );
+        if let (ItemKind::Type, Some(body)) = (kind, body) {
+            python_items(body, source, &format!("{}{}.", prefix, name), items);
+        }
+    }
+}
+
+fn is_go_exported(name: &str) -> bool {
+    name.chars().next().map(char::is_uppercase).unwrap_or(false)
+}
+
+/// The type name of a method receiver, without pointer or type parameters.
+fn go_receiver_type<'a>(receiver: Node, source: &'a str) -> Option<&'a str> {
+    let mut node = named_children(receiver)
+        .into_iter()
+        .find(|parameter| parameter.kind() == "parameter_declaration")?
+        .child_by_field_name("type")?;
+    loop {
+        node = match node.kind() {
+            "pointer_type" => named_children(node).into_iter().next()?,
+            "generic_type" => node.child_by_field_name("type")?,
+            "type_identifier" => return Some(text(node, source)),
+            _ => return None,
+        };
+    }
+}
+
+fn go_items(node: Node, source: &str, items: &mut Vec<ApiItem>) {
+    for child in named_children(node) {
+        match child.kind() {
+            "function_declaration" | "method_declaration" => {
+                let name = field_text(child, "name", source).unwrap_or_default();
+                // Methods are listed with their type, like `Client.Close`.
+                let receiver = match child.child_by_field_name("receiver") {
+                    Some(receiver) => match go_receiver_type(receiver, source) {
+                        Some(receiver) if is_go_exported(receiver) => Some(receiver),
+                        _ => continue,
+                    },
+                    None => None,
+                };
+                if is_go_exported(name) {
+                    push(
+                        items,
+                        ItemKind::Function,
+                        match receiver {
+                            Some(receiver) => format!("{}.{}", receiver, name),
+                            None => name.to_string(),
+                        },
+                        signature(child, child.child_by_field_name("body"), source),
+                    );
+                }
+            }
+            "type_declaration" => {
+                for spec in named_children(child) {
+                    let name = field_text(spec, "name", source).unwrap_or_default();
+                    if spec.kind() != "type_spec" || !is_go_exported(name) {
+                        continue;
+                    }
+                    let type_node = spec.child_by_field_name("type");
+                    let (kind, body) = match type_node.map(|t| t.kind()) {
+                        Some("interface_type") => (ItemKind::Trait, type_node),
+                        Some("struct_type") => (ItemKind::Type, type_node),
+                        _ => (ItemKind::Type, None),
+                    };
+                    let mut spec_signature = format!("type {}", signature(spec, body, source));
+                    if let Some(body) = body {
+                        // Keep `struct` or `interface`, drop the field list.
+                        spec_signature = format!(
+                            "{} {}",
+                            spec_signature,
+                            body.kind().trim_end_matches("_type")
+                        );
+                    }
+                    push(items, kind, name.to_string(), spec_signature);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signatures(grammar: Grammar, source: &str) -> Vec<(ItemKind, String, String)> {
+        extract(grammar, source)
+            .unwrap()
+            .into_iter()
+            .map(|item| (item.kind, item.name, item.signature))
+            .collect()
+    }
+
+    #[test]
+    fn rust_lists_public_items_and_inherent_methods() {
+        let source = r#"
+pub struct Point(pub i32, pub i32);
+struct Hidden;
+pub trait Shape { fn area(&self) -> f64; }
+impl Point {
+    pub fn new(
+        x: i32,
+        y: i32,
+    ) -> Point { Point(x, y) }
+    fn private(&self) {}
+}
+impl Shape for Point { fn area(&self) -> f64 { 0.0 } }
+pub mod geometry { pub fn origin() -> i32 { 0 } }
+"#;
+        assert_eq!(
+            signatures(Grammar::Rust, source),
+            vec![
+                (
+                    ItemKind::Type,
+                    "Point".to_string(),
+                    "pub struct Point(pub i32, pub i32)".to_string()
+                ),
+                (
+                    ItemKind::Trait,
+                    "Shape".to_string(),
+                    "pub trait Shape".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Point::new".to_string(),
+                    "pub fn new(x: i32, y: i32) -> Point".to_string()
+                ),
+                (
+                    ItemKind::Module,
+                    "geometry".to_string(),
+                    "pub mod geometry".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "geometry::origin".to_string(),
+                    "pub fn origin() -> i32".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn typescript_lists_exports_only() {
+        let source = r#"
+export function run(args: string[]): number { return 0; }
+function hidden() {}
+export interface Options { verbose: boolean }
+export const handler = (event: Event) => { return event; };
+export const VERSION = "1.0";
+"#;
+        assert_eq!(
+            signatures(Grammar::TypeScript, source),
+            vec![
+                (
+                    ItemKind::Function,
+                    "run".to_string(),
+                    "export function run(args: string[]): number".to_string()
+                ),
+                (
+                    ItemKind::Trait,
+                    "Options".to_string(),
+                    "export interface Options".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "handler".to_string(),
+                    "export const handler = (event: Event)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn python_skips_private_names() {
+        let source = r#"
+class Client:
+    def send(self, body):
+        pass
+    def _retry(self):
+        pass
+
+@cache
+def load(path: str) -> str:
+    return path
+
+def _helper():
+    pass
+"#;
+        assert_eq!(
+            signatures(Grammar::Python, source),
+            vec![
+                (
+                    ItemKind::Type,
+                    "Client".to_string(),
+                    "class Client".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Client.send".to_string(),
+                    "def send(self, body)".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "load".to_string(),
+                    "def load(path: str) -> str".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn go_lists_exported_names() {
+        let source = r#"
+package api
+
+type Client struct { url string }
+type reader interface { Read() }
+type Handler interface { Handle() error }
+type ID string
+
+func New(url string) *Client { return &Client{url} }
+func helper() {}
+func (c *Client) Close() error { return nil }
+func (l List[T]) Len() int { return 0 }
+func (r reader) Next() {}
+"#;
+        assert_eq!(
+            signatures(Grammar::Go, source),
+            vec![
+                (
+                    ItemKind::Type,
+                    "Client".to_string(),
+                    "type Client struct".to_string()
+                ),
+                (
+                    ItemKind::Trait,
+                    "Handler".to_string(),
+                    "type Handler interface".to_string()
+                ),
+                (
+                    ItemKind::Type,
+                    "ID".to_string(),
+                    "type ID string".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "New".to_string(),
+                    "func New(url string) *Client".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Client.Close".to_string(),
+                    "func (c *Client) Close() error".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "List.Len".to_string(),
+                    "func (l List[T]) Len() int".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_lists_public_class_methods() {
+        let source = r#"
+export class Client {
+    constructor(url) { this.url = url; }
+    static connect(url) { return new Client(url); }
+    #reset() {}
+}
+class Hidden { run() {} }
+"#;
+        assert_eq!(
+            signatures(Grammar::JavaScript, source),
+            vec![
+                (
+                    ItemKind::Type,
+                    "Client".to_string(),
+                    "export class Client".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Client.constructor".to_string(),
+                    "constructor(url)".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Client.connect".to_string(),
+                    "static connect(url)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn typescript_leaves_out_private_and_protected_methods() {
+        let source = r#"
+export abstract class Store {
+    private load(): void {}
+    protected save() {}
+    public get(key: string): string { return key; }
+    abstract clear(): void;
+}
+"#;
+        assert_eq!(
+            signatures(Grammar::TypeScript, source),
+            vec![
+                (
+                    ItemKind::Type,
+                    "Store".to_string(),
+                    "export abstract class Store".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Store.get".to_string(),
+                    "public get(key: string): string".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "Store.clear".to_string(),
+                    "abstract clear(): void".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn javascript_lists_default_exports() {
+        let named = "export default function render(page) { return page; }";
+        assert_eq!(
+            signatures(Grammar::JavaScript, named),
+            vec![(
+                ItemKind::Function,
+                "render".to_string(),
+                "export default function render(page)".to_string()
+            )]
+        );
+        let anonymous = "export default (page, options) => page;";
+        assert_eq!(
+            signatures(Grammar::JavaScript, anonymous),
+            vec![(
+                ItemKind::Function,
+                "default".to_string(),
+                "export default (page, options)".to_string()
+            )]
+        );
+        let class = "export default class { render() {} }";
+        assert_eq!(
+            signatures(Grammar::JavaScript, class),
+            vec![
+                (
+                    ItemKind::Type,
+                    "default".to_string(),
+                    "export default class".to_string()
+                ),
+                (
+                    ItemKind::Function,
+                    "default.render".to_string(),
+                    "render()".to_string()
+                ),
+            ]
+        );
+        assert!(signatures(Grammar::JavaScript, "export default config;").is_empty());
+    }
+}
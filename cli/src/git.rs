@@ -0,0 +1,469 @@
+//! Reads the local git repository with libgit2, so that neither a git
+//! executable nor a hosted service is needed.
+
+use crate::error::Error;
+use crate::types;
+use std::path;
+
+/// A commit of a release, without merge commits.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub id: String,
+    pub message: String,
+}
+
+impl Commit {
+    /// The first line of the message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("").trim()
+    }
+
+    /// The message after the first line.
+    pub fn body(&self) -> &str {
+        self.message
+            .split_once('\n')
+            .map(|(_, body)| body.trim())
+            .unwrap_or("")
+    }
+}
+
+/// The commits between a tag and the tag before it. Commits after the last
+/// tag form a release without a tag.
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub tag: Option<String>,
+    pub previous_tag: Option<String>,
+    /// Commit date of the tagged commit, `YYYY-MM-DD` in UTC.
+    pub date: Option<String>,
+    pub commits: Vec<Commit>,
+}
+
+/// What the changelog needs from a repository.
+#[derive(Debug, Clone)]
+pub struct History {
+    pub workdir: path::PathBuf,
+    pub origin_url: Option<String>,
+    /// Newest first, unreleased commits on top.
+    pub releases: Vec<Release>,
+}
+
+/// A file in the index and what `.gitattributes` says about it.
+#[derive(Debug, Clone)]
+pub struct TrackedFile {
+    /// Relative to the directory the files were listed from, like `./src/main.rs`.
+    pub path: path::PathBuf,
+    pub vendored: bool,
+    pub generated: bool,
+    pub documentation: bool,
+}
+
+fn read_error(e: git2::Error) -> Error {
+    Error::git("Couldn't read the git history.", e)
+}
+
+/// The civil date of a Unix timestamp, after Howard Hinnant's
+/// `civil_from_days`.
+fn date(seconds: i64) -> String {
+    let days = seconds.div_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Tags that point to commits, oldest first.
+fn tags(repository: &git2::Repository) -> types::Result<Vec<(String, git2::Commit<'_>)>> {
+    let names = repository.tag_names(None).map_err(read_error)?;
+    let mut tags: Vec<(String, git2::Commit<'_>)> = names
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            repository
+                .revparse_single(&format!("refs/tags/{}", name))
+                .and_then(|object| object.peel_to_commit())
+                .ok()
+                .map(|commit| (name.to_string(), commit))
+        })
+        .collect();
+    tags.sort_by(|(a_name, a), (b_name, b)| {
+        a.time()
+            .seconds()
+            .cmp(&b.time().seconds())
+            .then_with(|| a_name.cmp(b_name))
+    });
+    Ok(tags)
+}
+
+/// Commits reachable from `from` but not from `to`, like `git log to..from`.
+fn commits_between(
+    repository: &git2::Repository,
+    from: git2::Oid,
+    to: Option<git2::Oid>,
+) -> types::Result<Vec<Commit>> {
+    let mut walk = repository.revwalk().map_err(read_error)?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+        .map_err(read_error)?;
+    walk.push(from).map_err(read_error)?;
+    if let Some(to) = to {
+        walk.hide(to).map_err(read_error)?;
+    }
+    let mut commits = Vec::new();
+    for id in walk {
+        let commit = repository
+            .find_commit(id.map_err(read_error)?)
+            .map_err(read_error)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        commits.push(Commit {
+            id: commit.id().to_string(),
+            message: commit.message().unwrap_or("").to_string(),
+        });
+    }
+    Ok(commits)
+}
+
+fn remote_url(repository: &git2::Repository, name: &str) -> Option<String> {
+    repository
+        .find_remote(name)
+        .ok()
+        .and_then(|remote| remote.url().map(|url| url.to_string()))
+}
+
+/// The URL of the `origin` remote of the repository the current directory
+/// is in, also from a subdirectory or a worktree, and with `insteadOf`
+/// rewrites applied.
+pub fn origin_url() -> Option<String> {
+    git2::Repository::discover(".")
+        .ok()
+        .and_then(|repository| remote_url(&repository, "origin"))
+}
+
+/// Reads the releases of the repository `directory` is in.
+pub fn history(directory: &path::Path) -> types::Result<History> {
+    let repository = git2::Repository::discover(directory).map_err(|e| {
+        Error::config_caused_by(
+            "Couldn't open the git repository.",
+            Some("Run skriptorium inside a git repository."),
+            e,
+        )
+    })?;
+    let workdir = repository
+        .workdir()
+        .map(|workdir| workdir.to_path_buf())
+        .ok_or_else(|| {
+            Error::config(
+                "The git repository has no working directory.",
+                Some("Run skriptorium in a checkout, not in a bare repository."),
+            )
+        })?;
+    let origin_url = remote_url(&repository, "origin");
+
+    let tags = tags(&repository)?;
+    let mut releases = Vec::new();
+    let mut previous: Option<(&String, git2::Oid)> = None;
+    for (name, commit) in tags.iter() {
+        releases.push(Release {
+            tag: Some(name.clone()),
+            previous_tag: previous.map(|(name, _)| name.clone()),
+            date: Some(date(commit.time().seconds())),
+            commits: commits_between(&repository, commit.id(), previous.map(|(_, id)| id))?,
+        });
+        previous = Some((name, commit.id()));
+    }
+    // A repository without commits has no HEAD and nothing unreleased.
+    if let Ok(head) = repository.head().and_then(|head| head.peel_to_commit()) {
+        let commits = commits_between(&repository, head.id(), previous.map(|(_, id)| id))?;
+        if !commits.is_empty() {
+            releases.push(Release {
+                tag: None,
+                previous_tag: previous.map(|(name, _)| name.clone()),
+                date: None,
+                commits,
+            });
+        }
+    }
+    releases.reverse();
+    Ok(History {
+        workdir,
+        origin_url,
+        releases,
+    })
+}
+
+pub async fn history_async(directory: path::PathBuf) -> types::Result<History> {
+    let handle = tokio::task::spawn_blocking(move || history(&directory));
+    handle.await?
+}
+
+/// Whether a linguist attribute is set, as in `linguist-vendored` or
+/// `linguist-vendored=true`.
+fn is_set(repository: &git2::Repository, file: &path::Path, attribute: &str) -> bool {
+    let value = repository
+        .get_attr(file, attribute, git2::AttrCheckFlags::FILE_THEN_INDEX)
+        .ok()
+        .flatten();
+    match git2::AttrValue::from_string(value) {
+        git2::AttrValue::True => true,
+        git2::AttrValue::String(value) => value == "true",
+        _ => false,
+    }
+}
+
+/// The files in the index below `directory`, relative to it, or `None`
+/// outside a git repository. Files that are tracked but missing in the
+/// working directory are left out.
+pub fn tracked_files(directory: &path::Path) -> types::Result<Option<Vec<TrackedFile>>> {
+    let repository = match git2::Repository::discover(directory) {
+        Ok(repository) => repository,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(Error::git("Couldn't open the git repository.", e)),
+    };
+    let workdir = match repository.workdir() {
+        Some(workdir) => workdir.canonicalize().map_err(|e| Error::io(workdir, e))?,
+        None => return Ok(None),
+    };
+    let start = directory
+        .canonicalize()
+        .map_err(|e| Error::io(directory, e))?;
+    let prefix = start.strip_prefix(&workdir).unwrap_or(path::Path::new(""));
+
+    let index = repository
+        .index()
+        .map_err(|e| Error::git("Couldn't read the git index.", e))?;
+    let mut files = Vec::new();
+    for entry in index.iter() {
+        let file = match std::str::from_utf8(&entry.path) {
+            Ok(file) => path::Path::new(file),
+            Err(_) => continue,
+        };
+        let relative = match file.strip_prefix(prefix) {
+            Ok(relative) => relative,
+            Err(_) => continue,
+        };
+        let tracked = path::Path::new(".").join(relative);
+        // Conflicted files are in the index once per stage.
+        let repeated = files
+            .last()
+            .map(|last: &TrackedFile| last.path == tracked)
+            .unwrap_or(false);
+        if repeated || !workdir.join(file).is_file() {
+            continue;
+        }
+        files.push(TrackedFile {
+            path: tracked,
+            vendored: is_set(&repository, file, "linguist-vendored"),
+            generated: is_set(&repository, file, "linguist-generated"),
+            documentation: is_set(&repository, file, "linguist-documentation"),
+        });
+    }
+    Ok(Some(files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the files into the working directory and adds them to the index.
+    fn track(repository: &git2::Repository, files: &[(&str, &str)]) {
+        let workdir = repository.workdir().unwrap();
+        let mut index = repository.index().unwrap();
+        for (file, content) in files.iter() {
+            let path = workdir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            index.add_path(path::Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+    }
+
+    fn commit(repository: &git2::Repository, message: &str, seconds: i64) -> git2::Oid {
+        let tree_id = repository.index().unwrap().write_tree().unwrap();
+        let tree = repository.find_tree(tree_id).unwrap();
+        let signature =
+            git2::Signature::new("Author", "author@example.com", &git2::Time::new(seconds, 0))
+                .unwrap();
+        let parent = repository
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn remote_url_reads_the_named_remote() {
+        let directory = tempfile::tempdir().unwrap();
+        let repository = git2::Repository::init(directory.path()).unwrap();
+        assert_eq!(remote_url(&repository, "origin"), None);
+        repository
+            .remote("origin", "git@github.com:owner/repo.git")
+            .unwrap();
+        repository
+            .remote("fork", "https://github.com/fork/repo")
+            .unwrap();
+        assert_eq!(
+            remote_url(&repository, "origin").as_deref(),
+            Some("git@github.com:owner/repo.git")
+        );
+    }
+
+    #[test]
+    fn date_converts_timestamps_to_civil_dates() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(86_399), "1970-01-01");
+        assert_eq!(date(-1), "1969-12-31");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_709_251_199), "2024-02-29");
+        assert_eq!(date(1_709_251_200), "2024-03-01");
+    }
+
+    #[test]
+    fn is_set_reads_linguist_attributes() {
+        let directory = tempfile::tempdir().unwrap();
+        let repository = git2::Repository::init(directory.path()).unwrap();
+        std::fs::write(
+            directory.path().join(".gitattributes"),
+            "vendor/** linguist-vendored\n\
+             *.pb.rs linguist-generated=true\n\
+             docs/** linguist-documentation=false\n",
+        )
+        .unwrap();
+        let vendored = path::Path::new("vendor/lib.rs");
+        assert!(is_set(&repository, vendored, "linguist-vendored"));
+        assert!(!is_set(&repository, vendored, "linguist-generated"));
+        assert!(is_set(
+            &repository,
+            path::Path::new("src/api.pb.rs"),
+            "linguist-generated"
+        ));
+        assert!(!is_set(
+            &repository,
+            path::Path::new("docs/guide.md"),
+            "linguist-documentation"
+        ));
+        assert!(!is_set(
+            &repository,
+            path::Path::new("src/main.rs"),
+            "linguist-vendored"
+        ));
+    }
+
+    #[test]
+    fn history_splits_commits_at_tags() {
+        let directory = tempfile::tempdir().unwrap();
+        let repository = git2::Repository::init(directory.path()).unwrap();
+        track(&repository, &[("a.rs", "a")]);
+        let first = commit(&repository, "feat: add a", 86_400);
+        repository
+            .tag_lightweight(
+                "v0.1.0",
+                &repository.find_object(first, None).unwrap(),
+                false,
+            )
+            .unwrap();
+        track(&repository, &[("b.rs", "b")]);
+        commit(&repository, "fix: repair b\n\nDetails.", 2 * 86_400);
+
+        let history = history(&directory.path().join(".")).unwrap();
+        let releases: Vec<(Option<&str>, Option<&str>, Vec<&str>)> = history
+            .releases
+            .iter()
+            .map(|release| {
+                (
+                    release.tag.as_deref(),
+                    release.date.as_deref(),
+                    release.commits.iter().map(Commit::summary).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            releases,
+            vec![
+                (None, None, vec!["fix: repair b"]),
+                (Some("v0.1.0"), Some("1970-01-02"), vec!["feat: add a"]),
+            ]
+        );
+        assert_eq!(history.releases[0].previous_tag.as_deref(), Some("v0.1.0"));
+    }
+
+    #[test]
+    fn tracked_files_come_from_the_index() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        let repository = git2::Repository::init(root).unwrap();
+        track(
+            &repository,
+            &[
+                ("README.md", "# cli"),
+                ("conflicted.rs", "<<<<<<<"),
+                ("deleted.rs", ""),
+                ("src/cmd/scribe.rs", ""),
+                ("src/main.rs", ""),
+            ],
+        );
+        std::fs::write(root.join("scratch.txt"), "untracked").unwrap();
+        std::fs::remove_file(root.join("deleted.rs")).unwrap();
+        // A conflicted file is in the index once per stage.
+        let mut index = repository.index().unwrap();
+        let conflicted = path::Path::new("conflicted.rs");
+        let entry = index.get_path(conflicted, 0).unwrap();
+        index.remove_path(conflicted).unwrap();
+        for stage in 1..=3 {
+            index
+                .add(&git2::IndexEntry {
+                    ctime: entry.ctime,
+                    mtime: entry.mtime,
+                    dev: entry.dev,
+                    ino: entry.ino,
+                    mode: entry.mode,
+                    uid: entry.uid,
+                    gid: entry.gid,
+                    file_size: entry.file_size,
+                    id: entry.id,
+                    flags: entry.flags | (stage << 12),
+                    flags_extended: entry.flags_extended,
+                    path: entry.path.clone(),
+                })
+                .unwrap();
+        }
+        index.write().unwrap();
+
+        let paths = |start: &path::Path| -> Vec<String> {
+            tracked_files(start)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|file| file.path.to_string_lossy().to_string())
+                .collect()
+        };
+        assert_eq!(
+            paths(root),
+            vec![
+                "./README.md",
+                "./conflicted.rs",
+                "./src/cmd/scribe.rs",
+                "./src/main.rs"
+            ]
+        );
+        assert_eq!(
+            paths(&root.join("src")),
+            vec!["./cmd/scribe.rs", "./main.rs"]
+        );
+    }
+}
@@ -1,10 +1,12 @@
 extern crate tensorflow;
 
+use crate::detection;
+use crate::detection::ClassificationResult;
+use crate::error::Error;
 use crate::types;
 use futures::future::join_all;
 use std::collections::HashMap;
 use std::env;
-use std::fmt;
 use std::hash::Hash;
 use std::path;
 use tensorflow::Code;
@@ -15,20 +17,15 @@ use tensorflow::Status;
 use tensorflow::Tensor;
 use tokio::fs;
 
-#[derive(Debug)]
-pub enum ClassificationError {
-    ClassificationFailed,
-}
+pub const DEFAULT_MODEL_URL: &str =
+    "https://raw.githubusercontent.com/sigma-andex/guesslang/master/guesslang/data/";
 
-impl std::error::Error for ClassificationError {}
-
-impl fmt::Display for ClassificationError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ClassificationError::ClassificationFailed => write!(f, "Classification failed."),
-        }
-    }
-}
+pub const MODEL_FILES: [&str; 4] = [
+    "languages.json",
+    "model/saved_model.pb",
+    "model/variables/variables.index",
+    "model/variables/variables.data-00000-of-00001",
+];
 
 pub struct GuessLangSettings {
     bundle: tensorflow::SavedModelBundle,
@@ -63,11 +60,6 @@ fn load_model(
     Ok((bundle, graph))
 }
 
-pub struct ClassificationResult {
-    pub identifier: String,
-    pub score: f32,
-}
-
 pub fn classify(
     guess_lang_settings: &GuessLangSettings,
     snippet: String,
@@ -103,24 +95,13 @@ pub fn classify(
         .map(|(abbr, score)| (abbr.to_string(), score.clone()))
         .collect();
 
-    let sorted_results = sort_classifications(&results);
+    let sorted_results = detection::sort_classifications(&results);
 
     Ok(sorted_results)
 }
 
-fn sort_classifications(classifications: &Vec<(String, f32)>) -> Vec<ClassificationResult> {
-    let mut mapped: Vec<ClassificationResult> = classifications
-        .iter()
-        .map(|(identifier, score)| ClassificationResult {
-            identifier: identifier.to_string(),
-            score: score.clone(),
-        })
-        .collect();
-    mapped.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-    mapped
-}
-
 pub async fn load_settings(path: path::PathBuf) -> types::Result<GuessLangSettings> {
-    let (bundle, graph) = load_model(path)?;
+    let (bundle, graph) =
+        load_model(path).map_err(|e| Error::model("guesslang model couldn't be loaded.", e))?;
     Ok(GuessLangSettings { bundle, graph })
 }
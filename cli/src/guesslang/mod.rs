@@ -1,2 +1,5 @@
+#[cfg(feature = "guesslang")]
 pub mod classification;
 pub mod model_downloader;
+#[cfg(feature = "onnx")]
+pub mod onnx;
@@ -1,29 +1,16 @@
 use crate::dirs;
+use crate::error::Error;
+use crate::http;
 use crate::types;
-use std::error;
-use std::fmt;
+use console::style;
+use console::Emoji;
+use indicatif::ProgressBar;
+use indicatif::ProgressStyle;
 use std::fs;
-use std::io;
 use std::path;
+use tokio::io::AsyncWriteExt;
 
-#[derive(Debug)]
-pub enum DownloadError {
-    FileCreationFailed(path::PathBuf),
-    DownloadError(io::Error),
-}
-
-impl error::Error for DownloadError {}
-
-impl fmt::Display for DownloadError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            DownloadError::FileCreationFailed(path) => {
-                write!(f, "Failed to create path {:?}", path.to_str())
-            }
-            DownloadError::DownloadError(err) => write!(f, "Failed to download file"),
-        }
-    }
-}
+static DOWNLOAD: Emoji<'_, '_> = Emoji("📦", "D");
 
 pub fn get_models_guesslang_path() -> types::Result<path::PathBuf> {
     let mut models_guesslang_dir_buf = dirs::get_data_dir()?;
@@ -33,81 +20,332 @@ pub fn get_models_guesslang_path() -> types::Result<path::PathBuf> {
 
     let models_guesslang_dir = models_guesslang_dir_buf.as_path();
     if !&models_guesslang_dir_buf.exists() {
-        fs::create_dir_all(&models_guesslang_dir)?;
+        fs::create_dir_all(models_guesslang_dir).map_err(|e| Error::io(models_guesslang_dir, e))?;
     }
 
     Ok(models_guesslang_dir_buf)
 }
 
+fn download_progress_bar(file_name: &str, maybe_length: Option<u64>) -> ProgressBar {
+    match maybe_length {
+        Some(length) => {
+            let pb = ProgressBar::new(length);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template(" {msg:<30} [{bar:30.blue}] {bytes}/{total_bytes} ({eta})")
+                    .progress_chars("=> "),
+            );
+            pb.set_message(file_name.to_string());
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner().template(" {spinner:.blue} {msg:<30} {bytes}"),
+            );
+            pb.set_message(file_name.to_string());
+            pb
+        }
+    }
+}
+
 pub async fn get_or_download_file(
-    base_path: &path::PathBuf,
+    client: &http::HttpClient,
+    base_path: &path::Path,
     file: &path::Path,
     base_url: &reqwest::Url,
     file_url: &str,
 ) -> types::Result<path::PathBuf> {
-    let path_buf = base_path.clone();
+    let path_buf = base_path.to_path_buf();
     let absolute_path = base_path.join(file);
     if absolute_path.exists() {
         Ok(path_buf)
     } else {
-        let absolute_url = base_url.join(file_url)?;
-
-        let response = reqwest::get(absolute_url).await?;
-        let bytes = response.bytes().await?;
-        let mut cursor = io::Cursor::new(bytes);
-
-        let parent_directory = &absolute_path
-            .parent()
-            .ok_or(DownloadError::FileCreationFailed(absolute_path.clone()))?;
-        fs::create_dir_all(parent_directory)?;
-
-        let mut dest = fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(absolute_path)?;
-        io::copy(&mut cursor, &mut dest)?;
+        let absolute_url = base_url.join(file_url).map_err(|e| {
+            Error::config_caused_by(&format!("Invalid model file URL {}.", file_url), None, e)
+        })?;
+
+        let mut response = client
+            .send(|| client.get(absolute_url.clone()))
+            .await?
+            .error_for_status()?;
+
+        if let Some(parent_directory) = absolute_path.parent() {
+            fs::create_dir_all(parent_directory).map_err(|e| Error::io(parent_directory, e))?;
+        }
+
+        // Stream into a partial file first so that an interrupted download
+        // isn't mistaken for a complete one on the next run.
+        let mut partial_path = absolute_path.clone().into_os_string();
+        partial_path.push(".part");
+        let mut dest = tokio::fs::File::create(&partial_path)
+            .await
+            .map_err(|e| Error::io(&partial_path, e))?;
+
+        let file_name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(file_url);
+        let pb = download_progress_bar(file_name, response.content_length());
+        let streamed: types::Result<()> = async {
+            while let Some(chunk) = response.chunk().await? {
+                dest.write_all(&chunk)
+                    .await
+                    .map_err(|e| Error::io(&partial_path, e))?;
+                pb.inc(chunk.len() as u64);
+            }
+            dest.flush().await.map_err(|e| Error::io(&partial_path, e))
+        }
+        .await;
+        if let Err(err) = streamed {
+            // Don't leave the partial file behind, the next run starts over.
+            pb.abandon();
+            drop(dest);
+            let _ = tokio::fs::remove_file(&partial_path).await;
+            return Err(err);
+        }
+        pb.finish();
+
+        tokio::fs::rename(&partial_path, &absolute_path)
+            .await
+            .map_err(|e| Error::io(&absolute_path, e))?;
         Ok(path_buf)
     }
 }
 
-pub async fn retrieve_model() -> types::Result<path::PathBuf> {
+/// The model base URL, with a trailing slash so that relative file paths
+/// are joined below it rather than replacing its last segment.
+fn model_base_url(model_url: &str) -> types::Result<reqwest::Url> {
+    let url = if model_url.ends_with('/') {
+        model_url.to_string()
+    } else {
+        format!("{}/", model_url)
+    };
+    reqwest::Url::parse(&url).map_err(|e| {
+        Error::config_caused_by(
+            &format!("Invalid model URL {}.", url),
+            Some("--model-url expects an absolute http(s) URL or a local folder."),
+            e,
+        )
+    })
+}
+
+fn copy_model_files(
+    source: &path::Path,
+    destination: &path::Path,
+    model_files: &[&str],
+) -> types::Result<()> {
+    for model_file in model_files.iter() {
+        let target = destination.join(model_file);
+        if target.exists() {
+            continue;
+        }
+        if let Some(parent_directory) = target.parent() {
+            fs::create_dir_all(parent_directory).map_err(|e| Error::io(parent_directory, e))?;
+        }
+        let origin = source.join(model_file);
+        fs::copy(&origin, &target).map_err(|e| Error::io(&origin, e))?;
+    }
+    Ok(())
+}
+
+/// Makes sure `model_files` are in the data directory, downloading missing
+/// ones below `model_url` or copying them if it is a local folder.
+pub async fn retrieve_model(
+    client: &http::HttpClient,
+    model_url: &str,
+    model_files: &[&str],
+) -> types::Result<path::PathBuf> {
     let models_guesslang_dir_buf = get_models_guesslang_path()?;
 
-    let base_url = reqwest::Url::parse(
-        "https://raw.githubusercontent.com/sigma-andex/guesslang/master/guesslang/data/",
-    )?;
-    let languages_file = get_or_download_file(
-        &models_guesslang_dir_buf,
-        path::Path::new("languages.json"),
-        &base_url,
-        "languages.json",
-    )
-    .await?;
-
-    let model_file = get_or_download_file(
-        &models_guesslang_dir_buf,
-        path::Path::new("model/saved_model.pb"),
-        &base_url,
-        "model/saved_model.pb",
-    )
-    .await?;
-
-    let variables_index_file = get_or_download_file(
-        &models_guesslang_dir_buf,
-        path::Path::new("model/variables/variables.index"),
-        &base_url,
-        "model/variables/variables.index",
-    )
-    .await?;
-
-    let variables_data_file = get_or_download_file(
-        &models_guesslang_dir_buf,
-        path::Path::new("model/variables/variables.data-00000-of-00001"),
-        &base_url,
-        "model/variables/variables.data-00000-of-00001",
-    )
-    .await?;
+    let local_folder = path::Path::new(model_url);
+    if local_folder.is_dir() {
+        copy_model_files(local_folder, &models_guesslang_dir_buf, model_files)?;
+        return Ok(models_guesslang_dir_buf);
+    }
+
+    if !is_model_available(&models_guesslang_dir_buf, model_files) {
+        println!(
+            "{}  {}",
+            DOWNLOAD,
+            style("Downloading language model (first run only)...")
+                .dim()
+                .white()
+        );
+    }
+
+    let base_url = model_base_url(model_url)?;
+    for model_file in model_files.iter() {
+        get_or_download_file(
+            client,
+            &models_guesslang_dir_buf,
+            path::Path::new(model_file),
+            &base_url,
+            model_file,
+        )
+        .await?;
+    }
 
     Ok(models_guesslang_dir_buf)
 }
+
+/// The data directory if `model_files` were retrieved by an earlier run.
+#[cfg_attr(not(feature = "onnx"), allow(dead_code))]
+pub fn cached_model(model_files: &[&str]) -> types::Result<Option<path::PathBuf>> {
+    let models_guesslang_dir_buf = get_models_guesslang_path()?;
+    if is_model_available(&models_guesslang_dir_buf, model_files) {
+        Ok(Some(models_guesslang_dir_buf))
+    } else {
+        Ok(None)
+    }
+}
+
+fn is_model_available(models_path: &path::Path, model_files: &[&str]) -> bool {
+    model_files
+        .iter()
+        .all(|model_file| models_path.join(model_file).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::stub::{self, Reply};
+
+    const LAYOUT: [(&str, &[u8]); 3] = [
+        ("model/saved_model.pb", b"saved model"),
+        ("model/variables/variables.index", b"index"),
+        ("model/variables/variables.data-00000-of-00001", b"weights"),
+    ];
+
+    fn serve_layout(path: &str, _: usize) -> Reply {
+        LAYOUT
+            .iter()
+            .find(|(file, _)| path == format!("/models/{}", file))
+            .map(|(_, content)| Reply::ok(content))
+            .unwrap_or_else(|| Reply::status(404))
+    }
+
+    async fn download(
+        client: &http::HttpClient,
+        base_path: &path::Path,
+        base_url: &reqwest::Url,
+        file: &str,
+    ) -> types::Result<path::PathBuf> {
+        get_or_download_file(client, base_path, path::Path::new(file), base_url, file).await
+    }
+
+    fn part_file(path: &path::Path) -> path::PathBuf {
+        let mut partial_path = path.to_path_buf().into_os_string();
+        partial_path.push(".part");
+        path::PathBuf::from(partial_path)
+    }
+
+    #[tokio::test]
+    async fn downloads_the_model_layout() {
+        let server = stub::serve(serve_layout).await;
+        let dir = tempfile::tempdir().unwrap();
+        // A leftover from an interrupted run is overwritten.
+        fs::create_dir_all(dir.path().join("model")).unwrap();
+        fs::write(part_file(&dir.path().join(LAYOUT[0].0)), "stale").unwrap();
+
+        let base_url = model_base_url(&format!("{}models", server.url)).unwrap();
+        let client = stub::client(0);
+        for (file, _) in LAYOUT.iter() {
+            download(&client, dir.path(), &base_url, file)
+                .await
+                .unwrap();
+        }
+
+        for (file, content) in LAYOUT.iter() {
+            let path = dir.path().join(file);
+            assert_eq!(fs::read(&path).unwrap(), content.to_vec());
+            assert!(!part_file(&path).exists());
+        }
+        assert!(is_model_available(
+            dir.path(),
+            &LAYOUT.iter().map(|(file, _)| *file).collect::<Vec<_>>()
+        ));
+    }
+
+    #[tokio::test]
+    async fn skips_existing_files() {
+        let server = stub::serve(serve_layout).await;
+        let dir = tempfile::tempdir().unwrap();
+        let (file, _) = LAYOUT[0];
+        fs::create_dir_all(dir.path().join("model")).unwrap();
+        fs::write(dir.path().join(file), "cached").unwrap();
+
+        let base_url = model_base_url(&format!("{}models/", server.url)).unwrap();
+        download(&stub::client(0), dir.path(), &base_url, file)
+            .await
+            .unwrap();
+
+        assert!(server.requests().is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join(file)).unwrap(), "cached");
+    }
+
+    #[tokio::test]
+    async fn removes_the_partial_file_after_a_failed_download() {
+        let server = stub::serve(|_, _| Reply::Truncated {
+            length: 1024,
+            body: b"only a part".to_vec(),
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let (file, _) = LAYOUT[0];
+
+        let base_url = model_base_url(&server.url).unwrap();
+        let result = download(&stub::client(0), dir.path(), &base_url, file).await;
+
+        assert!(result.is_err());
+        let path = dir.path().join(file);
+        assert!(!path.exists());
+        assert!(!part_file(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_files_without_writing_anything() {
+        let server = stub::serve(serve_layout).await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let base_url = model_base_url(&server.url).unwrap();
+        let result = download(&stub::client(0), dir.path(), &base_url, "model/missing.pb").await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Http {
+                status: Some(404),
+                ..
+            })
+        ));
+        assert!(!dir.path().join("model/missing.pb").exists());
+        assert!(!part_file(&dir.path().join("model/missing.pb")).exists());
+    }
+
+    #[test]
+    fn copies_the_model_from_a_local_folder() {
+        let source = tempfile::tempdir().unwrap();
+        let destination = tempfile::tempdir().unwrap();
+        for (file, content) in LAYOUT.iter() {
+            let path = source.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let files: Vec<&str> = LAYOUT.iter().map(|(file, _)| *file).collect();
+
+        copy_model_files(source.path(), destination.path(), &files).unwrap();
+
+        assert!(is_model_available(destination.path(), &files));
+        assert!(copy_model_files(source.path(), destination.path(), &["missing.pb"]).is_err());
+    }
+
+    #[test]
+    fn appends_a_trailing_slash_to_the_model_url() {
+        let base_url = model_base_url("https://mirror.example.com/guesslang").unwrap();
+        assert_eq!(
+            base_url.join("model/saved_model.pb").unwrap().as_str(),
+            "https://mirror.example.com/guesslang/model/saved_model.pb"
+        );
+        assert!(model_base_url("not a url").is_err());
+    }
+}
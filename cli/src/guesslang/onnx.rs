@@ -0,0 +1,234 @@
+//! Runs the guesslang model exported to ONNX with tract.
+//!
+//! The TensorFlow graph tokenizes the raw snippet with string ops that ONNX
+//! cannot express, so the export starts right after the feature columns and
+//! the tokenization is replicated here. `scripts/export_guesslang_onnx.py`
+//! writes the model together with `onnx/preprocessing.json`, which carries
+//! the hyper parameters and the label vocabulary in the order of the
+//! `scores` output. The exported model isn't hosted anywhere, so it has to be
+//! passed once with `--model-url`.
+
+use crate::detection;
+use crate::detection::ClassificationResult;
+use crate::error::Error;
+use crate::types;
+use serde::Deserialize;
+use std::fs;
+use std::path;
+use tract_onnx::prelude::*;
+
+pub const MODEL_FILES: [&str; 2] = ["onnx/model.onnx", "onnx/preprocessing.json"];
+
+/// Mirrors `_preprocess_text` and the hashed `content` feature column of the
+/// guesslang estimator.
+#[derive(Deserialize, Debug)]
+pub struct Preprocessing {
+    /// Label vocabulary, in the order of the model's `scores` output.
+    pub classes: Vec<String>,
+    /// Width of the byte n-grams (`tf.strings.ngrams`).
+    pub ngram_width: usize,
+    /// Number of n-grams kept per snippet (`NB_TOKENS`).
+    pub max_tokens: usize,
+    /// Size of the hash bucket (`VOCABULARY_SIZE`).
+    pub buckets: usize,
+}
+
+pub struct OnnxSettings {
+    model: TypedRunnableModel<TypedModel>,
+    preprocessing: Preprocessing,
+}
+
+/// Counts the hashed byte n-grams of `snippet`, which is the dense
+/// equivalent of the sparse input the estimator's linear and embedding
+/// columns consume.
+fn token_counts(preprocessing: &Preprocessing, snippet: &str) -> Vec<f32> {
+    let mut counts = vec![0f32; preprocessing.buckets];
+    let bytes = snippet.as_bytes();
+    let width = preprocessing.ngram_width;
+    if bytes.len() < width {
+        return counts;
+    }
+    for window in bytes.windows(width).take(preprocessing.max_tokens) {
+        // `tf.strings.ngrams` joins the single-byte tokens with a space.
+        let mut ngram: Vec<u8> = Vec::with_capacity(width * 2);
+        for (i, byte) in window.iter().enumerate() {
+            if i > 0 {
+                ngram.push(b' ');
+            }
+            ngram.push(*byte);
+        }
+        // Same hash as `tf.strings.to_hash_bucket_fast`.
+        let bucket = farmhash::fingerprint64(&ngram) % preprocessing.buckets as u64;
+        counts[bucket as usize] += 1.0;
+    }
+    counts
+}
+
+fn load_model(
+    path: path::PathBuf,
+) -> types::Result<(TypedRunnableModel<TypedModel>, Preprocessing)> {
+    let preprocessing_path = path.join("onnx/preprocessing.json");
+    let preprocessing_json =
+        fs::read_to_string(&preprocessing_path).map_err(|e| Error::io(&preprocessing_path, e))?;
+    let preprocessing: Preprocessing = serde_json::from_str(&preprocessing_json)
+        .map_err(|e| Error::model("ONNX preprocessing settings are invalid.", e))?;
+
+    let model = tract_onnx::onnx()
+        .model_for_path(path.join("onnx/model.onnx"))
+        .and_then(|model| model.with_input_fact(0, f32::fact([1, preprocessing.buckets]).into()))
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(|e| Error::model("ONNX model couldn't be loaded.", e))?;
+    Ok((model, preprocessing))
+}
+
+pub fn classify(
+    onnx_settings: &OnnxSettings,
+    snippet: &str,
+) -> types::Result<Vec<ClassificationResult>> {
+    let OnnxSettings {
+        model,
+        preprocessing,
+    } = onnx_settings;
+
+    let counts = token_counts(preprocessing, snippet);
+    let input: Tensor = tract_ndarray::Array2::from_shape_vec((1, preprocessing.buckets), counts)
+        .map_err(|e| Error::detection("Couldn't build the model input.", e))?
+        .into();
+    let outputs = model
+        .run(tvec!(input.into()))
+        .map_err(|e| Error::detection("ONNX inference failed.", e))?;
+    let scores = outputs[0]
+        .to_array_view::<f32>()
+        .map_err(|e| Error::detection("ONNX model returned unexpected scores.", e))?;
+
+    if scores.len() != preprocessing.classes.len() {
+        return Err(Error::Model {
+            message: format!(
+                "Model returned {} scores but {} classes are known.",
+                scores.len(),
+                preprocessing.classes.len()
+            ),
+            source: None,
+        });
+    }
+
+    let results: Vec<(String, f32)> = preprocessing
+        .classes
+        .iter()
+        .zip(scores.iter())
+        .map(|(abbr, score)| (abbr.to_string(), *score))
+        .collect();
+
+    Ok(detection::sort_classifications(&results))
+}
+
+pub async fn load_settings(path: path::PathBuf) -> types::Result<OnnxSettings> {
+    let (model, preprocessing) = load_model(path)?;
+    Ok(OnnxSettings {
+        model,
+        preprocessing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::env;
+
+    fn preprocessing(max_tokens: usize) -> Preprocessing {
+        Preprocessing {
+            classes: Vec::new(),
+            ngram_width: 2,
+            max_tokens,
+            buckets: 16,
+        }
+    }
+
+    fn bucket(ngram: &[u8]) -> usize {
+        (farmhash::fingerprint64(ngram) % 16) as usize
+    }
+
+    #[test]
+    fn counts_space_joined_byte_ngrams() {
+        let counts = token_counts(&preprocessing(10), "abab");
+        assert_eq!(counts.iter().sum::<f32>(), 3.0);
+        assert!(counts[bucket(b"a b")] >= 2.0);
+        assert!(counts[bucket(b"b a")] >= 1.0);
+    }
+
+    #[test]
+    fn keeps_only_the_first_tokens() {
+        let counts = token_counts(&preprocessing(3), "abcdefgh");
+        assert_eq!(counts.iter().sum::<f32>(), 3.0);
+    }
+
+    #[test]
+    fn has_no_tokens_for_snippets_shorter_than_an_ngram() {
+        assert!(token_counts(&preprocessing(10), "a")
+            .iter()
+            .all(|c| *c == 0.0));
+    }
+
+    fn corpus() -> Vec<path::PathBuf> {
+        let corpus = path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/guesslang");
+        let mut files: Vec<path::PathBuf> = fs::read_dir(corpus)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn top_1(results: &[ClassificationResult]) -> String {
+        results.first().unwrap().identifier.clone()
+    }
+
+    /// The folder written by `scripts/export_guesslang_onnx.py`.
+    fn export_dir() -> path::PathBuf {
+        env::var("SKRIPTORIUM_ONNX_EXPORT")
+            .map(path::PathBuf::from)
+            .expect(
+                "SKRIPTORIUM_ONNX_EXPORT should point to the output of export_guesslang_onnx.py",
+            )
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a model exported by scripts/export_guesslang_onnx.py"]
+    async fn reproduces_the_tensorflow_top_1_on_the_fixture_corpus() {
+        let export_dir = export_dir();
+        let settings = load_settings(export_dir.clone()).await.unwrap();
+        let parity_path = export_dir.join("onnx/parity.json");
+        let expected: BTreeMap<String, String> =
+            serde_json::from_str(&fs::read_to_string(parity_path).unwrap()).unwrap();
+
+        let mut actual = BTreeMap::new();
+        for file in corpus() {
+            let snippet = fs::read_to_string(&file).unwrap();
+            let name = file.file_name().unwrap().to_string_lossy().to_string();
+            actual.insert(name, top_1(&classify(&settings, &snippet).unwrap()));
+        }
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "guesslang")]
+    #[tokio::test]
+    #[ignore = "needs libtensorflow, the downloaded guesslang model and a model exported by scripts/export_guesslang_onnx.py"]
+    async fn matches_the_tensorflow_backend_on_the_fixture_corpus() {
+        use crate::guesslang::{classification, model_downloader};
+
+        let onnx_settings = load_settings(export_dir()).await.unwrap();
+        let tensorflow_settings =
+            classification::load_settings(model_downloader::get_models_guesslang_path().unwrap())
+                .await
+                .unwrap();
+        for file in corpus() {
+            let snippet = fs::read_to_string(&file).unwrap();
+            let tensorflow =
+                classification::classify(&tensorflow_settings, snippet.clone()).unwrap();
+            let onnx = classify(&onnx_settings, &snippet).unwrap();
+            assert_eq!(top_1(&onnx), top_1(&tensorflow), "{}", file.display());
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use crate::error::Error;
+use crate::types;
+use log::info;
+use rand::Rng;
+use std::fs;
+use std::path;
+use std::time;
+
+const PEM_CERTIFICATE_END: &str = "-----END CERTIFICATE-----";
+
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Longest `Retry-After` we are willing to wait for before giving up.
+const MAX_RETRY_AFTER: time::Duration = time::Duration::from_secs(120);
+
+/// Network settings shared by the model downloader and the API client.
+/// Proxies from `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` are honored by reqwest
+/// unless an explicit proxy is given.
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<path::PathBuf>,
+    pub retry_policy: RetryPolicy,
+}
+
+impl HttpSettings {
+    pub fn from_matches(matches: &clap::ArgMatches) -> types::Result<HttpSettings> {
+        let max_retries = match matches.value_of("retries") {
+            Some(retries) => retries.parse::<u32>().map_err(|e| {
+                Error::config_caused_by(
+                    &format!("Invalid number of retries {}.", retries),
+                    Some("--retries expects a non-negative number."),
+                    e,
+                )
+            })?,
+            None => DEFAULT_RETRIES,
+        };
+        Ok(HttpSettings {
+            proxy: matches.value_of("proxy").map(|p| p.to_string()),
+            ca_bundle: matches.value_of("ca-bundle").map(path::PathBuf::from),
+            retry_policy: RetryPolicy {
+                max_retries,
+                ..RetryPolicy::default()
+            },
+        })
+    }
+}
+
+/// Exponential backoff with full jitter, applied to connection errors,
+/// reset connections, timeouts, `429 Too Many Requests` and `5xx` responses.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: time::Duration,
+    pub max_delay: time::Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_RETRIES,
+            base_delay: time::Duration::from_millis(500),
+            max_delay: time::Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> time::Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0..=exponential.as_millis() as u64);
+        time::Duration::from_millis(jitter)
+    }
+}
+
+/// A reqwest client together with the retry policy all requests share.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    retry_policy: RetryPolicy,
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Whether a connection broke off mid-request, e.g. reset by a proxy or a
+/// restarting server.
+fn is_connection_lost(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_error.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+fn is_retryable_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || is_connection_lost(error)
+}
+
+/// Parses `Retry-After` given either in seconds or as an HTTP date.
+fn retry_after(response: &reqwest::Response) -> Option<time::Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    value
+        .parse::<u64>()
+        .ok()
+        .map(time::Duration::from_secs)
+        .or_else(|| {
+            httpdate::parse_http_date(value).ok().map(|date| {
+                date.duration_since(time::SystemTime::now())
+                    .unwrap_or_default()
+            })
+        })
+}
+
+impl HttpClient {
+    #[cfg_attr(not(any(feature = "guesslang", feature = "onnx")), allow(dead_code))]
+    pub fn get(&self, url: reqwest::Url) -> reqwest::RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Sends the request built by `make_request`, retrying it according to
+    /// the retry policy. The last response is returned as is, so callers
+    /// still need to check its status.
+    pub async fn send<F>(&self, make_request: F) -> types::Result<reqwest::Response>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let result = make_request().send().await;
+            let delay = match &result {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    match retry_after(response) {
+                        Some(delay) if delay > MAX_RETRY_AFTER => return Ok(result?),
+                        Some(delay) => delay,
+                        None => self.retry_policy.backoff(attempt),
+                    }
+                }
+                Err(err) if is_retryable_error(err) => self.retry_policy.backoff(attempt),
+                _ => return Ok(result?),
+            };
+            if attempt >= self.retry_policy.max_retries {
+                return Ok(result?);
+            }
+            attempt += 1;
+            info!(
+                "Retrying request in {:?} (attempt {} of {})",
+                delay, attempt, self.retry_policy.max_retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn read_ca_bundle(ca_bundle: &path::Path) -> types::Result<Vec<reqwest::Certificate>> {
+    let pem = fs::read_to_string(ca_bundle).map_err(|e| Error::io(ca_bundle, e))?;
+    let certificates: Vec<reqwest::Certificate> = pem
+        .split_inclusive(PEM_CERTIFICATE_END)
+        .filter(|block| block.contains(PEM_CERTIFICATE_END))
+        .map(|block| reqwest::Certificate::from_pem(block.trim().as_bytes()))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| {
+            Error::config_caused_by(
+                &format!("CA bundle {} is invalid.", ca_bundle.display()),
+                Some("--ca-bundle expects a file with PEM encoded certificates."),
+                e,
+            )
+        })?;
+    if certificates.is_empty() {
+        Err(Error::config(
+            &format!(
+                "CA bundle {} contains no certificates.",
+                ca_bundle.display()
+            ),
+            Some("--ca-bundle expects a file with PEM encoded certificates."),
+        ))
+    } else {
+        Ok(certificates)
+    }
+}
+
+pub fn build_client(settings: &HttpSettings) -> types::Result<HttpClient> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = &settings.proxy {
+        let proxy = reqwest::Proxy::all(proxy.as_str()).map_err(|e| {
+            Error::config_caused_by(
+                &format!("Proxy {} is invalid.", proxy),
+                Some("--proxy expects a URL such as http://proxy.example.com:3128."),
+                e,
+            )
+        })?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(ca_bundle) = &settings.ca_bundle {
+        for certificate in read_ca_bundle(ca_bundle.as_path())? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    let client = builder
+        .build()
+        .map_err(|e| Error::config_caused_by("Couldn't set up the HTTP client.", None, e))?;
+    Ok(HttpClient {
+        client,
+        retry_policy: settings.retry_policy.clone(),
+    })
+}
+
+/// A minimal HTTP/1.1 server on a local port for tests. Every connection
+/// serves one request and is closed afterwards.
+#[cfg(test)]
+pub mod stub {
+    use std::sync::{Arc, Mutex};
+    use std::time;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    pub enum Reply {
+        Response {
+            status: u16,
+            headers: Vec<(String, String)>,
+            body: Vec<u8>,
+        },
+        /// Announces `length` bytes but closes the connection after `body`.
+        #[cfg_attr(not(any(feature = "guesslang", feature = "onnx")), allow(dead_code))]
+        Truncated { length: usize, body: Vec<u8> },
+        /// Resets the connection without answering.
+        Reset,
+    }
+
+    impl Reply {
+        pub fn status(status: u16) -> Reply {
+            Reply::Response {
+                status,
+                headers: Vec::new(),
+                body: Vec::new(),
+            }
+        }
+
+        pub fn ok(body: &[u8]) -> Reply {
+            Reply::Response {
+                status: 200,
+                headers: Vec::new(),
+                body: body.to_vec(),
+            }
+        }
+
+        pub fn with_header(self, name: &str, value: &str) -> Reply {
+            match self {
+                Reply::Response {
+                    status,
+                    mut headers,
+                    body,
+                } => {
+                    headers.push((name.to_string(), value.to_string()));
+                    Reply::Response {
+                        status,
+                        headers,
+                        body,
+                    }
+                }
+                other => other,
+            }
+        }
+    }
+
+    /// A client that talks to the stub directly, whatever proxy the
+    /// environment configures, and retries without waiting.
+    pub fn client(max_retries: u32) -> super::HttpClient {
+        super::HttpClient {
+            client: reqwest::Client::builder().no_proxy().build().unwrap(),
+            retry_policy: super::RetryPolicy {
+                max_retries,
+                base_delay: time::Duration::from_millis(1),
+                max_delay: time::Duration::from_millis(10),
+            },
+        }
+    }
+
+    pub struct StubServer {
+        pub url: String,
+        /// The paths of the requests received so far.
+        pub requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StubServer {
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    /// Serves the reply `handler` gives for the path and the index of each
+    /// request.
+    pub async fn serve<F>(handler: F) -> StubServer
+    where
+        F: Fn(&str, usize) -> Reply + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let mut stream = stream;
+                    if let Some(path) = read_request(&mut stream).await {
+                        let index = {
+                            let mut received = received.lock().unwrap();
+                            received.push(path.clone());
+                            received.len() - 1
+                        };
+                        write_reply(stream, handler(&path, index)).await;
+                    }
+                });
+            }
+        });
+        StubServer { url, requests }
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> Option<String> {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        let head_end = loop {
+            if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let read = stream.read(&mut buffer).await.ok()?;
+            if read == 0 {
+                return None;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        };
+        let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+        // Read the body as well, closing a socket with unread data resets it.
+        let content_length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        while request.len() < head_end + content_length {
+            let read = stream.read(&mut buffer).await.ok()?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        head.split_whitespace().nth(1).map(|path| path.to_string())
+    }
+
+    async fn write_reply(mut stream: TcpStream, reply: Reply) {
+        let (head, body) = match reply {
+            Reply::Response {
+                status,
+                headers,
+                body,
+            } => {
+                let mut head = format!(
+                    "HTTP/1.1 {} Stub\r\ncontent-length: {}\r\nconnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                for (name, value) in headers {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                (head, body)
+            }
+            Reply::Truncated { length, body } => (
+                format!(
+                    "HTTP/1.1 200 Stub\r\ncontent-length: {}\r\nconnection: close\r\n",
+                    length
+                ),
+                body,
+            ),
+            Reply::Reset => {
+                let _ = stream.set_linger(Some(time::Duration::ZERO));
+                return;
+            }
+        };
+        let _ = stream.write_all(format!("{}\r\n", head).as_bytes()).await;
+        let _ = stream.write_all(&body).await;
+        let _ = stream.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stub::{self, Reply};
+    use super::*;
+
+    fn url(server: &stub::StubServer) -> reqwest::Url {
+        reqwest::Url::parse(&server.url)
+            .unwrap()
+            .join("api")
+            .unwrap()
+    }
+
+    async fn get(client: &HttpClient, url: &reqwest::Url) -> types::Result<reqwest::Response> {
+        client.send(|| client.get(url.clone())).await
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = stub::serve(|_, index| match index {
+            0 | 1 => Reply::status(503),
+            _ => Reply::ok(b"done"),
+        })
+        .await;
+
+        let response = get(&stub::client(3), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_response_when_retries_are_exhausted() {
+        let server = stub::serve(|_, _| Reply::status(500)).await;
+
+        let response = get(&stub::client(2), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 500);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_reset_connections() {
+        let server = stub::serve(|_, index| match index {
+            0 => Reply::Reset,
+            _ => Reply::ok(b"done"),
+        })
+        .await;
+
+        let response = get(&stub::client(1), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let server = stub::serve(|_, _| Reply::status(404)).await;
+
+        let response = get(&stub::client(3), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn waits_for_retry_after_in_seconds() {
+        let server = stub::serve(|_, index| match index {
+            0 => Reply::status(429).with_header("Retry-After", "1"),
+            _ => Reply::ok(b"done"),
+        })
+        .await;
+
+        let started = time::Instant::now();
+        let response = get(&stub::client(1), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(started.elapsed() >= time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn waits_for_retry_after_as_http_date() {
+        let server = stub::serve(|_, index| match index {
+            0 => {
+                let date = time::SystemTime::now() + time::Duration::from_secs(2);
+                Reply::status(503).with_header("Retry-After", &httpdate::fmt_http_date(date))
+            }
+            _ => Reply::ok(b"done"),
+        })
+        .await;
+
+        let started = time::Instant::now();
+        let response = get(&stub::client(1), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        // HTTP dates have a resolution of one second.
+        assert!(started.elapsed() >= time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_retry_after_exceeds_the_cap() {
+        let server = stub::serve(|_, _| {
+            Reply::status(503)
+                .with_header("Retry-After", &(MAX_RETRY_AFTER.as_secs() + 1).to_string())
+        })
+        .await;
+
+        let started = time::Instant::now();
+        let response = get(&stub::client(3), &url(&server)).await.unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(server.requests().len(), 1);
+        assert!(started.elapsed() < time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn caps_the_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: time::Duration::from_millis(100),
+            max_delay: time::Duration::from_secs(1),
+        };
+        for attempt in 0..10 {
+            assert!(policy.backoff(attempt) <= time::Duration::from_secs(1));
+        }
+    }
+}
@@ -2,15 +2,21 @@ extern crate clap;
 
 mod api;
 mod cmd;
+mod detection;
 mod dirs;
+mod error;
+mod extraction;
+mod git;
+#[cfg(any(feature = "guesslang", feature = "onnx"))]
 mod guesslang;
+mod http;
 mod types;
 use console::style;
 
+use crate::cmd::changelog;
 use crate::cmd::scribe;
 
 use clap::{crate_version, App, AppSettings, Arg, SubCommand};
-use tokio;
 
 use types::Result;
 
@@ -18,17 +24,28 @@ use human_panic::setup_panic;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    setup_panic!(Metadata {
-        name: env!("CARGO_PKG_NAME").into(),
-        version: env!("CARGO_PKG_VERSION").into(),
-        authors: "sigma-andex".into(),
-        homepage: "https://github.com/sigma-andex/skriptorium".into(),
-    });
+    // human-panic 1.x still names the pre-1.81 `PanicInfo` alias.
+    #[allow(deprecated)]
+    {
+        setup_panic!(Metadata {
+            name: env!("CARGO_PKG_NAME").into(),
+            version: env!("CARGO_PKG_VERSION").into(),
+            authors: "sigma-andex".into(),
+            homepage: "https://github.com/sigma-andex/skriptorium".into(),
+        });
+    }
 
     let matches = App::new(format!("{}", style("skriptorium").bold()))
         .version(crate_version!())
         .about("\n...your little helper to write the boring documentation for you!\nSkriptorium analyses your code repo to generate documentation automatically using deep learning (Guesslang, OpenAI and NLPCloud).")
         .setting(AppSettings::ArgRequiredElseHelp)
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .global(true)
+                .help("Prints the full chain of causes when an error occurs"),
+        )
         .subcommand(
             SubCommand::with_name("scribe")
                 .about("generate documentation for the INPUT folder")
@@ -37,21 +54,233 @@ async fn main() -> Result<()> {
                         .help("The input folder to use")
                         .default_value(".")
                         .index(1),
+                )
+                .arg(
+                    Arg::with_name("model-url")
+                        .long("model-url")
+                        .env("SKRIPTORIUM_MODEL_URL")
+                        .takes_value(true)
+                        .value_name("URL")
+                        .help("Base URL or local folder of the guesslang model files, e.g. an internal mirror. Required on the first run with the onnx feature, see cli/scripts/export_guesslang_onnx.py (guesslang and onnx features only)"),
+                )
+                .args(&http_args())
+                .arg(
+                    Arg::with_name("git-tracked")
+                        .long("git-tracked")
+                        .help("Lists the files in the git index instead of walking the folder, honoring linguist-vendored, linguist-generated and linguist-documentation in .gitattributes"),
+                )
+                .arg(
+                    Arg::with_name("max-bytes")
+                        .long("max-bytes")
+                        .env("SKRIPTORIUM_MAX_BYTES")
+                        .takes_value(true)
+                        .value_name("BYTES")
+                        .conflicts_with("max-tokens")
+                        .help("Payload budget for the classification request in bytes (default: 262144)"),
+                )
+                .arg(
+                    Arg::with_name("max-tokens")
+                        .long("max-tokens")
+                        .env("SKRIPTORIUM_MAX_TOKENS")
+                        .takes_value(true)
+                        .value_name("TOKENS")
+                        .help("Payload budget for the classification request in estimated tokens"),
+                )
+                .arg(
+                    Arg::with_name("map-reduce")
+                        .long("map-reduce")
+                        .help("Summarizes the repo directory by directory within the payload budget before classifying the summaries"),
+                )
+                .arg(
+                    Arg::with_name("local-selection")
+                        .long("local-selection")
+                        .help("Selects files by the local ranking only instead of asking the server"),
+                )
+                .arg(
+                    Arg::with_name("api-reference")
+                        .long("api-reference")
+                        .help("Extracts the public API to add an API reference to the README and send it as context"),
+                )
+                .arg(
+                    Arg::with_name("architecture")
+                        .long("architecture")
+                        .help("Adds an Architecture section with a Mermaid graph of the modules and their imports"),
+                )
+                .arg(
+                    Arg::with_name("architecture-depth")
+                        .long("architecture-depth")
+                        .takes_value(true)
+                        .value_name("DEPTH")
+                        .requires("architecture")
+                        .help("Collapses modules deeper than DEPTH into their ancestor in the architecture graph"),
+                )
+                .arg(
+                    Arg::with_name("exclude-tests")
+                        .long("exclude-tests")
+                        .requires("architecture")
+                        .help("Leaves test modules out of the architecture graph"),
+                )
+                .arg(
+                    Arg::with_name("structure")
+                        .long("structure")
+                        .help("Adds a Project structure section with a tree of the directories and key files"),
+                )
+                .arg(
+                    Arg::with_name("structure-depth")
+                        .long("structure-depth")
+                        .takes_value(true)
+                        .value_name("DEPTH")
+                        .requires("structure")
+                        .help("Deepest directory level shown in the project structure (default: 2)"),
+                )
+                .arg(
+                    Arg::with_name("per-module")
+                        .long("per-module")
+                        .help("Also writes a page per module to docs/modules and links them from the README"),
+                )
+                .arg(
+                    Arg::with_name("badges")
+                        .long("badges")
+                        .takes_value(true)
+                        .value_name("LIST")
+                        .help("Badges to render, in order: version, license, language, ci, package, docs, coverage (default: all that apply)"),
+                )
+                .arg(
+                    Arg::with_name("published")
+                        .long("published")
+                        .help("The package is published: adds the crates.io, npm or PyPI badge and the docs.rs badge"),
+                )
+                .arg(
+                    Arg::with_name("badge-style")
+                        .long("badge-style")
+                        .takes_value(true)
+                        .value_name("STYLE")
+                        .help("Style of the shields.io badges: flat, flat-square, plastic, for-the-badge or social"),
+                )
+                .arg(
+                    Arg::with_name("offline-badges")
+                        .long("offline-badges")
+                        .help("Draws the badges into docs/badges instead of linking to shields.io"),
+                )
+                .arg(
+                    Arg::with_name("locale")
+                        .long("locale")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("CODE")
+                        .help("Also writes the README in this language: de, es or ja, e.g. to docs/README.de.md (repeatable)"),
+                )
+                .arg(
+                    Arg::with_name("toc-depth")
+                        .long("toc-depth")
+                        .takes_value(true)
+                        .value_name("LEVEL")
+                        .help("Deepest heading level in the table of contents, 1 leaves it out (default: 3)"),
+                )
+                .arg(
+                    Arg::with_name("candidates")
+                        .long("candidates")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Asks for N alternatives per section and keeps the best by a local quality score, or lets you pick with --interactive"),
+                )
+                .arg(
+                    Arg::with_name("interactive")
+                        .long("interactive")
+                        .conflicts_with("replay")
+                        .help("Reviews each generated section before the README is written: accept, edit in $EDITOR, regenerate or drop it. The choices are kept for the next run"),
+                )
+                .arg(
+                    Arg::with_name("explain-selection")
+                        .long("explain-selection")
+                        .help("Prints how the files were ranked and why, then exits"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Seed for the file shuffle, recorded in docs/skriptorium-run.json (default: random)"),
+                )
+                .arg(
+                    Arg::with_name("replay")
+                        .long("replay")
+                        .takes_value(true)
+                        .value_name("MANIFEST")
+                        .conflicts_with("seed")
+                        .help("Sends the requests recorded in a run manifest again and regenerates the README from them"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("changelog")
+                .about("writes or updates CHANGELOG.md from the local git history")
+                .arg(
+                    Arg::with_name("summarize")
+                        .long("summarize")
+                        .help("Adds a short summary written by the backend to each release"),
+                )
+                .arg(
+                    Arg::with_name("conventional-only")
+                        .long("conventional-only")
+                        .help("Leaves out commits that don't follow conventional commits"),
+                )
+                .args(&http_args()),
+        )
         .subcommand(
             SubCommand::with_name("watch")
                 .about("watches for file changes to generate a new documentation"),
         )
         .get_matches();
 
-    if let Some(matches) = matches.subcommand_matches("scribe") {
-        let result = scribe::scribe(matches).await;
+    if let Some(sub_matches) = matches.subcommand_matches("scribe") {
+        let verbose = matches.is_present("verbose") || sub_matches.is_present("verbose");
+        let result = scribe::scribe(sub_matches).await;
         match result {
-            Ok(res) => println!("{}", style("\nDone.").dim().white()),
-            Err(err) => println!("{:?}", err),
+            Ok(_res) => println!("{}", style("\nDone.").dim().white()),
+            Err(err) => {
+                eprintln!("\n{}", style(error::report(&err, verbose)).red());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("changelog") {
+        let verbose = matches.is_present("verbose") || sub_matches.is_present("verbose");
+        let result = changelog::changelog(sub_matches).await;
+        match result {
+            Ok(_res) => println!("{}", style("\nDone.").dim().white()),
+            Err(err) => {
+                eprintln!("\n{}", style(error::report(&err, verbose)).red());
+                std::process::exit(1);
+            }
         }
     }
 
     Ok(())
 }
+
+/// Arguments for the requests to the backend, shared by all subcommands that
+/// make any.
+fn http_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("proxy")
+            .long("proxy")
+            .env("SKRIPTORIUM_PROXY")
+            .takes_value(true)
+            .value_name("URL")
+            .help("Proxy to use for all requests (defaults to HTTP_PROXY/HTTPS_PROXY)"),
+        Arg::with_name("ca-bundle")
+            .long("ca-bundle")
+            .env("SKRIPTORIUM_CA_BUNDLE")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("PEM file with additional root certificates to trust"),
+        Arg::with_name("retries")
+            .long("retries")
+            .env("SKRIPTORIUM_RETRIES")
+            .takes_value(true)
+            .value_name("N")
+            .help("How often failed requests are retried (default: 3)"),
+    ]
+}
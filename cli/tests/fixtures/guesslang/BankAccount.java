@@ -0,0 +1,40 @@
+package com.example.bank;
+
+import java.math.BigDecimal;
+import java.util.ArrayList;
+import java.util.List;
+
+public class BankAccount {
+    private final String owner;
+    private BigDecimal balance = BigDecimal.ZERO;
+    private final List<String> history = new ArrayList<>();
+
+    public BankAccount(String owner) {
+        this.owner = owner;
+    }
+
+    public void deposit(BigDecimal amount) {
+        if (amount.signum() <= 0) {
+            throw new IllegalArgumentException("Deposits must be positive");
+        }
+        balance = balance.add(amount);
+        history.add("deposit " + amount);
+    }
+
+    public void withdraw(BigDecimal amount) {
+        if (balance.compareTo(amount) < 0) {
+            throw new IllegalStateException("Insufficient funds for " + owner);
+        }
+        balance = balance.subtract(amount);
+        history.add("withdraw " + amount);
+    }
+
+    public BigDecimal getBalance() {
+        return balance;
+    }
+
+    @Override
+    public String toString() {
+        return String.format("%s: %s", owner, balance);
+    }
+}
@@ -0,0 +1,30 @@
+module Queue
+  ( Queue
+  , empty
+  , push
+  , pop
+  , fromList
+  ) where
+
+import Data.List (foldl')
+
+-- | A FIFO queue made of two lists.
+data Queue a = Queue [a] [a]
+  deriving (Show)
+
+empty :: Queue a
+empty = Queue [] []
+
+push :: a -> Queue a -> Queue a
+push x (Queue front back) = Queue front (x : back)
+
+pop :: Queue a -> Maybe (a, Queue a)
+pop (Queue [] []) = Nothing
+pop (Queue [] back) = pop (Queue (reverse back) [])
+pop (Queue (x : front) back) = Just (x, Queue front back)
+
+fromList :: [a] -> Queue a
+fromList = foldl' (flip push) empty
+
+instance Functor Queue where
+  fmap f (Queue front back) = Queue (fmap f front) (fmap f back)
@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SOURCE="${1:?usage: backup.sh SOURCE [DESTINATION]}"
+DESTINATION="${2:-$HOME/backups}"
+STAMP="$(date +%Y-%m-%d_%H-%M-%S)"
+
+mkdir -p "$DESTINATION"
+ARCHIVE="$DESTINATION/$(basename "$SOURCE")-$STAMP.tar.gz"
+
+echo "Backing up $SOURCE to $ARCHIVE"
+tar -czf "$ARCHIVE" -C "$(dirname "$SOURCE")" "$(basename "$SOURCE")"
+
+# Keep the seven most recent archives.
+ls -1t "$DESTINATION"/*.tar.gz | tail -n +8 | while read -r old; do
+    echo "Removing $old"
+    rm -f "$old"
+done
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+/// Memoized Fibonacci numbers.
+pub struct Fibonacci {
+    cache: HashMap<u64, u64>,
+}
+
+impl Fibonacci {
+    pub fn new() -> Self {
+        Fibonacci {
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, n: u64) -> u64 {
+        if n < 2 {
+            return n;
+        }
+        if let Some(value) = self.cache.get(&n) {
+            return *value;
+        }
+        let value = self.get(n - 1) + self.get(n - 2);
+        self.cache.insert(n, value);
+        value
+    }
+}
+
+fn main() {
+    let mut fibonacci = Fibonacci::new();
+    for n in 0..20 {
+        println!("{}: {}", n, fibonacci.get(n));
+    }
+}
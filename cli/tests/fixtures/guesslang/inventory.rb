@@ -0,0 +1,37 @@
+require 'json'
+
+class Inventory
+  include Enumerable
+
+  Item = Struct.new(:name, :quantity, :price) do
+    def total
+      quantity * price
+    end
+  end
+
+  def initialize
+    @items = {}
+  end
+
+  def add(name, quantity:, price:)
+    @items[name] = Item.new(name, quantity, price)
+    self
+  end
+
+  def each(&block)
+    @items.values.each(&block)
+  end
+
+  def value
+    sum(&:total)
+  end
+
+  def to_json(*args)
+    map(&:to_h).to_json(*args)
+  end
+end
+
+inventory = Inventory.new
+inventory.add('apple', quantity: 10, price: 0.5).add('pear', quantity: 4, price: 0.75)
+puts "Total value: #{inventory.value}"
+puts inventory.to_json
@@ -0,0 +1,36 @@
+package main
+
+import (
+	"bufio"
+	"fmt"
+	"os"
+	"sort"
+	"strings"
+)
+
+type entry struct {
+	word  string
+	count int
+}
+
+func main() {
+	counts := make(map[string]int)
+	scanner := bufio.NewScanner(os.Stdin)
+	scanner.Split(bufio.ScanWords)
+	for scanner.Scan() {
+		counts[strings.ToLower(scanner.Text())]++
+	}
+	if err := scanner.Err(); err != nil {
+		fmt.Fprintln(os.Stderr, err)
+		os.Exit(1)
+	}
+
+	entries := make([]entry, 0, len(counts))
+	for word, count := range counts {
+		entries = append(entries, entry{word, count})
+	}
+	sort.Slice(entries, func(i, j int) bool { return entries[i].count > entries[j].count })
+	for _, e := range entries {
+		fmt.Printf("%6d %s\n", e.count, e.word)
+	}
+}
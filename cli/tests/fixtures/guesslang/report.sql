@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS orders (
+    id SERIAL PRIMARY KEY,
+    customer_id INTEGER NOT NULL REFERENCES customers (id),
+    total NUMERIC(10, 2) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS orders_customer_id_idx ON orders (customer_id);
+
+SELECT c.name,
+       COUNT(o.id) AS order_count,
+       SUM(o.total) AS revenue
+FROM customers c
+LEFT JOIN orders o ON o.customer_id = c.id
+WHERE o.created_at >= date_trunc('month', now()) - INTERVAL '1 month'
+GROUP BY c.name
+HAVING SUM(o.total) > 100
+ORDER BY revenue DESC
+LIMIT 20;
@@ -0,0 +1,31 @@
+const http = require('http');
+const url = require('url');
+
+const todos = [];
+
+function sendJson(res, status, body) {
+  res.writeHead(status, { 'Content-Type': 'application/json' });
+  res.end(JSON.stringify(body));
+}
+
+const server = http.createServer((req, res) => {
+  const { pathname } = url.parse(req.url, true);
+  if (pathname === '/todos' && req.method === 'GET') {
+    return sendJson(res, 200, todos);
+  }
+  if (pathname === '/todos' && req.method === 'POST') {
+    let body = '';
+    req.on('data', (chunk) => { body += chunk; });
+    req.on('end', () => {
+      const todo = { id: todos.length + 1, ...JSON.parse(body), done: false };
+      todos.push(todo);
+      sendJson(res, 201, todo);
+    });
+    return;
+  }
+  sendJson(res, 404, { error: 'Not found' });
+});
+
+server.listen(process.env.PORT || 3000, () => {
+  console.log(`Listening on port ${server.address().port}`);
+});
@@ -0,0 +1,36 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+typedef struct {
+    int *items;
+    size_t size;
+    size_t capacity;
+} stack_t;
+
+static void stack_push(stack_t *stack, int item) {
+    if (stack->size == stack->capacity) {
+        stack->capacity = stack->capacity ? stack->capacity * 2 : 8;
+        stack->items = realloc(stack->items, stack->capacity * sizeof(int));
+        if (!stack->items) {
+            perror("realloc");
+            exit(EXIT_FAILURE);
+        }
+    }
+    stack->items[stack->size++] = item;
+}
+
+static int stack_pop(stack_t *stack) {
+    return stack->items[--stack->size];
+}
+
+int main(void) {
+    stack_t stack = {0};
+    for (int i = 0; i < 10; i++) {
+        stack_push(&stack, i * i);
+    }
+    while (stack.size > 0) {
+        printf("%d\n", stack_pop(&stack));
+    }
+    free(stack.items);
+    return 0;
+}
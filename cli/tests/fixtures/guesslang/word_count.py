@@ -0,0 +1,28 @@
+import argparse
+import collections
+import sys
+
+
+def count_words(lines):
+    counter = collections.Counter()
+    for line in lines:
+        for word in line.split():
+            counter[word.lower().strip(".,;:!?")] += 1
+    return counter
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Counts the words of a file.")
+    parser.add_argument("file", nargs="?", default="-")
+    parser.add_argument("--top", type=int, default=10)
+    args = parser.parse_args()
+
+    stream = sys.stdin if args.file == "-" else open(args.file, encoding="utf-8")
+    with stream:
+        counter = count_words(stream)
+    for word, count in counter.most_common(args.top):
+        print(f"{count:>6} {word}")
+
+
+if __name__ == "__main__":
+    main()